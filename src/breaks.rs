@@ -0,0 +1,49 @@
+use crate::time::Timer;
+use chrono::{prelude::*, TimeDelta};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// A short or long break between Pomodoros
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize, JsonSchema)]
+pub struct Break {
+    #[serde(flatten)]
+    timer: Timer,
+    /// What the break is for, like "lunch"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
+impl Break {
+    /// Create a new break
+    pub fn new(starts_at: DateTime<Local>, duration: TimeDelta) -> Self {
+        Timer::new(starts_at, duration).into()
+    }
+
+    /// Get the struct describing the time this break is running
+    pub fn timer(&self) -> &Timer {
+        &self.timer
+    }
+
+    pub(crate) fn timer_mut(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+
+    /// Get the description
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Set the description
+    pub fn set_description(&mut self, description: &str) {
+        self.description = Some(description.to_string());
+    }
+}
+
+impl From<Timer> for Break {
+    fn from(timer: Timer) -> Self {
+        Self {
+            timer,
+            description: None,
+        }
+    }
+}
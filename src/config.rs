@@ -1,14 +1,19 @@
 use std::{
+    collections::BTreeMap,
     fs::read_to_string,
     path::{Path, PathBuf},
 };
 
-use anyhow::{Context, Result};
-use chrono::TimeDelta;
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{prelude::*, TimeDelta};
 use colored::Colorize;
 use directories::ProjectDirs;
-use log::info;
+use log::{info, warn};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use toml_edit::{DocumentMut, Item};
+
+use crate::{Hook, Phase, Pomodoro, PomodoroBuilder, Rotation, StateFormat};
 
 /// Global configuration values
 ///
@@ -25,64 +30,326 @@ use serde::{Deserialize, Serialize};
 ///
 /// The configuration file is written as a TOML file.
 /// See the documentation for each field to learn how they are serialized.
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+///
+/// ## Profiles
+///
+/// A config can also contain named [`Profile`]s, written as `[profiles.<name>]`
+/// tables, that override some of these values. Use [`Config::profile`] to get
+/// the config for a profile.
+#[derive(Clone, PartialEq, Debug, Deserialize, Serialize, JsonSchema)]
 pub struct Config {
     /// Directory to find hook executables
     ///
     /// Default is a directory called `hooks` inside the config directory.
     /// Serialized as an absolute path.
-    #[serde(default = "default_hooks_directory")]
+    #[serde(default = "serde_default_hooks_directory")]
     pub hooks_directory: PathBuf,
     /// File describing the current Pomodoro or break timer
     ///
     /// Default location is the user's state directory,
-    /// which is usually `~/.local/state/tomate/current.toml`.
+    /// which is usually `~/.local/state/tomate/current.toml`,
+    /// or the data directory on platforms without a state directory, like macOS.
     /// Serialized as an absolute path.
-    #[serde(default = "default_state_path")]
+    #[serde(default = "serde_default_state_path")]
     pub state_file_path: PathBuf,
     /// File describing historical Pomodoro or break timers
     ///
     /// Default location is the user's data directory,
     /// which is usually `~/.local/share/tomate/history.toml`.
     /// Serialized as an absolute path.
-    #[serde(default = "default_history_path")]
+    #[serde(default = "serde_default_history_path")]
     pub history_file_path: PathBuf,
+    /// How the history is split across files
+    ///
+    /// Default is `none`, which keeps every entry in `history_file_path`.
+    /// With `monthly`, each month's entries go in their own file next to it,
+    /// like `history-2024-03.toml`, so each file stays quick to read.
+    #[serde(default)]
+    pub history_rotation: Rotation,
+    /// Socket that `tomate serve` streams the current status on
+    ///
+    /// Default location is the user's runtime directory,
+    /// which is usually `/run/user/<uid>/tomate/tomate.sock`,
+    /// or the state directory if there is no runtime directory.
+    /// Serialized as an absolute path.
+    #[serde(default = "serde_default_socket_path")]
+    pub socket_path: PathBuf,
     /// Default duration for Pomodoro timers
     ///
     /// Default is 25 minutes (1500 seconds).
-    /// Serialized as an integer count of seconds.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
     #[serde(
         default = "default_pomodoro_duration",
-        with = "crate::time::duration::seconds"
+        with = "crate::time::duration::human"
     )]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
     pub pomodoro_duration: TimeDelta,
     /// Default duration for short break timers
     ///
     /// Default is 5 minutes (300 seconds).
-    /// Serialized as an integer count of seconds.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
     #[serde(
         default = "default_short_break_duration",
-        with = "crate::time::duration::seconds"
+        with = "crate::time::duration::human"
     )]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
     pub short_break_duration: TimeDelta,
     /// Default duration for long break timers
     ///
     /// Default is 20 minutes (1200 seconds).
-    /// Serialized as an integer count of seconds.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
     #[serde(
         default = "default_long_break_duration",
-        with = "crate::time::duration::seconds"
+        with = "crate::time::duration::human"
     )]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
     pub long_break_duration: TimeDelta,
+    /// Number of Pomodoros to finish before tomate suggests a long break
+    ///
+    /// Default is 4. Set to 0 to never suggest a long break.
+    #[serde(default = "default_poms_before_long_break")]
+    pub poms_before_long_break: usize,
+    /// Whether only today's Pomodoros count toward a long break
+    ///
+    /// If this is on, Pomodoros from before midnight don't count toward
+    /// `poms_before_long_break`, even without a long break since.
+    /// Default is false.
+    #[serde(default)]
+    pub reset_long_break_daily: bool,
+    /// How early a scheduled timer check can fire and still finish the timer
+    ///
+    /// Systemd may run the check slightly before the timer actually ends,
+    /// and a timer within this window of its end is treated as done so it is
+    /// not left running after its only scheduled check.
+    /// Default is 5 seconds.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
+    #[serde(
+        default = "default_timer_check_grace",
+        with = "crate::time::duration::human"
+    )]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub timer_check_grace: TimeDelta,
+    /// How precisely systemd should run scheduled timer checks
+    ///
+    /// Systemd can batch timers that are due within this window, so a larger
+    /// value wakes the computer less often and saves battery, at the cost of
+    /// timers finishing up to this much late.
+    /// Default is 1 second.
+    /// Serialized as a duration string like `"1s"` or `"500ms"`, or an integer
+    /// count of milliseconds.
+    #[serde(
+        default = "default_timer_accuracy",
+        with = "crate::time::duration::human::milliseconds"
+    )]
+    #[schemars(schema_with = "crate::time::duration::human::milliseconds::schema")]
+    pub timer_accuracy: TimeDelta,
+    /// Pause Pomodoros while the computer is suspended
+    ///
+    /// If this is on, a running Pomodoro is ticked at least every
+    /// `suspend_threshold`. Ticks only count time the computer is awake, so a
+    /// tick that runs more than `suspend_threshold` late means the computer
+    /// was asleep, and the Pomodoro is paused for that long.
+    /// Default is false.
+    #[serde(default)]
+    pub pause_on_suspend: bool,
+    /// How late a tick has to be to count as a suspend
+    ///
+    /// Only used if `pause_on_suspend` is on.
+    /// Default is 1 minute.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
+    #[serde(
+        default = "default_suspend_threshold",
+        with = "crate::time::duration::human"
+    )]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub suspend_threshold: TimeDelta,
+    /// How often to run the reminder hook while a timer has run out but isn't finished
+    ///
+    /// If this is set, scheduled timer checks leave a timer running after it
+    /// runs out, and run the `reminder` hook instead of finishing it.
+    /// Default is no reminders.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub reminder_interval: Option<TimeDelta>,
+    /// How often to run the tick hook while a Pomodoro is running
+    ///
+    /// Each tick starts a process, so this is off unless it's set.
+    /// Default is no ticks.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub tick_interval: Option<TimeDelta>,
+    /// How long the computer can go without input before a Pomodoro is paused
+    ///
+    /// If this is set, the Pomodoro is checked at least this often. Once the
+    /// computer has been idle for this long, the Pomodoro is paused from the
+    /// last input, and the first check after the user is back resumes it.
+    /// Default is never pausing.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub idle_timeout: Option<TimeDelta>,
+    /// Shortest Pomodoro that can be started
+    ///
+    /// Default is no minimum.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub min_pomodoro_duration: Option<TimeDelta>,
+    /// Longest Pomodoro that can be started
+    ///
+    /// Default is no maximum.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub max_pomodoro_duration: Option<TimeDelta>,
+    /// Granularity to round Pomodoro durations to when they're archived
+    ///
+    /// Durations are rounded to the nearest multiple, and a duration exactly
+    /// halfway between two multiples is rounded up. The rounded duration is
+    /// what gets stored in the history.
+    /// Default is no rounding.
+    /// Serialized as a duration string like `"25m"`, or an integer count of seconds.
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub history_rounding: Option<TimeDelta>,
+    /// Convert tags to lowercase when starting a Pomodoro
+    ///
+    /// Default is `false`.
+    #[serde(default)]
+    pub lowercase_tags: bool,
+    /// Copy the history file to a timestamped `.bak` file before deleting or rewriting it
+    ///
+    /// Default is `true`.
+    #[serde(default = "default_keep_backups")]
+    pub keep_backups: bool,
+    /// Finish a Pomodoro or break whose timer has run out when running `status`, `start`, or `break`
+    ///
+    /// This catches timers that were never finished because the scheduled
+    /// timer check didn't run, like when the computer was asleep.
+    /// Default is `false`.
+    #[serde(default)]
+    pub auto_finish_on_access: bool,
+    /// File format to write the state file in
+    ///
+    /// Default is `toml`. The state file can be read in either format, but
+    /// its path is not changed to match.
+    #[serde(default)]
+    pub state_format: StateFormat,
+    /// Show the Pomodoro that was just finished in `status` until the next timer starts
+    ///
+    /// Default is `false`.
+    #[serde(default)]
+    pub show_recent: bool,
+    /// How to show the start date of each entry in the history table
+    ///
+    /// Default is `%d %b %R`, like `27 Mar 12:00`.
+    /// Serialized as a [chrono format string](https://docs.rs/chrono/latest/chrono/format/strftime/index.html).
+    #[serde(default = "default_history_date_format")]
+    pub history_date_format: String,
+    /// Commands to run for hooks, instead of the files in the hooks directory
+    ///
+    /// Default is no commands.
+    /// Serialized as a `[hooks]` table mapping hook names like `start`
+    /// to a command line, like `start = "notify-send 'Pomodoro started'"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hooks: BTreeMap<Hook, String>,
+    /// Sound to play when a timer finishes
+    ///
+    /// Default is no sound.
+    /// Sounds are only played if tomate was built with the `sound` feature.
+    /// Serialized as an absolute path.
+    pub sound_file: Option<PathBuf>,
+    /// Sounds to play when a specific kind of timer finishes, instead of `sound_file`
+    ///
+    /// Default is no sounds.
+    /// Serialized as a `[sound_files]` table mapping `pomodoro`, `short_break`,
+    /// or `long_break` to an absolute path.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub sound_files: BTreeMap<Phase, PathBuf>,
+    /// Glyphs to print for each kind of timer with the `%I` format token
+    ///
+    /// Default is 🍅 for Pomodoros, ☕ for short breaks, and 🛋 for long breaks.
+    /// Serialized as a `[phase_icons]` table mapping `pomodoro`, `short_break`,
+    /// or `long_break` to a string.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub phase_icons: BTreeMap<Phase, String>,
+    /// Named sets of overrides for the values above
+    ///
+    /// Default is no profiles.
+    /// Serialized as a table of `[profiles.<name>]` tables.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Named defaults for starting Pomodoros, used with `tomate start --template`
+    ///
+    /// Default is no templates.
+    /// Serialized as a table of `[templates.<name>]` tables.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub templates: BTreeMap<String, PomodoroTemplate>,
+    /// Log changes to state and history files instead of making them
+    ///
+    /// Also prevents hooks from running and timers from being scheduled.
+    /// This is only set by the `--dry-run` CLI flag and is never read from
+    /// or written to the config file.
+    #[serde(skip)]
+    pub dry_run: bool,
+    /// Keys in the config file that tomate doesn't recognize
+    ///
+    /// These are kept so that saving the config doesn't delete them,
+    /// for example after downgrading tomate.
+    #[serde(flatten)]
+    #[schemars(skip)]
+    pub unknown: toml::Table,
 }
 
 impl Config {
+    /// Get the default config, or an error if the default file locations can't be determined
+    pub fn try_default() -> Result<Self> {
+        Ok(Self {
+            hooks_directory: default_hooks_directory()?,
+            state_file_path: default_state_path()?,
+            history_file_path: default_history_path()?,
+            history_rotation: Rotation::None,
+            socket_path: default_socket_path()?,
+            pomodoro_duration: default_pomodoro_duration(),
+            short_break_duration: default_short_break_duration(),
+            long_break_duration: default_long_break_duration(),
+            poms_before_long_break: default_poms_before_long_break(),
+            reset_long_break_daily: false,
+            timer_check_grace: default_timer_check_grace(),
+            timer_accuracy: default_timer_accuracy(),
+            pause_on_suspend: false,
+            suspend_threshold: default_suspend_threshold(),
+            reminder_interval: None,
+            tick_interval: None,
+            idle_timeout: None,
+            min_pomodoro_duration: None,
+            max_pomodoro_duration: None,
+            history_rounding: None,
+            lowercase_tags: false,
+            keep_backups: default_keep_backups(),
+            auto_finish_on_access: false,
+            state_format: StateFormat::default(),
+            show_recent: false,
+            history_date_format: default_history_date_format(),
+            hooks: BTreeMap::new(),
+            sound_file: None,
+            sound_files: BTreeMap::new(),
+            phase_icons: BTreeMap::new(),
+            profiles: BTreeMap::new(),
+            templates: BTreeMap::new(),
+            dry_run: false,
+            unknown: toml::Table::new(),
+        })
+    }
+
     /// Returns the current config, creating a default config file if one does not exist
     pub fn init(config_path: &Path) -> Result<Self> {
         if let Some(conf) = Config::load(config_path)? {
             Ok(conf)
         } else {
-            let conf = Config::default();
+            let conf = Config::try_default()?;
 
             info!(
                 "Creating config file at {}",
@@ -108,68 +375,338 @@ impl Config {
             let config_str = read_to_string(path)
                 .with_context(|| format!("Failed to read {}", path.display()))?;
 
-            toml::from_str(&config_str).with_context(|| "Failed to parse config from TOML")
+            // Missing paths default to these directories
+            project_dirs()?;
+
+            let config: Self =
+                toml::from_str(&config_str).with_context(|| "Failed to parse config from TOML")?;
+
+            check_date_format(&config.history_date_format)
+                .with_context(|| "Invalid history_date_format in config file")?;
+
+            if let (Some(minimum), Some(maximum)) =
+                (config.min_pomodoro_duration, config.max_pomodoro_duration)
+            {
+                if minimum > maximum {
+                    bail!(
+                        "min_pomodoro_duration ({}) is longer than max_pomodoro_duration ({}) in config file",
+                        crate::time::duration::human::format(&minimum),
+                        crate::time::duration::human::format(&maximum)
+                    );
+                }
+            }
+
+            for key in config.unknown.keys() {
+                warn!(
+                    "Unknown key {} in config file {}",
+                    key.yellow(),
+                    path.display().to_string().cyan()
+                );
+            }
+
+            Ok(Some(config))
         } else {
             Ok(None)
         }
     }
 
+    /// Get the sound file to play when a kind of timer finishes
+    pub fn sound_for(&self, phase: Phase) -> Option<&Path> {
+        self.sound_files
+            .get(&phase)
+            .or(self.sound_file.as_ref())
+            .map(PathBuf::as_path)
+    }
+
+    /// Get the glyph to print for a kind of timer
+    pub fn icon_for(&self, phase: Phase) -> &str {
+        match self.phase_icons.get(&phase) {
+            Some(icon) => icon,
+            None => phase.default_icon(),
+        }
+    }
+
+    /// Get the config to use for a named profile
+    ///
+    /// Values set in the profile replace the values of this config.
+    /// Each profile gets its own state and history files, so if the profile
+    /// does not set them, they default to this config's paths with the profile
+    /// name appended, like `current-work.toml` and `history-work.toml`.
+    /// The socket path always gets the profile name appended.
+    pub fn profile(&self, name: &str) -> Result<Self> {
+        let profile = self
+            .profiles
+            .get(name)
+            .with_context(|| format!("No profile named \"{}\" in the config file", name))?;
+
+        Ok(Self {
+            hooks_directory: profile
+                .hooks_directory
+                .clone()
+                .unwrap_or_else(|| self.hooks_directory.clone()),
+            state_file_path: profile
+                .state_file_path
+                .clone()
+                .unwrap_or_else(|| profile_path(&self.state_file_path, name)),
+            history_file_path: profile
+                .history_file_path
+                .clone()
+                .unwrap_or_else(|| profile_path(&self.history_file_path, name)),
+            socket_path: profile_path(&self.socket_path, name),
+            pomodoro_duration: profile.pomodoro_duration.unwrap_or(self.pomodoro_duration),
+            short_break_duration: profile
+                .short_break_duration
+                .unwrap_or(self.short_break_duration),
+            long_break_duration: profile
+                .long_break_duration
+                .unwrap_or(self.long_break_duration),
+            profiles: BTreeMap::new(),
+            ..self.clone()
+        })
+    }
+
+    /// Get the config for a session, which has its own state file
+    ///
+    /// The session's name is added to the state file and socket names, like
+    /// `current@work.toml`, so timers in different sessions don't replace
+    /// each other. The history file is shared by every session.
+    pub fn session(&self, name: &str) -> Result<Self> {
+        if name.is_empty() || name == "." || name == ".." || name.contains(['/', '\\']) {
+            bail!("\"{}\" is not a valid session name", name);
+        }
+
+        Ok(Self {
+            state_file_path: session_path(&self.state_file_path, name),
+            socket_path: session_path(&self.socket_path, name),
+            ..self.clone()
+        })
+    }
+
+    /// Get a Pomodoro template by name
+    pub fn template(&self, name: &str) -> Result<&PomodoroTemplate> {
+        self.templates
+            .get(name)
+            .with_context(|| format!("No template named \"{}\" in the config file", name))
+    }
+
+    /// Start building a Pomodoro with this config's defaults and an optional template
+    ///
+    /// The Pomodoro lasts for `pomodoro_duration`, unless the template has a
+    /// duration. Values set on the builder afterwards take precedence over the
+    /// template's, except that a description is added to the template's
+    /// description, like `Deep work: refactor the parser`.
+    /// Fails if there is no template with the given name.
+    pub fn pomodoro_builder(&self, template: Option<&str>) -> Result<PomodoroBuilder> {
+        let builder = Pomodoro::builder().duration(self.pomodoro_duration);
+
+        Ok(match template {
+            Some(name) => builder.template(self.template(name)?),
+            None => builder,
+        })
+    }
+
     /// Write this config file to the filesystem
+    ///
+    /// If the file already exists, only the values that changed are
+    /// rewritten, so comments and formatting in the file are kept.
     pub fn save(&self, path: &Path) -> Result<()> {
-        let toml = toml::to_string(&self).with_context(|| "Unable to format config as TOML")?;
+        let new_doc = toml_edit::ser::to_document(&self)
+            .with_context(|| "Unable to format config as TOML")?;
+
+        let doc = if path.exists() {
+            let config_str = read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?;
+            let mut doc: DocumentMut = config_str
+                .parse()
+                .with_context(|| "Failed to parse config from TOML")?;
 
-        std::fs::write(path, toml)
+            merge_item(doc.as_item_mut(), new_doc.as_item());
+            doc
+        } else {
+            new_doc
+        };
+
+        std::fs::write(path, doc.to_string())
             .with_context(|| format!("Unable to write config TOML to path {}", path.display()))
     }
 }
 
+/// Update `old` to match `new`, keeping the comments and formatting of `old`
+///
+/// Values that are equal in both are left alone, keys that are missing from
+/// `new` are removed, and keys that are only in `new` are added.
+fn merge_item(old: &mut Item, new: &Item) {
+    if let (Some(old_table), Some(new_table)) = (old.as_table_like_mut(), new.as_table_like()) {
+        let removed: Vec<String> = old_table
+            .iter()
+            .map(|(key, _)| key.to_string())
+            .filter(|key| !new_table.contains_key(key))
+            .collect();
+        for key in removed {
+            old_table.remove(&key);
+        }
+
+        for (key, new_item) in new_table.iter() {
+            match old_table.get_mut(key) {
+                Some(old_item) => merge_item(old_item, new_item),
+                None => {
+                    old_table.insert(key, new_item.clone());
+                }
+            }
+        }
+
+        return;
+    }
+
+    if let (Some(old_value), Some(new_value)) = (old.as_value(), new.as_value()) {
+        let mut new_value = new_value.clone();
+        new_value.decor_mut().clone_from(old_value.decor());
+
+        if new_value.to_string() != old_value.to_string() {
+            *old = Item::Value(new_value);
+        }
+
+        return;
+    }
+
+    *old = new.clone();
+}
+
 impl Default for Config {
+    /// Get the default config
+    ///
+    /// # Panics
+    ///
+    /// Panics if the user's home directory can't be determined.
+    /// Use [`Config::try_default`] to get an error instead.
     fn default() -> Self {
-        Self {
-            hooks_directory: default_hooks_directory(),
-            state_file_path: default_state_path(),
-            history_file_path: default_history_path(),
-            pomodoro_duration: default_pomodoro_duration(),
-            short_break_duration: default_short_break_duration(),
-            long_break_duration: default_long_break_duration(),
-        }
+        Self::try_default().expect("XDG directories should be available")
     }
 }
 
-/// Get the default location of the config file
-pub fn default_config_path() -> Result<PathBuf> {
-    let conf_path = ProjectDirs::from("dev", "Cosmicrose", "Tomate")
-        .with_context(|| "Unable to determine XDG directories")?
-        .config_dir()
-        .join("config.toml");
+/// Overrides for a subset of [`Config`] values
+///
+/// Every field is optional, and fields that are not set use the value from
+/// the main config. See [`Config::profile`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct Profile {
+    /// Directory to find hook executables
+    pub hooks_directory: Option<PathBuf>,
+    /// File describing the current Pomodoro or break timer
+    pub state_file_path: Option<PathBuf>,
+    /// File describing historical Pomodoro or break timers
+    pub history_file_path: Option<PathBuf>,
+    /// Default duration for Pomodoro timers
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub pomodoro_duration: Option<TimeDelta>,
+    /// Default duration for short break timers
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub short_break_duration: Option<TimeDelta>,
+    /// Default duration for long break timers
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub long_break_duration: Option<TimeDelta>,
+}
 
-    Ok(conf_path)
+/// Values to start a Pomodoro with, unless they're given on the command line
+///
+/// See [`Config::templates`] and [`Config::pomodoro_builder`].
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, Deserialize, Serialize, JsonSchema)]
+pub struct PomodoroTemplate {
+    /// Length of the Pomodoro
+    #[serde(default, with = "crate::time::durationopt::human")]
+    #[schemars(schema_with = "crate::time::duration::human::schema")]
+    pub duration: Option<TimeDelta>,
+    /// Start of the description, which a description given on the command line is added to
+    pub description: Option<String>,
+    /// Tags to categorize the work
+    pub tags: Option<Vec<String>>,
 }
 
-fn default_hooks_directory() -> PathBuf {
-    let project_dirs = ProjectDirs::from("dev", "Cosmicrose", "Tomate")
-        .with_context(|| "Unable to determine XDG directories")
-        .unwrap();
+fn profile_path(path: &Path, profile: &str) -> PathBuf {
+    suffixed_path(path, '-', profile)
+}
 
-    project_dirs.config_dir().join("hooks")
+/// Add a session's name to a file name, like `current@work.toml`
+///
+/// Sessions use a different separator than profiles, so a session and a
+/// profile with the same name don't share a state file.
+fn session_path(path: &Path, session: &str) -> PathBuf {
+    suffixed_path(path, '@', session)
 }
 
-fn default_state_path() -> PathBuf {
-    ProjectDirs::from("dev", "Cosmicrose", "Tomate")
-        .with_context(|| "Unable to determine XDG directories")
-        .unwrap()
-        .state_dir()
-        .with_context(|| "Getting state dir")
-        .unwrap()
-        .join("current.toml")
+fn suffixed_path(path: &Path, separator: char, suffix: &str) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let file_name = match path.extension() {
+        Some(ext) => format!("{}{}{}.{}", stem, separator, suffix, ext.to_string_lossy()),
+        None => format!("{}{}{}", stem, separator, suffix),
+    };
+
+    path.with_file_name(file_name)
 }
 
-fn default_history_path() -> PathBuf {
+/// Get the default location of the config file
+pub fn default_config_path() -> Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join("config.toml"))
+}
+
+fn project_dirs() -> Result<ProjectDirs> {
     ProjectDirs::from("dev", "Cosmicrose", "Tomate")
         .with_context(|| "Unable to determine XDG directories")
-        .unwrap()
-        .data_dir()
-        .join("history.toml")
+}
+
+/// Directory for state files, which is the data directory on platforms without one
+fn state_dir<'a>(state_dir: Option<&'a Path>, data_dir: &'a Path) -> &'a Path {
+    state_dir.unwrap_or(data_dir)
+}
+
+fn default_hooks_directory() -> Result<PathBuf> {
+    Ok(project_dirs()?.config_dir().join("hooks"))
+}
+
+fn default_state_path() -> Result<PathBuf> {
+    let project_dirs = project_dirs()?;
+    let dir = state_dir(project_dirs.state_dir(), project_dirs.data_dir());
+
+    Ok(dir.join("current.toml"))
+}
+
+fn default_history_path() -> Result<PathBuf> {
+    Ok(project_dirs()?.data_dir().join("history.toml"))
+}
+
+fn default_socket_path() -> Result<PathBuf> {
+    let project_dirs = project_dirs()?;
+    let dir = project_dirs
+        .runtime_dir()
+        .unwrap_or_else(|| state_dir(project_dirs.state_dir(), project_dirs.data_dir()));
+
+    Ok(dir.join("tomate.sock"))
+}
+
+// Serde needs infallible defaults. Config::load checks that the directories
+// can be determined before deserializing, so these never panic from there.
+
+fn serde_default_hooks_directory() -> PathBuf {
+    default_hooks_directory().expect("XDG directories should be available")
+}
+
+fn serde_default_state_path() -> PathBuf {
+    default_state_path().expect("XDG directories should be available")
+}
+
+fn serde_default_history_path() -> PathBuf {
+    default_history_path().expect("XDG directories should be available")
+}
+
+fn serde_default_socket_path() -> PathBuf {
+    default_socket_path().expect("XDG directories should be available")
 }
 
 fn default_pomodoro_duration() -> TimeDelta {
@@ -183,3 +720,328 @@ fn default_short_break_duration() -> TimeDelta {
 fn default_long_break_duration() -> TimeDelta {
     TimeDelta::new(20 * 60, 0).unwrap()
 }
+
+fn default_poms_before_long_break() -> usize {
+    4
+}
+
+fn default_timer_check_grace() -> TimeDelta {
+    TimeDelta::new(5, 0).unwrap()
+}
+
+fn default_timer_accuracy() -> TimeDelta {
+    TimeDelta::seconds(1)
+}
+
+fn default_suspend_threshold() -> TimeDelta {
+    TimeDelta::minutes(1)
+}
+
+fn default_keep_backups() -> bool {
+    true
+}
+
+fn default_history_date_format() -> String {
+    "%d %b %R".to_string()
+}
+
+/// Check that a chrono format string can format a date
+///
+/// Formatting a date with an invalid format string panics, so this catches
+/// mistakes when the config is loaded instead.
+fn check_date_format(date_format: &str) -> Result<()> {
+    use std::fmt::Write;
+
+    let date = DateTime::from_timestamp(0, 0)
+        .unwrap()
+        .with_timezone(&Local);
+    let mut formatted = String::new();
+
+    write!(formatted, "{}", date.format(date_format))
+        .map_err(|_| anyhow!("Invalid date format \"{}\"", date_format))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::{Path, PathBuf};
+
+    use chrono::TimeDelta;
+
+    use super::Config;
+    use crate::Phase;
+
+    const CONFIG: &str = r#"
+hooks_directory = "/home/tomate/.config/tomate/hooks"
+state_file_path = "/home/tomate/.local/state/tomate/current.toml"
+history_file_path = "/home/tomate/.local/share/tomate/history.toml"
+pomodoro_duration = 1500
+short_break_duration = 300
+long_break_duration = "20m"
+
+[profiles.work]
+pomodoro_duration = 3000
+
+[profiles.personal]
+history_file_path = "/home/tomate/personal.toml"
+
+[templates.deepwork]
+duration = "50m"
+tags = ["focus"]
+"#;
+
+    #[test]
+    fn profile_overrides_values() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+
+        let work = config.profile("work").unwrap();
+
+        assert_eq!(work.pomodoro_duration, TimeDelta::minutes(50));
+        assert_eq!(work.short_break_duration, TimeDelta::minutes(5));
+        assert_eq!(work.hooks_directory, config.hooks_directory);
+    }
+
+    #[test]
+    fn templates_are_looked_up_by_name() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+
+        let deepwork = config.template("deepwork").unwrap();
+        assert_eq!(deepwork.duration, Some(TimeDelta::minutes(50)));
+        assert_eq!(deepwork.description, None);
+        assert_eq!(deepwork.tags, Some(vec!["focus".to_string()]));
+
+        assert!(config.template("shallowwork").is_err());
+        assert!(config.profile("work").unwrap().template("deepwork").is_ok());
+    }
+
+    #[test]
+    fn profiles_get_their_own_files() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+
+        let work = config.profile("work").unwrap();
+        let personal = config.profile("personal").unwrap();
+
+        assert_eq!(
+            work.state_file_path,
+            PathBuf::from("/home/tomate/.local/state/tomate/current-work.toml")
+        );
+        assert_eq!(
+            work.history_file_path,
+            PathBuf::from("/home/tomate/.local/share/tomate/history-work.toml")
+        );
+        assert_eq!(
+            personal.state_file_path,
+            PathBuf::from("/home/tomate/.local/state/tomate/current-personal.toml")
+        );
+        assert_eq!(
+            personal.history_file_path,
+            PathBuf::from("/home/tomate/personal.toml")
+        );
+    }
+
+    #[test]
+    fn sessions_get_their_own_state_file() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+
+        let session = config.session("work").unwrap();
+
+        assert_eq!(
+            session.state_file_path,
+            PathBuf::from("/home/tomate/.local/state/tomate/current@work.toml")
+        );
+        assert_eq!(session.history_file_path, config.history_file_path);
+        assert_ne!(session.socket_path, config.socket_path);
+
+        let profile = config.profile("work").unwrap();
+        assert_ne!(session.state_file_path, profile.state_file_path);
+        assert_ne!(session.socket_path, profile.socket_path);
+
+        let both = profile.session("a").unwrap();
+        assert_eq!(
+            both.state_file_path,
+            PathBuf::from("/home/tomate/.local/state/tomate/current-work@a.toml")
+        );
+
+        for name in ["", "..", "a/b"] {
+            assert!(config.session(name).is_err(), "for {:?}", name);
+        }
+    }
+
+    #[test]
+    fn builder_overrides_template() {
+        let config: Config = toml::from_str(
+            r#"
+pomodoro_duration = 1500
+
+[templates.deepwork]
+duration = 3000
+description = "Deep work"
+tags = ["focus"]
+
+[templates.empty]
+"#,
+        )
+        .unwrap();
+
+        let pom = config
+            .pomodoro_builder(Some("deepwork"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pom.timer().duration(), TimeDelta::minutes(50));
+        assert_eq!(pom.description(), Some("Deep work"));
+        assert_eq!(pom.tags(), Some(&vec!["focus".to_string()]));
+
+        let pom = config
+            .pomodoro_builder(Some("deepwork"))
+            .unwrap()
+            .duration(TimeDelta::minutes(10))
+            .description("reply to email")
+            .tags(["admin", "email"])
+            .build()
+            .unwrap();
+        assert_eq!(pom.timer().duration(), TimeDelta::minutes(10));
+        assert_eq!(pom.description(), Some("Deep work: reply to email"));
+        assert_eq!(
+            pom.tags(),
+            Some(&vec!["admin".to_string(), "email".to_string()])
+        );
+
+        let pom = config
+            .pomodoro_builder(Some("empty"))
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(pom.timer().duration(), TimeDelta::minutes(25));
+        assert_eq!(pom.description(), None);
+
+        let pom = config
+            .pomodoro_builder(None)
+            .unwrap()
+            .description("no template")
+            .build()
+            .unwrap();
+        assert_eq!(pom.description(), Some("no template"));
+
+        assert!(config.pomodoro_builder(Some("missing")).is_err());
+    }
+
+    #[test]
+    fn unknown_profile_is_an_error() {
+        let config: Config = toml::from_str(CONFIG).unwrap();
+
+        assert!(config.profile("nope").is_err());
+    }
+
+    #[test]
+    fn save_keeps_comments() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            format!(
+                "# My Pomodoros are longer than most\n{}",
+                CONFIG.trim_start()
+            ),
+        )
+        .unwrap();
+
+        let mut config = Config::load(&path).unwrap().unwrap();
+        config.short_break_duration = TimeDelta::minutes(10);
+        config.save(&path).unwrap();
+
+        let saved = std::fs::read_to_string(&path).unwrap();
+        assert!(saved.starts_with("# My Pomodoros are longer than most\n"));
+        assert!(saved.contains("short_break_duration = \"10m\"\n"));
+        assert!(saved.contains("[profiles.work]\npomodoro_duration = \"50m\"\n"));
+
+        let reloaded = Config::load(&path).unwrap().unwrap();
+        assert_eq!(reloaded.short_break_duration, TimeDelta::minutes(10));
+        assert_eq!(reloaded.profiles, config.profiles);
+    }
+
+    #[test]
+    fn unknown_keys_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            format!("future_option = true\n{}", CONFIG.trim_start()),
+        )
+        .unwrap();
+
+        let config = Config::load(&path).unwrap().unwrap();
+        assert_eq!(
+            config.unknown.get("future_option"),
+            Some(&toml::Value::Boolean(true))
+        );
+
+        std::fs::remove_file(&path).unwrap();
+        config.save(&path).unwrap();
+
+        let reloaded = Config::load(&path).unwrap().unwrap();
+        assert_eq!(reloaded.unknown, config.unknown);
+        assert_eq!(reloaded.profiles, config.profiles);
+    }
+
+    #[test]
+    fn phase_icons_default_per_phase() {
+        let mut config = Config::try_default().unwrap();
+
+        assert_eq!(config.icon_for(Phase::Pomodoro), "🍅");
+        assert_eq!(config.icon_for(Phase::ShortBreak), "☕");
+        assert_eq!(config.icon_for(Phase::LongBreak), "🛋");
+
+        config
+            .phase_icons
+            .insert(Phase::ShortBreak, "SB".to_string());
+        assert_eq!(config.icon_for(Phase::Pomodoro), "🍅");
+        assert_eq!(config.icon_for(Phase::ShortBreak), "SB");
+    }
+
+    #[test]
+    fn state_dir_falls_back_to_data_dir() {
+        let data_dir = Path::new("/home/tomate/Library/Application Support/tomate");
+        let state = Path::new("/home/tomate/.local/state/tomate");
+
+        assert_eq!(super::state_dir(None, data_dir), data_dir);
+        assert_eq!(super::state_dir(Some(state), data_dir), state);
+    }
+
+    #[test]
+    fn invalid_date_format_is_rejected() {
+        assert!(super::check_date_format("%Y-%m-%d %H:%M").is_ok());
+        assert!(super::check_date_format("%Q").is_err());
+    }
+
+    #[test]
+    fn load_rejects_invalid_date_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "history_date_format = \"%d %Q\"\n").unwrap();
+
+        assert!(Config::load(&path).is_err());
+    }
+
+    #[test]
+    fn load_rejects_minimum_above_maximum() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "min_pomodoro_duration = \"30m\"\nmax_pomodoro_duration = \"20m\"\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            Config::load(&path).unwrap_err().to_string(),
+            "min_pomodoro_duration (30m) is longer than max_pomodoro_duration (20m) in config file"
+        );
+
+        std::fs::write(
+            &path,
+            "min_pomodoro_duration = \"20m\"\nmax_pomodoro_duration = \"20m\"\n",
+        )
+        .unwrap();
+        assert!(Config::load(&path).is_ok());
+    }
+}
@@ -0,0 +1,353 @@
+//! Checks for problems with the environment tomate is running in
+
+use std::{
+    fs::OpenOptions,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{hooks::split_command, Config, Hook};
+
+/// Outcome of a single environment check
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum CheckStatus {
+    /// Everything is fine
+    Pass,
+    /// Tomate will work, but maybe not the way you expect
+    Warn,
+    /// Something tomate depends on is broken
+    Fail,
+}
+
+/// The result of checking one part of the environment
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Check {
+    /// Whether the check passed
+    pub status: CheckStatus,
+    /// Human-readable description of what was found
+    pub message: String,
+}
+
+impl Check {
+    fn pass(message: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Pass,
+            message: message.into(),
+        }
+    }
+
+    fn warn(message: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Warn,
+            message: message.into(),
+        }
+    }
+
+    fn fail(message: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Fail,
+            message: message.into(),
+        }
+    }
+}
+
+/// Run every environment check for a config
+pub fn run_checks(config: &Config, config_path: &Path) -> Vec<Check> {
+    let mut checks = check_systemd("systemd-run", "systemctl");
+    checks.extend(check_unknown_keys(config));
+    checks.extend(check_hooks(config));
+    checks.push(check_writable("Config file", config_path));
+    checks.push(check_writable("State file", &config.state_file_path));
+    checks.push(check_writable("History file", &config.history_file_path));
+    checks
+}
+
+/// Check that timers can be scheduled with systemd
+///
+/// `systemd_run` and `systemctl` are the programs to check.
+pub fn check_systemd(systemd_run: &str, systemctl: &str) -> Vec<Check> {
+    match Command::new(systemd_run).arg("--version").output() {
+        Ok(output) if output.status.success() => {}
+        Ok(_) => return vec![Check::fail(format!("{} --version failed", systemd_run))],
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            return vec![Check::fail(format!(
+                "{} was not found on PATH, so timers will not finish automatically",
+                systemd_run
+            ))]
+        }
+        Err(e) => {
+            return vec![Check::fail(format!(
+                "{} could not be executed: {}",
+                systemd_run, e
+            ))]
+        }
+    }
+
+    let mut checks = vec![Check::pass(format!("{} is installed", systemd_run))];
+
+    match Command::new(systemctl)
+        .args(["--user", "show-environment"])
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            checks.push(Check::pass("The systemd user manager is running"))
+        }
+        _ => checks.push(Check::fail(
+            "The systemd user manager is not reachable, so timers cannot be scheduled with --user",
+        )),
+    }
+
+    checks
+}
+
+/// Check for keys in the config file that tomate doesn't recognize, which are likely typos
+pub fn check_unknown_keys(config: &Config) -> Vec<Check> {
+    config
+        .unknown
+        .keys()
+        .map(|key| Check::warn(format!("Config file has an unknown key {}", key)))
+        .collect()
+}
+
+/// Check which hooks are set up, and whether they can run
+///
+/// Hooks with a command in the config's `[hooks]` table are checked first,
+/// since those take the place of files in the hooks directory.
+pub fn check_hooks(config: &Config) -> Vec<Check> {
+    let hooks_directory = &config.hooks_directory;
+    let has_directory = hooks_directory.is_dir();
+
+    let mut checks = Vec::new();
+    if !has_directory && config.hooks.is_empty() {
+        checks.push(Check::warn(format!(
+            "Hooks directory {} does not exist",
+            hooks_directory.display()
+        )));
+    }
+
+    checks.extend(Hook::all().filter_map(|hook| {
+        let name = hook.file_name();
+
+        if let Some(command_line) = config.hooks.get(&hook) {
+            return Some(check_hook_command(name, command_line));
+        }
+        if !has_directory {
+            return None;
+        }
+
+        let path = hook.path(hooks_directory);
+        Some(if !path.exists() {
+            Check::pass(format!("No {} hook", name))
+        } else if is_executable(&path) {
+            Check::pass(format!("{} hook {} is executable", name, path.display()))
+        } else {
+            Check::warn(format!(
+                "{} hook {} is not executable and will fail to run",
+                name,
+                path.display()
+            ))
+        })
+    }));
+
+    checks
+}
+
+fn check_hook_command(name: &str, command_line: &str) -> Check {
+    let words = match split_command(command_line) {
+        Ok(words) => words,
+        Err(e) => {
+            return Check::warn(format!(
+                "{} hook command {} is invalid and will fail to run: {}",
+                name, command_line, e
+            ))
+        }
+    };
+    let Some(program) = words.first() else {
+        return Check::warn(format!(
+            "{} hook command is empty and will fail to run",
+            name
+        ));
+    };
+
+    if find_program(program).is_some() {
+        Check::pass(format!("{} hook runs {}", name, command_line))
+    } else {
+        Check::warn(format!(
+            "{} hook command {} will fail to run, because {} was not found on PATH",
+            name, command_line, program
+        ))
+    }
+}
+
+/// Find the executable a program name runs, the way a shell would
+///
+/// Names with a path separator are used as they are, and other names are
+/// looked up in each directory on `PATH`.
+fn find_program(program: &str) -> Option<PathBuf> {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return is_executable(path).then(|| path.to_path_buf());
+    }
+
+    std::env::split_paths(&std::env::var_os("PATH")?)
+        .map(|dir| dir.join(program))
+        .find(|candidate| is_executable(candidate))
+}
+
+/// Check that a file can be written to, or created if it doesn't exist
+///
+/// `name` describes the file in the check's message.
+pub fn check_writable(name: &str, path: &Path) -> Check {
+    if path.exists() {
+        return match OpenOptions::new().append(true).open(path) {
+            Ok(_) => Check::pass(format!("{} {} is writable", name, path.display())),
+            Err(e) => Check::fail(format!(
+                "{} {} is not writable: {}",
+                name,
+                path.display(),
+                e
+            )),
+        };
+    }
+
+    let Some(dir) = nearest_existing_ancestor(path) else {
+        return Check::fail(format!(
+            "{} {} has no existing parent directory",
+            name,
+            path.display()
+        ));
+    };
+
+    let probe = dir.join(".tomate-doctor");
+    match OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::pass(format!("{} {} can be created", name, path.display()))
+        }
+        Err(e) => Check::fail(format!(
+            "{} {} cannot be created in {}: {}",
+            name,
+            path.display(),
+            dir.display(),
+            e
+        )),
+    }
+}
+
+fn nearest_existing_ancestor(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .skip(1)
+        .find(|dir| dir.is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// Check whether a file exists and can be executed
+#[cfg(unix)]
+pub fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Check whether a file exists and can be executed
+#[cfg(not(unix))]
+pub fn is_executable(path: &Path) -> bool {
+    path.is_file()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_hooks, check_systemd, check_unknown_keys, check_writable, CheckStatus};
+    use crate::{Config, Hook};
+
+    #[test]
+    fn missing_systemd_run_fails() {
+        let checks = check_systemd("tomate-missing-systemd-run", "systemctl");
+
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].status, CheckStatus::Fail);
+    }
+
+    #[test]
+    fn missing_hooks_directory_warns() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let config = Config {
+            hooks_directory: dir.path().join("hooks"),
+            ..Default::default()
+        };
+
+        let checks = check_hooks(&config);
+
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].status, CheckStatus::Warn);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_executable_hook_warns() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("start"), "#!/bin/sh\n").unwrap();
+
+        let config = Config {
+            hooks_directory: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+
+        let checks = check_hooks(&config);
+
+        assert_eq!(checks.len(), Hook::all().count());
+        assert_eq!(checks[0].status, CheckStatus::Warn);
+        assert!(checks[1..]
+            .iter()
+            .all(|check| check.status == CheckStatus::Pass));
+    }
+
+    #[test]
+    fn hook_commands_are_checked() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            hooks_directory: dir.path().join("hooks"),
+            hooks: [
+                (Hook::Start, "sh -c 'echo started'".to_string()),
+                (Hook::Stop, "tomate-missing-program --stop".to_string()),
+                (Hook::Break, "sh -c 'unterminated".to_string()),
+            ]
+            .into(),
+            ..Default::default()
+        };
+
+        let checks = check_hooks(&config);
+
+        let statuses: Vec<CheckStatus> = checks.iter().map(|check| check.status).collect();
+        assert_eq!(
+            statuses,
+            [CheckStatus::Pass, CheckStatus::Warn, CheckStatus::Warn]
+        );
+        assert!(checks[1].message.contains("tomate-missing-program"));
+    }
+
+    #[test]
+    fn missing_file_in_writable_directory_passes() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let check = check_writable("State file", &dir.path().join("tomate/current.toml"));
+
+        assert_eq!(check.status, CheckStatus::Pass);
+        assert!(!dir.path().join("tomate").exists());
+    }
+
+    #[test]
+    fn unknown_config_key_warns() {
+        let config: Config = toml::from_str("pomodoro_durration = 3000").unwrap();
+
+        let checks = check_unknown_keys(&config);
+
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].status, CheckStatus::Warn);
+        assert!(checks[0].message.contains("pomodoro_durration"));
+    }
+}
@@ -0,0 +1,82 @@
+//! Errors returned by the library
+
+use std::path::PathBuf;
+
+use chrono::TimeDelta;
+use thiserror::Error;
+
+/// Something that went wrong while managing Pomodoros
+///
+/// The first few variants are about the current status not allowing what
+/// was asked, like starting a Pomodoro while one is already running. These
+/// are mistakes a user can fix, unlike the rest.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum TomateError {
+    /// A Pomodoro is already running
+    #[error("There is already an unfinished Pomodoro. Finish it with \"tomate finish\"")]
+    AlreadyActive,
+    /// A break is running
+    #[error("You're currently taking a break!")]
+    OnBreak,
+    /// No Pomodoro is running
+    #[error("No active Pomodoro. Start one with \"tomate start\"")]
+    NoActiveTimer,
+    /// No break is running
+    #[error("No active break. Start a Pomodoro with \"tomate start\"")]
+    NoActiveBreak,
+    /// The current Pomodoro's timer has already run out
+    #[error("The current Pomodoro has run out. Finish it with \"tomate finish\"")]
+    TimerRanOut,
+    /// The Pomodoro is shorter than `min_pomodoro_duration`
+    #[error("A Pomodoro must last at least {}", minutes_or_seconds(.minimum))]
+    TooShort {
+        /// Shortest Pomodoro that can be started
+        minimum: TimeDelta,
+    },
+    /// The Pomodoro is longer than `max_pomodoro_duration`
+    #[error("A Pomodoro can last at most {}", minutes_or_seconds(.maximum))]
+    TooLong {
+        /// Longest Pomodoro that can be started
+        maximum: TimeDelta,
+    },
+    /// A file couldn't be read or written
+    #[error("Failed to access {}", .path.display())]
+    Io {
+        /// File that was being read or written
+        path: PathBuf,
+        /// What went wrong
+        #[source]
+        source: std::io::Error,
+    },
+    /// The state file couldn't be parsed
+    #[error("Failed to parse state file")]
+    Parse(#[source] Box<dyn std::error::Error + Send + Sync>),
+    /// A timer check couldn't be scheduled
+    #[error("Failed to schedule a timer check with {program}")]
+    SchedulerUnavailable {
+        /// Program that was used to schedule the check
+        program: String,
+        /// What went wrong
+        #[source]
+        source: std::io::Error,
+    },
+    /// Anything else
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+fn minutes_or_seconds(duration: &TimeDelta) -> String {
+    if duration.num_seconds() % 60 == 0 {
+        format!("{} minutes", duration.num_minutes())
+    } else {
+        format!("{} seconds", duration.num_seconds())
+    }
+}
+
+impl TomateError {
+    pub(crate) fn io(path: impl Into<PathBuf>) -> impl FnOnce(std::io::Error) -> Self {
+        let path = path.into();
+        move |source| Self::Io { path, source }
+    }
+}
@@ -1,45 +1,209 @@
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs::{read_to_string, OpenOptions};
 use std::io::prelude::*;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use anyhow::{Context, Result};
-use chrono::{prelude::*, TimeDelta};
+use anyhow::{bail, Context, Result};
+use chrono::{prelude::*, IsoWeek, TimeDelta};
 use colored::Colorize;
-use log::info;
+use log::{info, warn};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::Pomodoro;
+use crate::{Break, Phase, Pomodoro};
 
-/// A record of a past Pomodoro timer
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+/// How the history is split across files
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum Rotation {
+    /// Every entry is in the one history file
+    #[default]
+    None,
+    /// Each month's entries are in their own file next to the history file,
+    /// named like `history-2024-03.toml`
+    Monthly,
+}
+
+/// A record of a past Pomodoro or break timer
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Default, Deserialize, Serialize)]
 pub struct HistoryEntry {
+    #[serde(default)]
+    phase: Phase,
     #[serde(default, with = "crate::time::datetime::unix")]
     started_at: DateTime<Local>,
     #[serde(with = "crate::time::duration::seconds")]
     duration: TimeDelta,
     tags: Option<Vec<String>>,
     description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<Uuid>,
 }
 
 impl HistoryEntry {
-    pub fn archive(pom: &Pomodoro) -> Result<Self> {
+    /// Create a history entry from a finished Pomodoro
+    ///
+    /// If `rounding` is set, the duration is rounded to the nearest multiple
+    /// of it, with ties rounded up.
+    pub fn archive(pom: &Pomodoro, rounding: Option<TimeDelta>) -> Result<Self> {
         let duration = pom
             .duration()
             .with_context(|| "Pomodoro is not finished yet")?;
+        let duration = match rounding {
+            Some(granularity) => round_duration(duration, granularity),
+            None => duration,
+        };
 
         Ok(Self {
+            phase: Phase::Pomodoro,
             duration,
             started_at: pom.timer().starts_at(),
             tags: pom.tags().cloned(),
             description: pom.description().map(|s| s.to_owned()),
+            id: pom.id(),
         })
     }
+
+    /// Create a history entry from a break that ended at `now`
+    ///
+    /// Like a Pomodoro's, the duration leaves out the time the break was paused.
+    pub fn archive_break(phase: Phase, rest: &Break, now: DateTime<Local>) -> Self {
+        let timer = rest.timer();
+
+        Self {
+            phase,
+            started_at: timer.starts_at(),
+            duration: now - timer.starts_at() - timer.paused_for(),
+            description: rest.description().map(str::to_owned),
+            id: Some(Uuid::new_v4()),
+            ..Default::default()
+        }
+    }
+
+    /// Get the kind of timer this entry records
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    /// Get the unique identifier of the Pomodoro or break
+    ///
+    /// Entries archived by older versions of tomate don't have one.
+    pub fn id(&self) -> Option<Uuid> {
+        self.id
+    }
+
+    /// Get the time the Pomodoro was started
+    pub fn started_at(&self) -> DateTime<Local> {
+        self.started_at
+    }
+
+    /// Get the length of time the Pomodoro actually lasted
+    pub fn duration(&self) -> TimeDelta {
+        self.duration
+    }
+
+    /// Get the description
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Set the description
+    pub fn set_description(&mut self, description: &str) {
+        self.description = Some(description.to_string());
+    }
+
+    /// Get the tags
+    pub fn tags(&self) -> Option<&Vec<String>> {
+        self.tags.as_ref()
+    }
+
+    /// Set the tags
+    pub fn set_tags(&mut self, tags: Vec<String>) {
+        self.tags = Some(tags);
+    }
+
+    /// Check whether any of this entry's tags match a pattern
+    ///
+    /// A pattern ending in `:*`, like `project:*`, matches every tag in that
+    /// namespace, like `project:alpha`. Any other pattern has to match a tag exactly.
+    pub fn has_tag(&self, pattern: &str) -> bool {
+        let Some(tags) = &self.tags else {
+            return false;
+        };
+
+        match pattern.strip_suffix('*') {
+            Some(prefix) if prefix.ends_with(':') => tags.iter().any(|tag| tag.starts_with(prefix)),
+            _ => tags.iter().any(|tag| tag == pattern),
+        }
+    }
+}
+
+/// Round a duration to the nearest multiple of `granularity`, with ties rounded up
+fn round_duration(duration: TimeDelta, granularity: TimeDelta) -> TimeDelta {
+    let granularity = granularity.num_seconds();
+    if granularity <= 0 {
+        return duration;
+    }
+
+    let seconds = duration.num_seconds();
+    let rounded = (seconds + granularity / 2).div_euclid(granularity) * granularity;
+
+    TimeDelta::seconds(rounded)
+}
+
+/// Sums of the time spent in part of the history
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Totals {
+    /// Number of Pomodoros
+    pub pomodoros: usize,
+    /// Time spent in Pomodoros
+    pub focus: TimeDelta,
+    /// Time spent on short and long breaks
+    pub breaks: TimeDelta,
+}
+
+impl<'a> FromIterator<&'a HistoryEntry> for Totals {
+    fn from_iter<I>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = &'a HistoryEntry>,
+    {
+        let mut totals = Self::default();
+
+        for entry in entries {
+            match entry.phase {
+                Phase::Pomodoro => {
+                    totals.pomodoros += 1;
+                    totals.focus += entry.duration;
+                }
+                Phase::ShortBreak | Phase::LongBreak => {
+                    totals.breaks += entry.duration;
+                }
+            }
+        }
+
+        totals
+    }
+}
+
+/// Runs of consecutive days with at least one Pomodoro
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub struct Streaks {
+    /// Number of days in a row, up to today, with a Pomodoro
+    ///
+    /// A streak that reached yesterday still counts, since there's still
+    /// time to keep it going today.
+    pub current: usize,
+    /// Most days in a row there has ever been a Pomodoro
+    pub longest: usize,
+    /// Day of the most recent Pomodoro, if there has been one
+    pub last_day: Option<NaiveDate>,
 }
 
 /// A record of past Pomodoro timers
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct History {
-    pomodoros: Vec<Pomodoro>,
+    #[serde(default)]
+    pomodoros: Vec<HistoryEntry>,
 }
 
 impl History {
@@ -53,15 +217,602 @@ impl History {
         toml::from_str(&history_str).with_context(|| "Failed to parse history file")
     }
 
+    /// Load the history from every file it is split across
+    ///
+    /// With [`Rotation::Monthly`], this reads the history file itself, for
+    /// entries recorded before rotation was turned on, and then every monthly
+    /// file next to it. Entries are sorted by when they started.
+    pub fn load_rotated(path: &Path, rotation: Rotation) -> Result<Self> {
+        let mut history = Self::default();
+
+        for path in Self::rotated_files(path, rotation)? {
+            history.pomodoros.extend(Self::load(&path)?.pomodoros);
+        }
+        history.pomodoros.sort_by_key(|entry| entry.started_at);
+
+        Ok(history)
+    }
+
+    /// Load the history like [`History::load_rotated`], skipping entries that can't be read
+    ///
+    /// See [`History::load_lenient`].
+    pub fn load_lenient_rotated(path: &Path, rotation: Rotation) -> Result<(Self, usize)> {
+        let mut history = Self::default();
+        let mut skipped = 0;
+
+        for path in Self::rotated_files(path, rotation)? {
+            let (loaded, count) = Self::load_lenient(&path)?;
+            history.pomodoros.extend(loaded.pomodoros);
+            skipped += count;
+        }
+        history.pomodoros.sort_by_key(|entry| entry.started_at);
+
+        Ok((history, skipped))
+    }
+
+    /// The file an entry started on `date` is appended to
+    pub fn rotated_path(path: &Path, rotation: Rotation, date: NaiveDate) -> PathBuf {
+        match rotation {
+            Rotation::None => path.to_path_buf(),
+            Rotation::Monthly => {
+                let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+                let mut file_name = format!("{}-{}", stem, date.format("%Y-%m"));
+                if let Some(extension) = path.extension() {
+                    file_name.push('.');
+                    file_name.push_str(&extension.to_string_lossy());
+                }
+                path.with_file_name(file_name)
+            }
+        }
+    }
+
+    /// Every existing file the history is split across, oldest first
+    pub fn rotated_files(path: &Path, rotation: Rotation) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+
+        if path.try_exists()? {
+            files.push(path.to_path_buf());
+        }
+
+        if rotation == Rotation::None {
+            return Ok(files);
+        }
+
+        let Some(dir) = path.parent().filter(|dir| dir.is_dir()) else {
+            return Ok(files);
+        };
+
+        let mut monthly: Vec<PathBuf> = std::fs::read_dir(dir)
+            .with_context(|| format!("Failed to list {}", dir.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|candidate| Self::is_monthly_file(path, candidate))
+            .collect();
+        // Months are zero-padded, so sorting by name sorts them in order
+        monthly.sort();
+        files.extend(monthly);
+
+        Ok(files)
+    }
+
+    fn is_monthly_file(path: &Path, candidate: &Path) -> bool {
+        let (Some(stem), Some(candidate_stem)) = (path.file_stem(), candidate.file_stem()) else {
+            return false;
+        };
+        if path.extension() != candidate.extension() {
+            return false;
+        }
+
+        candidate_stem
+            .to_string_lossy()
+            .strip_prefix(&format!("{}-", stem.to_string_lossy()))
+            .is_some_and(|month| {
+                month.len() == 7
+                    && NaiveDate::parse_from_str(&format!("{}-01", month), "%Y-%m-%d").is_ok()
+            })
+    }
+
+    /// Load the history from a TOML file, skipping entries that can't be read
+    ///
+    /// Entries from before breaks and ids were recorded are read like any other.
+    /// Returns the history along with the number of entries that were skipped.
+    pub fn load_lenient(path: &Path) -> Result<(Self, usize)> {
+        if !path.try_exists()? {
+            return Ok((Self::default(), 0));
+        }
+
+        let history_str = read_to_string(path).with_context(|| "Failed to read history file")?;
+        let mut table: toml::Table =
+            toml::from_str(&history_str).with_context(|| "Failed to parse history file")?;
+
+        let values = match table.remove("pomodoros") {
+            Some(toml::Value::Array(values)) => values,
+            Some(_) => bail!("Failed to parse history file: pomodoros is not an array"),
+            None => Vec::new(),
+        };
+
+        let mut pomodoros = Vec::with_capacity(values.len());
+        let mut skipped = 0;
+        for (index, value) in values.into_iter().enumerate() {
+            match HistoryEntry::deserialize(value) {
+                Ok(entry) => pomodoros.push(entry),
+                Err(e) => {
+                    warn!("Skipping history entry {}: {}", index + 1, e);
+                    skipped += 1;
+                }
+            }
+        }
+
+        Ok((Self { pomodoros }, skipped))
+    }
+
+    /// Overwrite a history file with the contents of this history
+    ///
+    /// The new contents are written to a temporary file in the same directory,
+    /// which then replaces the original file, so an interrupted save never
+    /// leaves a partially-written history behind.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        info!("Saving history to {}", &path.display().to_string().cyan());
+
+        // An empty array can't be followed by the tables appended by `append_entry`
+        let history_str = if self.pomodoros.is_empty() {
+            String::new()
+        } else {
+            toml::to_string(&self).with_context(|| "Unable to serialize history")?
+        };
+
+        crate::write_atomic(path, history_str.as_bytes())
+            .with_context(|| format!("Failed to save history to {}", path.display()))
+    }
+
+    /// Overwrite the files the history is split across
+    ///
+    /// With [`Rotation::Monthly`], each entry is saved to the file for the
+    /// month it started in, and any other file the history was split across,
+    /// like the history file from before rotation was turned on, is emptied.
+    pub fn save_rotated(&self, path: &Path, rotation: Rotation) -> Result<()> {
+        if rotation == Rotation::None {
+            return self.save(path);
+        }
+
+        let mut files: BTreeMap<PathBuf, Self> = Self::rotated_files(path, rotation)?
+            .into_iter()
+            .map(|file| (file, Self::default()))
+            .collect();
+
+        for entry in &self.pomodoros {
+            let file = Self::rotated_path(path, rotation, entry.started_at.date_naive());
+            files.entry(file).or_default().push(entry.clone());
+        }
+
+        for (file, history) in files {
+            history.save(&file)?;
+        }
+
+        Ok(())
+    }
+
+    /// Copy a history file to a timestamped `.bak` file next to it
+    ///
+    /// Returns the path of the copy.
+    pub fn backup(path: &Path) -> Result<PathBuf> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("{} does not name a file", path.display()))?;
+        let backup_path = path.with_file_name(format!(
+            "{}.{}.bak",
+            file_name.to_string_lossy(),
+            Local::now().format("%Y%m%dT%H%M%S%.3f")
+        ));
+
+        info!(
+            "Backing up history to {}",
+            &backup_path.display().to_string().cyan()
+        );
+
+        std::fs::copy(path, &backup_path)
+            .with_context(|| format!("Failed to back up history to {}", backup_path.display()))?;
+
+        Ok(backup_path)
+    }
+
+    /// Parse entries exported from another tool as JSON
+    ///
+    /// The input can be a JSON array of objects, or one object per line.
+    /// Each object needs a `started_at`, as a Unix timestamp or an RFC 3339
+    /// string, and a `duration` in seconds. It can also have an `id`, a
+    /// `phase`, a `description`, and an array of `tags`.
+    pub fn import_json<R>(reader: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        let mut records = Vec::new();
+        for value in serde_json::Deserializer::from_reader(reader).into_iter() {
+            match value.with_context(|| "Failed to parse JSON")? {
+                serde_json::Value::Array(values) => records.extend(values),
+                value => records.push(value),
+            }
+        }
+
+        let pomodoros = records
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let record: JsonRecord = serde_json::from_value(value)
+                    .with_context(|| format!("Entry {} is invalid", i + 1))?;
+                let started_at = match record.started_at {
+                    Some(serde_json::Value::Number(ts)) => Some(
+                        ts.as_i64()
+                            .and_then(|ts| Local.timestamp_opt(ts, 0).single())
+                            .with_context(|| {
+                                format!("Entry {} has an invalid start time", i + 1)
+                            })?,
+                    ),
+                    Some(serde_json::Value::String(dt)) => Some(parse_start_time(&dt, i + 1)?),
+                    Some(serde_json::Value::Null) | None => None,
+                    Some(_) => bail!("Entry {} has an invalid start time", i + 1),
+                };
+
+                imported_entry(
+                    i + 1,
+                    record.id,
+                    record.phase,
+                    started_at,
+                    record.duration,
+                    record.description,
+                    record.tags,
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { pomodoros })
+    }
+
+    /// Parse entries exported from another tool as CSV
+    ///
+    /// The first row is a header naming the columns. `started_at`, as a Unix
+    /// timestamp or an RFC 3339 string, and `duration`, in seconds, are
+    /// required. `id`, `phase`, `description`, and comma-separated `tags`
+    /// columns are optional.
+    pub fn import_csv<R>(reader: R) -> Result<Self>
+    where
+        R: Read,
+    {
+        let pomodoros = csv::Reader::from_reader(reader)
+            .deserialize()
+            .enumerate()
+            .map(|(i, record)| {
+                let record: CsvRecord =
+                    record.with_context(|| format!("Entry {} is invalid", i + 1))?;
+                let started_at = record
+                    .started_at
+                    .map(|dt| parse_start_time(&dt, i + 1))
+                    .transpose()?;
+                let tags = record
+                    .tags
+                    .map(|tags| tags.split(',').map(str::to_owned).collect());
+
+                imported_entry(
+                    i + 1,
+                    record.id,
+                    record.phase,
+                    started_at,
+                    record.duration,
+                    record.description,
+                    tags,
+                )
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Self { pomodoros })
+    }
+
+    /// Check whether an entry with the same id, or the same start time and duration, is already in the history
+    pub fn contains_duplicate(&self, entry: &HistoryEntry) -> bool {
+        self.pomodoros.iter().any(|existing| {
+            (existing.id.is_some() && existing.id == entry.id)
+                || (existing.started_at == entry.started_at && existing.duration == entry.duration)
+        })
+    }
+
+    /// Remove entries that duplicate an earlier one
+    ///
+    /// Entries are duplicates if they have the same id, or the same start time
+    /// and duration. The first one is kept, and takes any id, description, or
+    /// tags it's missing from the ones that are dropped.
+    /// Returns the number of entries that were removed.
+    pub fn dedupe(&mut self) -> usize {
+        let count = self.pomodoros.len();
+        let mut kept = Self::default();
+
+        for entry in self.pomodoros.drain(..) {
+            let existing = kept.pomodoros.iter_mut().find(|existing| {
+                (existing.id.is_some() && existing.id == entry.id)
+                    || (existing.started_at == entry.started_at
+                        && existing.duration == entry.duration)
+            });
+
+            match existing {
+                Some(existing) => {
+                    existing.id = existing.id.or(entry.id);
+                    existing.description = existing.description.take().or(entry.description);
+                    existing.tags = existing.tags.take().or(entry.tags);
+                }
+                None => kept.pomodoros.push(entry),
+            }
+        }
+
+        self.pomodoros = kept.pomodoros;
+        count - self.pomodoros.len()
+    }
+
+    /// Write each entry as a line of compact JSON
+    ///
+    /// Entries are written one at a time, as they are serialized.
+    pub fn write_jsonl<W>(&self, mut writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        for entry in &self.pomodoros {
+            serde_json::to_writer(&mut writer, entry)
+                .with_context(|| "Unable to serialize history entry as JSON")?;
+            writeln!(writer).with_context(|| "Unable to write history entry")?;
+        }
+
+        writer.flush().with_context(|| "Unable to write history")
+    }
+
+    /// Write the history as an iCalendar file, with one event per entry
+    ///
+    /// Times are written in UTC, so calendars show them in their own time zone.
+    /// Events are summarized by their description, or their phase if they
+    /// have none, and tags become categories. `now` is recorded as the time
+    /// the events were exported.
+    pub fn to_ics<W>(&self, mut writer: W, now: DateTime<Local>) -> Result<()>
+    where
+        W: Write,
+    {
+        let mut lines = vec![
+            "BEGIN:VCALENDAR".to_string(),
+            "VERSION:2.0".to_string(),
+            format!("PRODID:-//tomate//tomate {}//EN", env!("CARGO_PKG_VERSION")),
+        ];
+
+        for entry in &self.pomodoros {
+            let uid = match entry.id {
+                Some(id) => id.to_string(),
+                None => format!("{}-{}", entry.phase, entry.started_at.timestamp()),
+            };
+            let summary = match &entry.description {
+                Some(description) => description.clone(),
+                None => entry.phase.to_string(),
+            };
+
+            lines.push("BEGIN:VEVENT".to_string());
+            lines.push(format!("UID:{}@tomate", ics_escape(&uid)));
+            lines.push(format!("DTSTAMP:{}", ics_time(now)));
+            lines.push(format!("DTSTART:{}", ics_time(entry.started_at)));
+            lines.push(format!(
+                "DTEND:{}",
+                ics_time(entry.started_at + entry.duration)
+            ));
+            lines.push(format!("SUMMARY:{}", ics_escape(&summary)));
+            if let Some(tags) = entry.tags.as_ref().filter(|tags| !tags.is_empty()) {
+                let tags: Vec<_> = tags.iter().map(|tag| ics_escape(tag)).collect();
+                lines.push(format!("CATEGORIES:{}", tags.join(",")));
+            }
+            lines.push("END:VEVENT".to_string());
+        }
+
+        lines.push("END:VCALENDAR".to_string());
+
+        for line in lines {
+            write!(writer, "{}\r\n", ics_fold(&line)).with_context(|| "Unable to write history")?;
+        }
+
+        writer.flush().with_context(|| "Unable to write history")
+    }
+
     /// Get the list of historical Pomodoros
-    pub fn pomodoros(&self) -> &Vec<Pomodoro> {
+    pub fn entries(&self) -> &Vec<HistoryEntry> {
         &self.pomodoros
     }
 
+    /// Add up every entry in the history
+    pub fn totals(&self) -> Totals {
+        self.pomodoros.iter().collect()
+    }
+
+    /// Add up the entries that started in an ISO week, in local time
+    ///
+    /// Only entries that started before `before` are counted, so a week that
+    /// is still in progress can be compared to the same part of another week.
+    pub fn week_totals(&self, week: IsoWeek, before: DateTime<Local>) -> Totals {
+        self.pomodoros
+            .iter()
+            .filter(|entry| entry.started_at.iso_week() == week && entry.started_at < before)
+            .collect()
+    }
+
+    /// Add up the duration of every entry, including breaks
+    pub fn total_duration(&self) -> TimeDelta {
+        self.pomodoros.iter().map(|entry| entry.duration).sum()
+    }
+
+    /// Get the entries that started on a day, in local time
+    pub fn entries_for_day(&self, date: NaiveDate) -> Vec<&HistoryEntry> {
+        self.entries_on_day(date, &Local).collect()
+    }
+
+    /// Count the entries that started on a day, in local time
+    pub fn count_for_day(&self, date: NaiveDate) -> usize {
+        self.entries_on_day(date, &Local).count()
+    }
+
+    /// Add up the duration of the entries that started on a day, in local time
+    pub fn duration_for_day(&self, date: NaiveDate) -> TimeDelta {
+        self.entries_on_day(date, &Local)
+            .map(|entry| entry.duration)
+            .sum()
+    }
+
+    /// Get the entries that started on a day in a time zone
+    ///
+    /// Comparing calendar dates keeps days that are shorter or longer than
+    /// 24 hours, like daylight saving time transitions, intact.
+    fn entries_on_day<'a, Tz: TimeZone>(
+        &'a self,
+        date: NaiveDate,
+        tz: &'a Tz,
+    ) -> impl Iterator<Item = &'a HistoryEntry> {
+        self.pomodoros
+            .iter()
+            .filter(move |entry| entry.started_at.with_timezone(tz).date_naive() == date)
+    }
+
+    /// Find the current and longest runs of days with a Pomodoro, in local time
+    pub fn streaks(&self, today: NaiveDate) -> Streaks {
+        let days: BTreeSet<NaiveDate> = self
+            .pomodoros
+            .iter()
+            .filter(|entry| entry.phase == Phase::Pomodoro)
+            .map(|entry| entry.started_at.date_naive())
+            .filter(|day| *day <= today)
+            .collect();
+
+        let mut longest = 0;
+        let mut run = 0;
+        let mut previous: Option<NaiveDate> = None;
+        for day in &days {
+            run = match previous {
+                Some(previous) if previous.succ_opt() == Some(*day) => run + 1,
+                _ => 1,
+            };
+            longest = longest.max(run);
+            previous = Some(*day);
+        }
+
+        let last_day = days.last().copied();
+        let current = match last_day {
+            Some(last_day) if today - last_day <= TimeDelta::days(1) => run,
+            _ => 0,
+        };
+
+        Streaks {
+            current,
+            longest,
+            last_day,
+        }
+    }
+
+    /// Get every distinct tag used in the history, sorted
+    pub fn all_tags(&self) -> BTreeSet<String> {
+        self.tag_counts().into_keys().collect()
+    }
+
+    /// Count the number of entries each tag is used in
+    pub fn tag_counts(&self) -> BTreeMap<String, usize> {
+        let mut counts = BTreeMap::new();
+
+        for tag in self
+            .pomodoros
+            .iter()
+            .filter_map(|entry| entry.tags())
+            .flatten()
+        {
+            *counts.entry(tag.clone()).or_insert(0) += 1;
+        }
+
+        counts
+    }
+
+    /// Count the Pomodoros finished since the most recent long break
+    ///
+    /// Counts every Pomodoro if no long break has been recorded.
+    pub fn poms_since_last_long_break(&self) -> usize {
+        self.pomodoros
+            .iter()
+            .rev()
+            .take_while(|entry| entry.phase != Phase::LongBreak)
+            .filter(|entry| entry.phase == Phase::Pomodoro)
+            .count()
+    }
+
+    /// Count the Pomodoros started on `day` or later, since the most recent long break
+    ///
+    /// This is like [`History::poms_since_last_long_break`], but starts
+    /// counting again at local midnight.
+    pub fn poms_since_last_long_break_on(&self, day: NaiveDate) -> usize {
+        self.pomodoros
+            .iter()
+            .rev()
+            .take_while(|entry| {
+                entry.phase != Phase::LongBreak && entry.started_at.date_naive() >= day
+            })
+            .filter(|entry| entry.phase == Phase::Pomodoro)
+            .count()
+    }
+
+    /// Get the most recent entry
+    pub fn last(&self) -> Option<&HistoryEntry> {
+        self.pomodoros.last()
+    }
+
+    /// Get the most recent Pomodoro, for modification
+    pub fn last_mut(&mut self) -> Option<&mut HistoryEntry> {
+        self.pomodoros.last_mut()
+    }
+
+    /// Add an entry to the end of the history
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.pomodoros.push(entry);
+    }
+
+    /// Keep only the entries that match a predicate
+    pub fn retain<F>(&mut self, f: F)
+    where
+        F: FnMut(&HistoryEntry) -> bool,
+    {
+        self.pomodoros.retain(f);
+    }
+
+    /// Remove every entry that started before `before`
+    ///
+    /// Returns the number of entries that were removed.
+    pub fn prune(&mut self, before: DateTime<Local>) -> usize {
+        let count = self.pomodoros.len();
+        self.pomodoros.retain(|entry| entry.started_at() >= before);
+        count - self.pomodoros.len()
+    }
+
+    /// Keep only the `count` most recent entries
+    pub fn keep_last(&mut self, count: usize) {
+        let excess = self.pomodoros.len().saturating_sub(count);
+        self.pomodoros.drain(..excess);
+    }
+
+    /// Reverse the order of the entries, so the newest comes first
+    pub fn reverse(&mut self) {
+        self.pomodoros.reverse();
+    }
+
     /// Append a new Pomodoro to a history file
     pub fn append(pomodoro: &Pomodoro, history_file_path: &Path) -> Result<()> {
+        Self::append_entry(&HistoryEntry::archive(pomodoro, None)?, history_file_path)
+    }
+
+    /// Append an entry to the file it belongs in, for the month it started if rotation is on
+    pub fn append_rotated(entry: &HistoryEntry, path: &Path, rotation: Rotation) -> Result<()> {
+        Self::append_entry(
+            entry,
+            &Self::rotated_path(path, rotation, entry.started_at.date_naive()),
+        )
+    }
+
+    /// Append an entry to a history file
+    pub fn append_entry(entry: &HistoryEntry, history_file_path: &Path) -> Result<()> {
         info!(
-            "Archiving Pomodoro to {}",
+            "Archiving {} to {}",
+            entry.phase(),
             &history_file_path.display().to_string().cyan()
         );
 
@@ -76,11 +827,684 @@ impl History {
             .append(true)
             .open(history_file_path)?;
 
-        let entry = HistoryEntry::archive(pomodoro)?;
-
-        let pom_str = toml::to_string(&entry)?;
+        let pom_str = toml::to_string(entry)?;
         writeln!(history_file, "[[pomodoros]]\n{}", pom_str)?;
 
         Ok(())
     }
 }
+
+#[derive(Deserialize)]
+struct JsonRecord {
+    id: Option<Uuid>,
+    phase: Option<Phase>,
+    started_at: Option<serde_json::Value>,
+    duration: Option<i64>,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct CsvRecord {
+    id: Option<Uuid>,
+    phase: Option<Phase>,
+    started_at: Option<String>,
+    duration: Option<i64>,
+    description: Option<String>,
+    tags: Option<String>,
+}
+
+/// Format a time for iCalendar, in UTC
+fn ics_time(dt: DateTime<Local>) -> String {
+    dt.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape the characters that are special in iCalendar text values
+fn ics_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace(['\n', '\r'], "\\n")
+}
+
+/// Fold an iCalendar content line so no line is longer than 75 bytes
+fn ics_fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+
+    folded
+}
+
+/// Parse a start time as a Unix timestamp or an RFC 3339 string
+fn parse_start_time(s: &str, number: usize) -> Result<DateTime<Local>> {
+    let start = match s.trim().parse::<i64>() {
+        Ok(ts) => Local.timestamp_opt(ts, 0).single(),
+        Err(_) => DateTime::parse_from_rfc3339(s.trim())
+            .ok()
+            .map(|dt| dt.with_timezone(&Local)),
+    };
+
+    start.with_context(|| format!("Entry {} has an invalid start time \"{}\"", number, s))
+}
+
+/// Validate the fields of an imported entry
+///
+/// `number` is the entry's position in the imported file, starting at 1.
+/// Entries without an id are given a new one.
+fn imported_entry(
+    number: usize,
+    id: Option<Uuid>,
+    phase: Option<Phase>,
+    started_at: Option<DateTime<Local>>,
+    duration: Option<i64>,
+    description: Option<String>,
+    tags: Option<Vec<String>>,
+) -> Result<HistoryEntry> {
+    let started_at = started_at.with_context(|| format!("Entry {} has no start time", number))?;
+    let duration = duration
+        .with_context(|| format!("Entry {} has no duration", number))
+        .and_then(|seconds| {
+            TimeDelta::try_seconds(seconds)
+                .filter(|duration| *duration >= TimeDelta::zero())
+                .with_context(|| format!("Entry {} has an invalid duration", number))
+        })?;
+    let tags = tags
+        .map(|tags| crate::normalize_tags(tags, false))
+        .transpose()
+        .with_context(|| format!("Entry {} has invalid tags", number))?
+        .filter(|tags| !tags.is_empty());
+
+    Ok(HistoryEntry {
+        phase: phase.unwrap_or_default(),
+        started_at,
+        duration,
+        tags,
+        description: description.filter(|description| !description.is_empty()),
+        id: Some(id.unwrap_or_else(Uuid::new_v4)),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{prelude::*, TimeDelta};
+
+    use super::{History, HistoryEntry, Rotation};
+    use crate::{Break, Phase, Pomodoro};
+
+    fn finished_pom(started_at: &str) -> Pomodoro {
+        let dt: DateTime<Local> = started_at.parse().unwrap();
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.finish(dt + TimeDelta::minutes(25));
+        pom
+    }
+
+    #[test]
+    fn amend_last_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        History::append(&finished_pom("2024-03-27T12:00:00-06:00"), &path).unwrap();
+        History::append(&finished_pom("2024-03-27T13:00:00-06:00"), &path).unwrap();
+
+        let mut history = History::load(&path).unwrap();
+        let last = history.last_mut().unwrap();
+        last.set_description("amended");
+        last.set_tags(vec!["work".to_string()]);
+        history.save(&path).unwrap();
+
+        let history = History::load(&path).unwrap();
+        let entries = history.entries();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].description(), None);
+        assert_eq!(entries[1].description(), Some("amended"));
+        assert_eq!(entries[1].tags(), Some(&vec!["work".to_string()]));
+        assert_eq!(entries[1].duration(), TimeDelta::minutes(25));
+        assert!(entries[1].id().is_some());
+        assert_ne!(entries[0].id(), entries[1].id());
+    }
+
+    #[test]
+    fn write_jsonl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        History::append(&finished_pom("2024-03-27T12:00:00-06:00"), &path).unwrap();
+        History::append(&finished_pom("2024-03-27T13:00:00-06:00"), &path).unwrap();
+        History::append(&finished_pom("2024-03-27T14:00:00-06:00"), &path).unwrap();
+
+        let history = History::load(&path).unwrap();
+
+        let mut output = Vec::new();
+        history.write_jsonl(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), history.entries().len());
+
+        for line in lines {
+            let value: serde_json::Value = serde_json::from_str(line).unwrap();
+            assert_eq!(value["duration"], 1500);
+        }
+    }
+
+    #[test]
+    fn old_entries_default_to_pomodoro() {
+        let history: History =
+            toml::from_str("[[pomodoros]]\nstarted_at = 1711562400\nduration = 1500\n").unwrap();
+
+        assert_eq!(history.entries()[0].phase(), Phase::Pomodoro);
+        assert_eq!(history.entries()[0].id(), None);
+    }
+
+    fn entry_at(started_at: DateTime<Local>, minutes: i64) -> HistoryEntry {
+        let rest = Break::new(started_at, TimeDelta::minutes(minutes));
+        HistoryEntry::archive_break(Phase::ShortBreak, &rest, rest.timer().ends_at())
+    }
+
+    #[test]
+    fn totals_for_days_split_at_local_midnight() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 27).unwrap();
+        let local = |date: NaiveDate, h, m| {
+            Local
+                .from_local_datetime(&date.and_hms_opt(h, m, 0).unwrap())
+                .unwrap()
+        };
+
+        let mut history = History::default();
+        history.push(entry_at(local(day, 0, 5), 25));
+        history.push(entry_at(local(day, 23, 50), 25));
+        history.push(entry_at(local(day.succ_opt().unwrap(), 0, 10), 5));
+
+        assert_eq!(history.total_duration(), TimeDelta::minutes(55));
+        assert_eq!(history.count_for_day(day), 2);
+        assert_eq!(history.duration_for_day(day), TimeDelta::minutes(50));
+        assert_eq!(history.count_for_day(day.succ_opt().unwrap()), 1);
+        assert_eq!(
+            history.duration_for_day(day.succ_opt().unwrap()),
+            TimeDelta::minutes(5)
+        );
+        assert_eq!(history.count_for_day(day.pred_opt().unwrap()), 0);
+    }
+
+    #[test]
+    fn entries_on_daylight_saving_day() {
+        use chrono_tz::America::New_York;
+
+        // Clocks in New York sprang forward at 02:00 on 2024-03-10, so the day was 23 hours long
+        let day = NaiveDate::from_ymd_opt(2024, 3, 10).unwrap();
+        let at = |s: &str| {
+            s.parse::<DateTime<FixedOffset>>()
+                .unwrap()
+                .with_timezone(&Local)
+        };
+
+        let mut history = History::default();
+        history.push(entry_at(at("2024-03-09T23:55:00-05:00"), 25));
+        history.push(entry_at(at("2024-03-10T00:05:00-05:00"), 25));
+        history.push(entry_at(at("2024-03-10T23:30:00-04:00"), 25));
+        history.push(entry_at(at("2024-03-11T00:00:00-04:00"), 25));
+
+        let on_day: Vec<_> = history.entries_on_day(day, &New_York).collect();
+
+        assert_eq!(on_day.len(), 2);
+        assert_eq!(on_day[0], &history.entries()[1]);
+        assert_eq!(on_day[1], &history.entries()[2]);
+    }
+
+    #[test]
+    fn week_totals_across_year_boundary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        // 2024-12-30 is the Monday of ISO week 1 of 2025
+        History::append(&finished_pom("2024-12-24T12:00:00Z"), &path).unwrap();
+        History::append(&finished_pom("2024-12-29T12:00:00Z"), &path).unwrap();
+        History::append(&finished_pom("2024-12-31T12:00:00Z"), &path).unwrap();
+        History::append(&finished_pom("2025-01-02T12:00:00Z"), &path).unwrap();
+
+        let break_start: DateTime<Local> = "2024-12-31T13:00:00Z".parse().unwrap();
+        let rest = Break::new(break_start, TimeDelta::minutes(5));
+        let entry = HistoryEntry::archive_break(
+            Phase::ShortBreak,
+            &rest,
+            break_start + rest.timer().duration(),
+        );
+        History::append_entry(&entry, &path).unwrap();
+
+        let history = History::load(&path).unwrap();
+        let now: DateTime<Local> = "2025-01-01T12:00:00Z".parse().unwrap();
+        let last_week = now - TimeDelta::weeks(1);
+
+        let this = history.week_totals(now.iso_week(), now);
+        assert_eq!(this.pomodoros, 1);
+        assert_eq!(this.focus, TimeDelta::minutes(25));
+        assert_eq!(this.breaks, TimeDelta::minutes(5));
+
+        let last = history.week_totals(last_week.iso_week(), last_week);
+        assert_eq!(last.pomodoros, 1);
+
+        let last = history.week_totals(last_week.iso_week(), now);
+        assert_eq!(last.pomodoros, 2);
+        assert_eq!(last.focus, TimeDelta::minutes(50));
+    }
+
+    #[test]
+    fn archive_rounds_to_nearest() {
+        let five_minutes = Some(TimeDelta::minutes(5));
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.finish(dt + TimeDelta::minutes(23));
+        let entry = HistoryEntry::archive(&pom, five_minutes).unwrap();
+        assert_eq!(entry.duration(), TimeDelta::minutes(25));
+
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.finish(dt + TimeDelta::minutes(22));
+        let entry = HistoryEntry::archive(&pom, five_minutes).unwrap();
+        assert_eq!(entry.duration(), TimeDelta::minutes(20));
+
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.finish(dt + TimeDelta::seconds(22 * 60 + 30));
+        let entry = HistoryEntry::archive(&pom, five_minutes).unwrap();
+        assert_eq!(entry.duration(), TimeDelta::minutes(25));
+
+        let entry = HistoryEntry::archive(&pom, None).unwrap();
+        assert_eq!(entry.duration(), TimeDelta::seconds(22 * 60 + 30));
+    }
+
+    #[test]
+    fn count_poms_since_last_long_break() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        let take_break = |phase: Phase, started_at: &str| {
+            let dt: DateTime<Local> = started_at.parse().unwrap();
+            let rest = Break::new(dt, TimeDelta::minutes(5));
+            let entry = HistoryEntry::archive_break(phase, &rest, dt + rest.timer().duration());
+            History::append_entry(&entry, &path).unwrap();
+        };
+
+        History::append(&finished_pom("2024-03-27T09:00:00-06:00"), &path).unwrap();
+        assert_eq!(
+            History::load(&path).unwrap().poms_since_last_long_break(),
+            1
+        );
+
+        take_break(Phase::LongBreak, "2024-03-27T09:30:00-06:00");
+        assert_eq!(
+            History::load(&path).unwrap().poms_since_last_long_break(),
+            0
+        );
+
+        History::append(&finished_pom("2024-03-27T10:00:00-06:00"), &path).unwrap();
+        take_break(Phase::ShortBreak, "2024-03-27T10:30:00-06:00");
+        History::append(&finished_pom("2024-03-27T11:00:00-06:00"), &path).unwrap();
+        assert_eq!(
+            History::load(&path).unwrap().poms_since_last_long_break(),
+            2
+        );
+    }
+
+    #[test]
+    fn count_poms_since_long_break_resets_at_midnight() {
+        let day = NaiveDate::from_ymd_opt(2024, 3, 27).unwrap();
+        let today = day.succ_opt().unwrap();
+        let local = |date: NaiveDate, h, m| {
+            Local
+                .from_local_datetime(&date.and_hms_opt(h, m, 0).unwrap())
+                .unwrap()
+        };
+        let pom_at = |started_at: DateTime<Local>| {
+            let mut pom = Pomodoro::new(started_at, TimeDelta::minutes(25));
+            pom.finish(started_at + TimeDelta::minutes(25));
+            HistoryEntry::archive(&pom, None).unwrap()
+        };
+
+        let mut history = History::default();
+        history.push(pom_at(local(day, 22, 0)));
+        history.push(pom_at(local(day, 22, 30)));
+        history.push(pom_at(local(day, 23, 50)));
+        assert_eq!(history.poms_since_last_long_break_on(today), 0);
+
+        history.push(pom_at(local(today, 0, 20)));
+        assert_eq!(history.poms_since_last_long_break(), 4);
+        assert_eq!(history.poms_since_last_long_break_on(today), 1);
+        assert_eq!(history.poms_since_last_long_break_on(day), 4);
+
+        let rest = Break::new(local(today, 0, 45), TimeDelta::minutes(20));
+        history.push(HistoryEntry::archive_break(
+            Phase::LongBreak,
+            &rest,
+            rest.timer().ends_at(),
+        ));
+        history.push(pom_at(local(today, 1, 10)));
+        assert_eq!(history.poms_since_last_long_break_on(today), 1);
+        assert_eq!(history.poms_since_last_long_break_on(day), 1);
+    }
+
+    #[test]
+    fn match_tags_exactly_or_by_namespace() {
+        let mut entry = HistoryEntry::default();
+        assert!(!entry.has_tag("project:alpha"));
+
+        entry.set_tags(vec![
+            "project:alpha".to_string(),
+            "type:meeting".to_string(),
+        ]);
+        assert!(entry.has_tag("project:alpha"));
+        assert!(entry.has_tag("type:meeting"));
+        assert!(entry.has_tag("project:*"));
+        assert!(entry.has_tag("type:*"));
+
+        assert!(!entry.has_tag("project:beta"));
+        assert!(!entry.has_tag("project"));
+        assert!(!entry.has_tag("client:*"));
+        assert!(!entry.has_tag("proj*"));
+        assert!(!entry.has_tag("project:a"));
+    }
+
+    #[test]
+    fn monthly_rotation_across_a_month_boundary() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+        let local = |y, m, d, h| Local.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap();
+        let pom_at = |started_at: DateTime<Local>| {
+            let mut pom = Pomodoro::new(started_at, TimeDelta::minutes(25));
+            pom.finish(started_at + TimeDelta::minutes(25));
+            HistoryEntry::archive(&pom, None).unwrap()
+        };
+
+        // Recorded before rotation was turned on
+        History::append_entry(&pom_at(local(2024, 2, 20, 9)), &path).unwrap();
+
+        History::append_rotated(&pom_at(local(2024, 3, 31, 23)), &path, Rotation::Monthly).unwrap();
+        History::append_rotated(&pom_at(local(2024, 4, 1, 9)), &path, Rotation::Monthly).unwrap();
+        History::append_rotated(&pom_at(local(2024, 3, 30, 9)), &path, Rotation::Monthly).unwrap();
+
+        let march = dir.path().join("history-2024-03.toml");
+        let april = dir.path().join("history-2024-04.toml");
+        assert_eq!(History::load(&march).unwrap().entries().len(), 2);
+        assert_eq!(History::load(&april).unwrap().entries().len(), 1);
+        assert_eq!(
+            History::rotated_files(&path, Rotation::Monthly).unwrap(),
+            vec![path.clone(), march.clone(), april.clone()]
+        );
+        assert_eq!(
+            History::rotated_files(&path, Rotation::None).unwrap(),
+            vec![path.clone()]
+        );
+
+        let history = History::load_rotated(&path, Rotation::Monthly).unwrap();
+        let starts: Vec<_> = history.entries().iter().map(|e| e.started_at()).collect();
+        assert_eq!(
+            starts,
+            vec![
+                local(2024, 2, 20, 9),
+                local(2024, 3, 30, 9),
+                local(2024, 3, 31, 23),
+                local(2024, 4, 1, 9)
+            ]
+        );
+        assert_eq!(
+            History::load_rotated(&path, Rotation::None)
+                .unwrap()
+                .entries()
+                .len(),
+            1
+        );
+
+        // Saving moves older entries into their month's file
+        let mut history = history;
+        history.prune(local(2024, 3, 31, 0));
+        history.save_rotated(&path, Rotation::Monthly).unwrap();
+        assert!(History::load(&path).unwrap().entries().is_empty());
+        assert_eq!(History::load(&march).unwrap().entries().len(), 1);
+        assert_eq!(
+            History::load_rotated(&path, Rotation::Monthly)
+                .unwrap()
+                .entries()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn tags_are_distinct_and_counted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        for (started_at, tags) in [
+            ("2024-03-27T09:00:00-06:00", vec!["work", "email"]),
+            ("2024-03-27T10:00:00-06:00", vec!["work"]),
+            ("2024-03-27T11:00:00-06:00", vec![]),
+        ] {
+            let mut pom = finished_pom(started_at);
+            pom.set_tags(tags.into_iter().map(String::from).collect());
+            History::append(&pom, &path).unwrap();
+        }
+        History::append(&finished_pom("2024-03-27T12:00:00-06:00"), &path).unwrap();
+
+        let history = History::load(&path).unwrap();
+
+        assert_eq!(
+            history.all_tags().into_iter().collect::<Vec<_>>(),
+            vec!["email", "work"]
+        );
+        assert_eq!(history.tag_counts()["work"], 2);
+        assert_eq!(history.tag_counts()["email"], 1);
+    }
+
+    #[test]
+    fn keep_last_then_reverse() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        for started_at in [
+            "2024-03-27T09:00:00-06:00",
+            "2024-03-27T10:00:00-06:00",
+            "2024-03-27T11:00:00-06:00",
+        ] {
+            History::append(&finished_pom(started_at), &path).unwrap();
+        }
+
+        let mut history = History::load(&path).unwrap();
+        history.keep_last(2);
+        history.reverse();
+
+        let starts: Vec<DateTime<Local>> = history
+            .entries()
+            .iter()
+            .map(HistoryEntry::started_at)
+            .collect();
+        assert_eq!(
+            starts,
+            vec![
+                "2024-03-27T11:00:00-06:00"
+                    .parse::<DateTime<Local>>()
+                    .unwrap(),
+                "2024-03-27T10:00:00-06:00".parse().unwrap(),
+            ]
+        );
+
+        history.keep_last(5);
+        assert_eq!(history.entries().len(), 2);
+    }
+
+    #[test]
+    fn import_json_lines_and_arrays() {
+        let json = r#"
+            [{"started_at": 1711562400, "duration": 1500, "tags": ["work"]}]
+            {"phase": "long_break", "started_at": "2024-03-27T12:30:00-06:00", "duration": 1200}
+        "#;
+
+        let history = History::import_json(json.as_bytes()).unwrap();
+        let entries = history.entries();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].phase(), Phase::Pomodoro);
+        assert_eq!(entries[0].tags(), Some(&vec!["work".to_string()]));
+        assert_eq!(entries[1].phase(), Phase::LongBreak);
+        assert_eq!(
+            entries[1].started_at(),
+            "2024-03-27T12:30:00-06:00"
+                .parse::<DateTime<Local>>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn prune_removes_entries_before_date() {
+        let mut history = History::default();
+        for started_at in [
+            "2023-01-15T09:00:00-06:00",
+            "2024-03-26T23:59:59-06:00",
+            "2024-03-27T00:00:00-06:00",
+            "2024-03-28T09:00:00-06:00",
+        ] {
+            history.push(HistoryEntry::archive(&finished_pom(started_at), None).unwrap());
+        }
+
+        let removed = history.prune("2024-03-27T00:00:00-06:00".parse().unwrap());
+
+        assert_eq!(removed, 2);
+        let survivors: Vec<_> = history
+            .entries()
+            .iter()
+            .map(|entry| entry.started_at().to_rfc3339())
+            .collect();
+        assert_eq!(
+            survivors,
+            [
+                "2024-03-27T00:00:00-06:00"
+                    .parse::<DateTime<Local>>()
+                    .unwrap()
+                    .to_rfc3339(),
+                "2024-03-28T09:00:00-06:00"
+                    .parse::<DateTime<Local>>()
+                    .unwrap()
+                    .to_rfc3339(),
+            ]
+        );
+    }
+
+    #[test]
+    fn append_after_saving_empty_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history.toml");
+
+        History::default().save(&path).unwrap();
+        assert!(History::load(&path).unwrap().entries().is_empty());
+        History::append(&finished_pom("2024-03-27T12:00:00-06:00"), &path).unwrap();
+
+        assert_eq!(History::load(&path).unwrap().entries().len(), 1);
+    }
+
+    #[test]
+    fn ics_has_one_event_per_entry() {
+        let mut history = History::default();
+        let mut pom = Pomodoro::new(
+            "2024-03-27T12:00:00-06:00".parse().unwrap(),
+            TimeDelta::minutes(25),
+        );
+        pom.set_description("emails, calls; more");
+        pom.set_tags(vec!["work".to_string(), "admin".to_string()]);
+        pom.finish("2024-03-27T12:25:00-06:00".parse().unwrap());
+        history.push(HistoryEntry::archive(&pom, None).unwrap());
+        history.push(HistoryEntry::archive_break(
+            Phase::ShortBreak,
+            &Break::new(
+                "2024-03-27T12:25:00-06:00".parse().unwrap(),
+                TimeDelta::minutes(5),
+            ),
+            "2024-03-27T12:30:00-06:00".parse().unwrap(),
+        ));
+
+        let mut ics = Vec::new();
+        history
+            .to_ics(&mut ics, "2024-03-28T09:00:00-06:00".parse().unwrap())
+            .unwrap();
+        let ics = String::from_utf8(ics).unwrap();
+        let lines: Vec<_> = ics.split_terminator("\r\n").collect();
+
+        assert_eq!(lines.first(), Some(&"BEGIN:VCALENDAR"));
+        assert_eq!(lines.last(), Some(&"END:VCALENDAR"));
+        assert_eq!(lines.iter().filter(|l| **l == "BEGIN:VEVENT").count(), 2);
+        assert_eq!(lines.iter().filter(|l| **l == "END:VEVENT").count(), 2);
+        assert!(lines.contains(&"DTSTAMP:20240328T150000Z"));
+        assert!(lines.contains(&"DTSTART:20240327T180000Z"));
+        assert!(lines.contains(&"DTEND:20240327T182500Z"));
+        assert!(lines.contains(&r"SUMMARY:emails\, calls\; more"));
+        assert!(lines.contains(&"CATEGORIES:work,admin"));
+        assert!(lines.contains(&"SUMMARY:short break"));
+        assert!(lines.iter().all(|l| l.len() <= 75));
+    }
+
+    #[test]
+    fn streaks_across_gaps() {
+        let mut history = History::default();
+        for started_at in [
+            "2024-03-01T09:00:00-06:00",
+            "2024-03-02T09:00:00-06:00",
+            "2024-03-03T09:00:00-06:00",
+            "2024-03-03T15:00:00-06:00",
+            "2024-03-10T09:00:00-06:00",
+            "2024-03-11T09:00:00-06:00",
+        ] {
+            history.push(HistoryEntry::archive(&finished_pom(started_at), None).unwrap());
+        }
+        history.push(HistoryEntry::archive_break(
+            Phase::ShortBreak,
+            &Break::new(
+                "2024-03-12T09:00:00-06:00".parse().unwrap(),
+                TimeDelta::minutes(5),
+            ),
+            "2024-03-12T09:05:00-06:00".parse().unwrap(),
+        ));
+
+        let local_day =
+            |started_at: &str| started_at.parse::<DateTime<Local>>().unwrap().date_naive();
+
+        // Entries are bucketed into days in local time, so "today" is too
+        let streaks = history.streaks(local_day("2024-03-11T12:00:00-06:00"));
+        assert_eq!(streaks.longest, 3);
+        assert_eq!(streaks.current, 2);
+        assert_eq!(
+            streaks.last_day,
+            Some(local_day("2024-03-11T09:00:00-06:00"))
+        );
+
+        let streaks = history.streaks(local_day("2024-03-12T12:00:00-06:00"));
+        assert_eq!(streaks.current, 2);
+
+        let streaks = history.streaks(local_day("2024-03-13T12:00:00-06:00"));
+        assert_eq!(streaks.current, 0);
+        assert_eq!(streaks.longest, 3);
+
+        let streaks = history.streaks(local_day("2024-03-02T12:00:00-06:00"));
+        assert_eq!(streaks.longest, 2);
+        assert_eq!(
+            History::default().streaks(local_day("2024-03-02T12:00:00-06:00")),
+            super::Streaks::default()
+        );
+    }
+
+    #[test]
+    fn import_requires_start_and_duration() {
+        let err = History::import_json(r#"{"started_at": 1711562400}"#.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "Entry 1 has no duration");
+
+        let err = History::import_csv("duration\n1500\n".as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "Entry 1 has no start time");
+    }
+}
@@ -1,56 +1,356 @@
-use std::path::Path;
-
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use colored::Colorize;
 use log::info;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
 
-pub fn run_start_hook(hooks_directory: &Path) -> Result<()> {
-    let start_hook_path = hooks_directory.join("start");
+use std::path::{Path, PathBuf};
 
-    if start_hook_path.exists() {
-        info!(
-            "Executing start hook at {}",
-            start_hook_path.display().to_string().cyan()
-        );
+use crate::{Config, Phase};
 
-        std::process::Command::new(start_hook_path)
-            .output()
-            .with_context(|| "Failed to execute start hook")?;
+/// Events that can trigger a user-defined command
+///
+/// A hook runs the command configured for it in the `[hooks]` table of the
+/// config file. If there is none, it runs the executable file in the hooks
+/// directory with the hook's name, if one exists.
+#[derive(
+    Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Deserialize, Serialize, JsonSchema,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Hook {
+    /// A Pomodoro started
+    Start,
+    /// A Pomodoro or break stopped
+    Stop,
+    /// A break started
+    Break,
+    /// A Pomodoro stopped
+    PomodoroEnd,
+    /// A short break started
+    ShortBreakStart,
+    /// A short break stopped
+    ShortBreakEnd,
+    /// A long break started
+    LongBreakStart,
+    /// A long break stopped
+    LongBreakEnd,
+    /// A timer ran out but hasn't been finished yet
+    ///
+    /// Only runs if `reminder_interval` is set in the config.
+    Reminder,
+    /// A Pomodoro is still running
+    ///
+    /// Only runs if `tick_interval` is set in the config, once every interval.
+    /// The seconds left are in the `TOMATE_REMAINING_SECONDS` environment variable.
+    Tick,
+}
+
+impl Hook {
+    /// Every hook, in the order they're documented
+    pub fn all() -> impl Iterator<Item = Self> {
+        [
+            Self::Start,
+            Self::Stop,
+            Self::Break,
+            Self::PomodoroEnd,
+            Self::ShortBreakStart,
+            Self::ShortBreakEnd,
+            Self::LongBreakStart,
+            Self::LongBreakEnd,
+            Self::Reminder,
+            Self::Tick,
+        ]
+        .into_iter()
     }
 
-    Ok(())
-}
+    /// The hook for a kind of timer starting, besides [`Hook::Start`] or [`Hook::Break`]
+    ///
+    /// Pomodoros only have the [`Hook::Start`] hook, so this is `None` for them.
+    pub fn phase_start(phase: Phase) -> Option<Self> {
+        match phase {
+            Phase::Pomodoro => None,
+            Phase::ShortBreak => Some(Self::ShortBreakStart),
+            Phase::LongBreak => Some(Self::LongBreakStart),
+        }
+    }
 
-pub fn run_stop_hook(hooks_directory: &Path) -> Result<()> {
-    let stop_hook_path = hooks_directory.join("stop");
+    /// The hook for a kind of timer stopping, besides [`Hook::Stop`]
+    pub fn phase_end(phase: Phase) -> Self {
+        match phase {
+            Phase::Pomodoro => Self::PomodoroEnd,
+            Phase::ShortBreak => Self::ShortBreakEnd,
+            Phase::LongBreak => Self::LongBreakEnd,
+        }
+    }
 
-    if stop_hook_path.exists() {
-        info!(
-            "Executing stop hook at {}",
-            stop_hook_path.display().to_string().cyan()
-        );
+    /// Name of this hook's executable file in the hooks directory
+    pub fn file_name(&self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Stop => "stop",
+            Self::Break => "break",
+            Self::PomodoroEnd => "pomodoro-end",
+            Self::ShortBreakStart => "short-break-start",
+            Self::ShortBreakEnd => "short-break-end",
+            Self::LongBreakStart => "long-break-start",
+            Self::LongBreakEnd => "long-break-end",
+            Self::Reminder => "reminder",
+            Self::Tick => "tick",
+        }
+    }
+
+    /// A commented shell script showing what this hook's file could do
+    ///
+    /// The script explains how to turn it into a real hook, since `tomate init`
+    /// writes it next to where the hook would go instead of in its place.
+    pub fn example_script(&self) -> String {
+        let (when, message) = match self {
+            Self::Start => ("a Pomodoro starts", "Pomodoro started"),
+            Self::Stop => ("a Pomodoro or break stops", "Timer stopped"),
+            Self::Break => ("a break starts", "Break started"),
+            Self::PomodoroEnd => ("a Pomodoro stops", "Pomodoro finished"),
+            Self::ShortBreakStart => ("a short break starts", "Short break started"),
+            Self::ShortBreakEnd => ("a short break stops", "Short break over"),
+            Self::LongBreakStart => ("a long break starts", "Long break started"),
+            Self::LongBreakEnd => ("a long break stops", "Long break over"),
+            Self::Reminder => (
+                "a timer has run out but isn't finished, if reminder_interval is set",
+                "Time's up! Finish with tomate finish",
+            ),
+            Self::Tick => (
+                "a Pomodoro is running, once every tick_interval if that is set",
+                "$((TOMATE_REMAINING_SECONDS / 60)) minutes left",
+            ),
+        };
+        let name = self.file_name();
+
+        format!(
+            "#!/bin/sh
+# Example tomate hook, run when {when}.
+#
+# To use it, rename this file to \"{name}\" and make it executable:
+#
+#     mv {name}.example {name} && chmod +x {name}
+#
+# Or set a command for it in the [hooks] table of the config file instead:
+#
+#     {name} = \"notify-send 'Tomate'\"
+
+notify-send \"Tomate\" \"{message}\"
+"
+        )
+    }
+
+    /// Path of this hook's executable file in a hooks directory
+    pub fn path(&self, hooks_directory: &Path) -> PathBuf {
+        hooks_directory.join(self.file_name())
+    }
+
+    /// Run this hook's command or executable, if the user has set one up
+    pub fn run(&self, config: &Config) -> Result<()> {
+        self.run_with_env(config, &[])
+    }
+
+    /// Run this hook like [`Hook::run`], with extra environment variables set
+    pub fn run_with_env(&self, config: &Config, env: &[(&str, String)]) -> Result<()> {
+        let name = self.file_name();
+
+        let mut command = if let Some(command_line) = config.hooks.get(self) {
+            let mut words = split_command(command_line)
+                .with_context(|| format!("Invalid command for {} hook", name))?
+                .into_iter();
+            let program = words
+                .next()
+                .with_context(|| format!("Command for {} hook is empty", name))?;
+
+            if config.dry_run {
+                info!(
+                    "Dry run: would execute {} hook command {}",
+                    name,
+                    command_line.cyan()
+                );
+                return Ok(());
+            }
+
+            info!("Executing {} hook command {}", name, command_line.cyan());
+
+            let mut command = std::process::Command::new(program);
+            command.args(words);
+            command
+        } else {
+            let hook_path = self.path(&config.hooks_directory);
+
+            if !hook_path.exists() {
+                return Ok(());
+            }
+
+            if config.dry_run {
+                info!(
+                    "Dry run: would execute {} hook at {}",
+                    name,
+                    hook_path.display().to_string().cyan()
+                );
+                return Ok(());
+            }
+
+            info!(
+                "Executing {} hook at {}",
+                name,
+                hook_path.display().to_string().cyan()
+            );
+
+            std::process::Command::new(hook_path)
+        };
 
-        std::process::Command::new(stop_hook_path)
+        command
+            .envs(env.iter().map(|(key, value)| (key, value)))
             .output()
-            .with_context(|| "Failed to execute stop hook")?;
+            .with_context(|| format!("Failed to execute {} hook", name))?;
+
+        Ok(())
+    }
+}
+
+/// Split a command line into words, the way a shell would
+///
+/// Words are separated by whitespace. Single quotes preserve everything
+/// inside them, double quotes preserve everything except backslash escapes,
+/// and a backslash outside of single quotes escapes the next character.
+pub(crate) fn split_command(command_line: &str) -> Result<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = command_line.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated single quote"),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c) => word.push(c),
+                            None => bail!("Unterminated double quote"),
+                        },
+                        Some(c) => word.push(c),
+                        None => bail!("Unterminated double quote"),
+                    }
+                }
+            }
+            '\\' => {
+                in_word = true;
+                match chars.next() {
+                    Some(c) => word.push(c),
+                    None => bail!("Trailing backslash"),
+                }
+            }
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            c => {
+                in_word = true;
+                word.push(c);
+            }
+        }
+    }
+
+    if in_word {
+        words.push(word);
     }
 
-    Ok(())
+    Ok(words)
 }
 
-pub fn run_break_hook(hooks_directory: &Path) -> Result<()> {
-    let break_hook_path = hooks_directory.join("break");
+#[cfg(test)]
+mod test {
+    use super::{split_command, Hook};
+    use crate::Config;
+
+    #[test]
+    fn file_names_are_unique() {
+        let mut names: Vec<&str> = Hook::all().map(|hook| hook.file_name()).collect();
+        names.sort();
+        names.dedup();
+
+        assert_eq!(names.len(), Hook::all().count());
+    }
+
+    #[test]
+    fn config_keys_match_file_names() {
+        for hook in Hook::all() {
+            let key: Hook = toml::Value::String(hook.file_name().to_string())
+                .try_into()
+                .unwrap();
+
+            assert_eq!(key, hook);
+        }
+    }
+
+    #[test]
+    fn split_plain_words() {
+        let words = split_command("notify-send  Tomate done").unwrap();
+
+        assert_eq!(words, vec!["notify-send", "Tomate", "done"]);
+    }
+
+    #[test]
+    fn split_quoted_words() {
+        let words = split_command(r#"notify-send 'Pomodoro done' "it's \"over\"" a\ b"#).unwrap();
 
-    if break_hook_path.exists() {
-        info!(
-            "Executing break hook at {}",
-            break_hook_path.display().to_string().cyan()
+        assert_eq!(
+            words,
+            vec!["notify-send", "Pomodoro done", r#"it's "over""#, "a b"]
         );
+    }
 
-        std::process::Command::new(break_hook_path)
-            .output()
-            .with_context(|| "Failed to execute break hook")?;
+    #[test]
+    fn split_unterminated_quote() {
+        assert!(split_command("notify-send 'oops").is_err());
     }
 
-    Ok(())
+    #[cfg(unix)]
+    #[test]
+    fn configured_command_takes_precedence() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let hooks_directory = dir.path().join("hooks");
+        std::fs::create_dir(&hooks_directory).unwrap();
+
+        let file_hook = hooks_directory.join("start");
+        std::fs::write(
+            &file_hook,
+            format!("#!/bin/sh\ntouch '{}'\n", dir.path().join("file").display()),
+        )
+        .unwrap();
+        std::fs::set_permissions(&file_hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let mut config = Config {
+            hooks_directory,
+            ..Default::default()
+        };
+        config.hooks.insert(
+            Hook::Start,
+            format!("touch '{}'", dir.path().join("command").display()),
+        );
+
+        Hook::Start.run(&config).unwrap();
+
+        assert!(dir.path().join("command").exists());
+        assert!(!dir.path().join("file").exists());
+    }
 }
@@ -0,0 +1,100 @@
+//! Finding out how long the user has been away from the computer
+//!
+//! Idle time is only used to pause Pomodoros if `idle_timeout` is set in the
+//! config. It is read through the [`IdleSource`] trait, so callers can swap
+//! in their own way of measuring it.
+
+use std::process::Command;
+
+use chrono::TimeDelta;
+use log::debug;
+
+use crate::Result;
+
+/// A way to find out how long it's been since the user last used the computer
+pub trait IdleSource {
+    /// Get the time since the last keyboard or mouse input
+    ///
+    /// Returns `None` if the idle time can't be measured here.
+    fn idle_time(&self) -> Result<Option<TimeDelta>>;
+}
+
+/// An [`IdleSource`] that asks the desktop session
+///
+/// On X11 this runs `xprintidle`. Otherwise it asks GNOME's idle monitor
+/// over D-Bus with `gdbus`, which also works on Wayland.
+/// If neither is available, the idle time is unknown.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SessionIdle;
+
+impl IdleSource for SessionIdle {
+    fn idle_time(&self) -> Result<Option<TimeDelta>> {
+        if let Some(millis) = run("xprintidle", &[]).and_then(|out| parse_xprintidle(&out)) {
+            return Ok(Some(TimeDelta::milliseconds(millis)));
+        }
+
+        let gnome = run(
+            "gdbus",
+            &[
+                "call",
+                "--session",
+                "--dest",
+                "org.gnome.Mutter.IdleMonitor",
+                "--object-path",
+                "/org/gnome/Mutter/IdleMonitor/Core",
+                "--method",
+                "org.gnome.Mutter.IdleMonitor.GetIdletime",
+            ],
+        );
+        if let Some(millis) = gnome.and_then(|out| parse_gdbus(&out)) {
+            return Ok(Some(TimeDelta::milliseconds(millis)));
+        }
+
+        Ok(None)
+    }
+}
+
+/// Run a command, returning its output if it succeeded
+fn run(program: &str, args: &[&str]) -> Option<String> {
+    match Command::new(program).args(args).output() {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).into_owned())
+        }
+        Ok(output) => {
+            debug!("{} exited with {}", program, output.status);
+            None
+        }
+        Err(e) => {
+            debug!("Failed to run {}: {}", program, e);
+            None
+        }
+    }
+}
+
+/// Parse `xprintidle` output, which is the idle time in milliseconds
+fn parse_xprintidle(output: &str) -> Option<i64> {
+    output.trim().parse().ok()
+}
+
+/// Parse a `gdbus` reply like `(uint64 12345,)`, in milliseconds
+fn parse_gdbus(output: &str) -> Option<i64> {
+    output
+        .trim()
+        .strip_prefix("(uint64 ")?
+        .strip_suffix(",)")?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_gdbus, parse_xprintidle};
+
+    #[test]
+    fn parse_idle_outputs() {
+        assert_eq!(parse_xprintidle("4521\n"), Some(4521));
+        assert_eq!(parse_xprintidle("nope"), None);
+        assert_eq!(parse_gdbus("(uint64 98765,)\n"), Some(98765));
+        assert_eq!(parse_gdbus("Error: no such service"), None);
+    }
+}
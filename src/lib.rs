@@ -13,43 +13,348 @@
 use std::{
     fs::OpenOptions,
     io::{Read, Write},
-    path::Path,
+    path::{Path, PathBuf},
 };
 
-use anyhow::{anyhow, bail, Context, Result};
-use chrono::prelude::*;
+use anyhow::Context;
+use chrono::{prelude::*, TimeDelta};
 use colored::Colorize;
 use log::info;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
+mod breaks;
+pub use breaks::Break;
 mod config;
-pub use config::{default_config_path, Config};
+mod error;
+pub use config::{default_config_path, Config, PomodoroTemplate, Profile};
+pub use error::TomateError;
+pub mod doctor;
 mod history;
-pub use history::History;
+pub use history::{History, HistoryEntry, Rotation, Streaks, Totals};
 mod hooks;
+pub use hooks::Hook;
+pub mod idle;
 mod pomodoro;
-pub use pomodoro::Pomodoro;
+pub use pomodoro::{normalize_tags, Pomodoro, PomodoroBuilder};
+#[cfg(unix)]
+pub mod serve;
+mod sound;
+pub use sound::play_sound;
 mod time;
-pub use time::Timer;
+pub use time::{duration, humanize_ago, Clock, FixedClock, SystemClock, Timer};
+
+/// Kinds of timers in the Pomodoro technique
+#[derive(
+    Clone,
+    Copy,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    Hash,
+    Debug,
+    Default,
+    Deserialize,
+    Serialize,
+    JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// A period of focused work
+    #[default]
+    Pomodoro,
+    /// A short rest between Pomodoros
+    ShortBreak,
+    /// A longer rest after several Pomodoros
+    LongBreak,
+}
+
+/// Result of a library function that can fail with a [`TomateError`]
+type Result<T, E = TomateError> = std::result::Result<T, E>;
+
+/// File formats the state file can be written in
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default, Deserialize, Serialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum StateFormat {
+    /// TOML, like the config and history files
+    #[default]
+    Toml,
+    /// JSON, for tools that would rather not parse TOML
+    Json,
+}
+
+/// Kinds of break to take after a Pomodoro
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum BreakKind {
+    /// A short break, between most Pomodoros
+    Short,
+    /// A long break, after several Pomodoros
+    Long,
+}
 
 /// Phases of the Pomodoro technique
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Deserialize, Serialize, JsonSchema)]
 pub enum Status {
     /// No Pomodoro or break is active
     Inactive,
     /// A Pomodoro is active
     Active(Pomodoro),
     /// A timer for a short break is active
-    ShortBreak(Timer),
+    ShortBreak(Break),
     /// A timer for a long break is active
-    LongBreak(Timer),
+    LongBreak(Break),
+}
+
+impl Phase {
+    /// Get the glyph printed for this kind of timer when none is configured
+    pub fn default_icon(self) -> &'static str {
+        match self {
+            Self::Pomodoro => "🍅",
+            Self::ShortBreak => "☕",
+            Self::LongBreak => "🛋",
+        }
+    }
+}
+
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pomodoro => write!(f, "pomodoro"),
+            Self::ShortBreak => write!(f, "short break"),
+            Self::LongBreak => write!(f, "long break"),
+        }
+    }
 }
 
 impl Status {
+    /// Get the kind of timer that is active, if any
+    pub fn phase(&self) -> Option<Phase> {
+        match self {
+            Self::Inactive => None,
+            Self::Active(_) => Some(Phase::Pomodoro),
+            Self::ShortBreak(_) => Some(Phase::ShortBreak),
+            Self::LongBreak(_) => Some(Phase::LongBreak),
+        }
+    }
+
+    /// Check if a Pomodoro is active
+    ///
+    /// This is false during breaks, see [`Status::is_break`].
+    pub fn is_active(&self) -> bool {
+        matches!(self, Self::Active(_))
+    }
+
+    /// Check if a short or long break is active
+    pub fn is_break(&self) -> bool {
+        matches!(self, Self::ShortBreak(_) | Self::LongBreak(_))
+    }
+
+    /// Get the amount of time left on the active timer, if any
+    pub fn remaining(&self, now: DateTime<Local>) -> Option<TimeDelta> {
+        self.timer().map(|timer| timer.remaining(now))
+    }
+
+    /// Get the description of the active Pomodoro or break, if any
+    pub fn description(&self) -> Option<&str> {
+        match self {
+            Self::Inactive => None,
+            Self::Active(pom) => pom.description(),
+            Self::ShortBreak(rest) | Self::LongBreak(rest) => rest.description(),
+        }
+    }
+
+    fn timer(&self) -> Option<&Timer> {
+        match self {
+            Self::Inactive => None,
+            Self::Active(pom) => Some(pom.timer()),
+            Self::ShortBreak(rest) | Self::LongBreak(rest) => Some(rest.timer()),
+        }
+    }
+
+    fn timer_mut(&mut self) -> Option<&mut Timer> {
+        match self {
+            Self::Inactive => None,
+            Self::Active(pom) => Some(pom.timer_mut()),
+            Self::ShortBreak(rest) | Self::LongBreak(rest) => Some(rest.timer_mut()),
+        }
+    }
+
+    /// Start a Pomodoro, returning the status that results
+    ///
+    /// This only computes the next status, it does not save anything or run
+    /// any hooks. Use [`start`] to do all of that at once.
+    pub fn start(&self, pomodoro: Pomodoro) -> Result<Self> {
+        match self {
+            Self::ShortBreak(_) | Self::LongBreak(_) => Err(TomateError::OnBreak),
+            Self::Active(_) => Err(TomateError::AlreadyActive),
+            Self::Inactive => Ok(Self::Active(pomodoro)),
+        }
+    }
+
+    /// Start a new Pomodoro from its parts, returning the status that results
+    ///
+    /// See [`Status::start`].
+    pub fn start_pomodoro(
+        &self,
+        now: DateTime<Local>,
+        duration: TimeDelta,
+        description: Option<&str>,
+        tags: Option<Vec<String>>,
+    ) -> Result<Self> {
+        let mut pomodoro = Pomodoro::new(now, duration);
+
+        if let Some(description) = description {
+            pomodoro.set_description(description);
+        }
+
+        if let Some(tags) = tags {
+            pomodoro.set_tags(tags);
+        }
+
+        self.start(pomodoro)
+    }
+
+    /// Start the current Pomodoro over, returning the status that results
+    ///
+    /// The new Pomodoro keeps the description and tags of the current one,
+    /// but starts at `now` and lasts for `duration`, or the current Pomodoro's
+    /// duration if that is `None`. The current Pomodoro is discarded without
+    /// being archived.
+    /// This only computes the next status, it does not save anything or run
+    /// any hooks. Use [`restart`] to do all of that at once.
+    pub fn restart(&self, now: DateTime<Local>, duration: Option<TimeDelta>) -> Result<Self> {
+        match self {
+            Self::Inactive => Err(TomateError::NoActiveTimer),
+            Self::ShortBreak(_) | Self::LongBreak(_) => Err(TomateError::OnBreak),
+            Self::Active(pom) => {
+                let duration = duration.unwrap_or(pom.timer().duration());
+                let mut restarted = Pomodoro::new(now, duration);
+
+                if let Some(description) = pom.description() {
+                    restarted.set_description(description);
+                }
+
+                if let Some(tags) = pom.tags() {
+                    restarted.set_tags(tags.clone());
+                }
+
+                Ok(Self::Active(restarted))
+            }
+        }
+    }
+
+    /// Split the current Pomodoro at `now`, returning the status that results
+    ///
+    /// The current Pomodoro is returned as well, marked as finished at `now`
+    /// and ready to be archived. The new Pomodoro keeps its description and
+    /// tags, starts at `now`, and lasts for the time that was left.
+    /// This only computes the next status, it does not save anything or run
+    /// any hooks. Use [`split`] to do all of that at once.
+    pub fn split(&self, now: DateTime<Local>) -> Result<(Self, Pomodoro)> {
+        match self {
+            Self::Inactive => Err(TomateError::NoActiveTimer),
+            Self::ShortBreak(_) | Self::LongBreak(_) => Err(TomateError::OnBreak),
+            Self::Active(pom) => {
+                let remaining = pom.timer().remaining(now);
+                if remaining <= TimeDelta::zero() {
+                    return Err(TomateError::TimerRanOut);
+                }
+
+                let next = self.restart(now, Some(remaining))?;
+
+                let mut finished = pom.clone();
+                finished.finish(now);
+
+                Ok((next, finished))
+            }
+        }
+    }
+
+    /// Change the description and tags of the current Pomodoro, returning the status that results
+    ///
+    /// Only the values that are `Some` are changed. The Pomodoro keeps
+    /// running as it was.
+    /// This only computes the next status, it does not save anything or run
+    /// any hooks. Use [`describe`] to do all of that at once.
+    pub fn describe(&self, description: Option<&str>, tags: Option<Vec<String>>) -> Result<Self> {
+        match self {
+            Self::Inactive => Err(TomateError::NoActiveTimer),
+            Self::ShortBreak(_) | Self::LongBreak(_) => Err(TomateError::OnBreak),
+            Self::Active(pom) => {
+                let mut pom = pom.clone();
+
+                if let Some(description) = description {
+                    pom.set_description(description);
+                }
+
+                if let Some(tags) = tags {
+                    pom.set_tags(tags);
+                }
+
+                Ok(Self::Active(pom))
+            }
+        }
+    }
+
+    /// Start a short break, returning the status that results
+    ///
+    /// This only computes the next status, it does not save anything or run
+    /// any hooks. Use [`take_short_break`] to do all of that at once.
+    pub fn start_short_break(&self, rest: impl Into<Break>) -> Result<Self> {
+        self.check_can_take_break()?;
+
+        Ok(Self::ShortBreak(rest.into()))
+    }
+
+    /// Start a long break, returning the status that results
+    ///
+    /// This only computes the next status, it does not save anything or run
+    /// any hooks. Use [`take_long_break`] to do all of that at once.
+    pub fn start_long_break(&self, rest: impl Into<Break>) -> Result<Self> {
+        self.check_can_take_break()?;
+
+        Ok(Self::LongBreak(rest.into()))
+    }
+
+    fn check_can_take_break(&self) -> Result<()> {
+        match self {
+            Self::Active(_) => Err(TomateError::AlreadyActive),
+            Self::ShortBreak(_) | Self::LongBreak(_) => Err(TomateError::OnBreak),
+            Self::Inactive => Ok(()),
+        }
+    }
+
+    /// Finish the current Pomodoro or break, returning the status that results
+    ///
+    /// If a Pomodoro was active, it is returned as well, marked as finished at `now`
+    /// and ready to be archived.
+    /// This only computes the next status, it does not save anything or run
+    /// any hooks. Use [`finish`] to do all of that at once.
+    pub fn finish(&self, now: DateTime<Local>) -> Result<(Self, Option<Pomodoro>)> {
+        match self {
+            Self::Inactive => Err(TomateError::NoActiveTimer),
+            Self::ShortBreak(_) | Self::LongBreak(_) => Ok((Self::Inactive, None)),
+            Self::Active(pom) => {
+                let mut pom = pom.clone();
+                pom.finish(now);
+
+                Ok((Self::Inactive, Some(pom)))
+            }
+        }
+    }
+
     /// Load from a state file
     pub fn load(state_file_path: &Path) -> Result<Self> {
-        if state_file_path.try_exists()? {
-            let file = OpenOptions::new().read(true).open(state_file_path)?;
+        let exists = state_file_path
+            .try_exists()
+            .map_err(TomateError::io(state_file_path))?;
+        if exists {
+            let file = OpenOptions::new()
+                .read(true)
+                .open(state_file_path)
+                .map_err(TomateError::io(state_file_path))?;
             Self::from_reader(file)
         } else {
             Ok(Self::Inactive)
@@ -57,6 +362,9 @@ impl Status {
     }
 
     /// Load state from a reader
+    ///
+    /// The state can be in any [`StateFormat`]. JSON is recognized by its
+    /// opening brace, which a TOML file can't start with.
     pub fn from_reader<R>(reader: R) -> Result<Self>
     where
         R: Read,
@@ -64,45 +372,46 @@ impl Status {
         let state_str =
             std::io::read_to_string(reader).with_context(|| "Failed to read state file")?;
 
-        toml::from_str(&state_str).with_context(|| "Failed to parse state file")
+        if state_str.trim_start().starts_with('{') {
+            serde_json::from_str(&state_str).map_err(|e| TomateError::Parse(e.into()))
+        } else {
+            toml::from_str(&state_str).map_err(|e| TomateError::Parse(e.into()))
+        }
     }
 
     /// Save this status as a TOML file
+    ///
+    /// Use [`Status::save_as`] to pick the format.
     pub fn save(&self, state_file_path: &Path) -> Result<()> {
+        self.save_as(state_file_path, StateFormat::Toml)
+    }
+
+    /// Save this status to a file in the given format
+    pub fn save_as(&self, state_file_path: &Path, format: StateFormat) -> Result<()> {
         match &self {
             Self::Inactive => {
                 info!(
                     "Deleting current Pomodoro state file {}",
                     &state_file_path.display().to_string().cyan()
                 );
-                std::fs::remove_file(state_file_path)?;
+                std::fs::remove_file(state_file_path).map_err(TomateError::io(state_file_path))?;
                 Ok(())
             }
             _ => {
-                if !state_file_path.try_exists()? {
+                let exists = state_file_path
+                    .try_exists()
+                    .map_err(TomateError::io(state_file_path))?;
+                if !exists {
                     info!(
                         "Creating Pomodoro state file {}",
                         &state_file_path.display().to_string().cyan()
                     );
                 }
 
-                let state_file_dir = state_file_path
-                    .parent()
-                    .with_context(|| "State file path does not have a parent directory")?;
-                std::fs::create_dir_all(state_file_dir)
-                    .with_context(|| "Failed to create directory for state file")?;
-
-                let file = OpenOptions::new()
-                    .create(true)
-                    .read(true)
-                    .write(true)
-                    .truncate(true)
-                    .open(state_file_path)
-                    .with_context(|| {
-                        format!("Unable to open state file {}", state_file_path.display())
-                    })?;
-
-                self.to_writer(file).with_context(|| {
+                write_atomic_with(state_file_path, |writer| {
+                    Ok(self.to_writer_as(writer, format)?)
+                })
+                .with_context(|| {
                     format!("Failed to save Pomodoro to {}", state_file_path.display())
                 })?;
 
@@ -112,109 +421,497 @@ impl Status {
     }
 
     /// Save this pomodoro to an output stream
-    pub fn to_writer<W>(&self, mut writer: W) -> Result<()>
+    ///
+    /// Use [`Status::to_writer_as`] to pick the format.
+    pub fn to_writer<W>(&self, writer: W) -> Result<()>
+    where
+        W: Write,
+    {
+        self.to_writer_as(writer, StateFormat::Toml)
+    }
+
+    /// Save this pomodoro to an output stream in the given format
+    pub fn to_writer_as<W>(&self, mut writer: W, format: StateFormat) -> Result<()>
     where
         W: Write,
     {
-        let contents = toml::to_string(&self).with_context(|| "Unable to serialize Pomodoro")?;
+        let contents = match format {
+            StateFormat::Toml => {
+                toml::to_string(&self).with_context(|| "Unable to serialize Pomodoro")?
+            }
+            StateFormat::Json => serde_json::to_string_pretty(&self)
+                .map(|json| json + "\n")
+                .with_context(|| "Unable to serialize Pomodoro")?,
+        };
 
         writer
             .write_all(contents.as_bytes())
-            .with_context(|| "Unable to save Pomodoro to writer")
+            .with_context(|| "Unable to save Pomodoro to writer")?;
+
+        Ok(())
+    }
+}
+
+/// Count the Pomodoros that count toward the next long break
+///
+/// These are the Pomodoros since the last long break, or only the ones
+/// started today if `reset_long_break_daily` is set in the config.
+pub fn poms_toward_long_break(history: &History, config: &Config, now: DateTime<Local>) -> usize {
+    if config.reset_long_break_daily {
+        history.poms_since_last_long_break_on(now.date_naive())
+    } else {
+        history.poms_since_last_long_break()
+    }
+}
+
+/// Decide which kind of break to take next
+///
+/// A long break is due once `poms_before_long_break` Pomodoros have been
+/// finished since the last one, as counted by [`poms_toward_long_break`],
+/// so with the default of 4, every fourth break is long. Every break is
+/// short if `poms_before_long_break` is 0.
+pub fn next_break(history: &History, config: &Config, now: DateTime<Local>) -> BreakKind {
+    let poms = poms_toward_long_break(history, config, now);
+
+    if config.poms_before_long_break > 0 && poms >= config.poms_before_long_break {
+        BreakKind::Long
+    } else {
+        BreakKind::Short
     }
 }
 
 /// Start a Pomodoro timer
+///
+/// The Pomodoro's tags are cleaned up with [`normalize_tags`] before it is saved.
+/// Fails with [`TomateError::TooShort`] or [`TomateError::TooLong`] if the
+/// Pomodoro's duration is outside the limits set in the config.
 pub fn start(config: &Config, pomodoro: Pomodoro) -> Result<Status> {
+    check_pomodoro_duration(config, &pomodoro)?;
+    let pomodoro = normalize_pomodoro_tags(config, pomodoro)?;
+
     let status = Status::load(&config.state_file_path)?;
+    let next_status = status.start(pomodoro)?;
 
-    match status {
-        Status::ShortBreak(_timer) => Err(anyhow!("You're currently taking a break!")),
-        Status::LongBreak(_timer) => Err(anyhow!("You're currently taking a break!")),
-        Status::Active(_pom) => Err(anyhow!("There is already an unfinished Pomodoro")),
-        Status::Inactive => {
-            let next_status = Status::Active(pomodoro);
-            next_status
-                .save(&config.state_file_path)
-                .with_context(|| "Unable to save new Pomodoro")?;
+    save_status(config, &next_status).with_context(|| "Unable to save new Pomodoro")?;
 
-            hooks::run_start_hook(&config.hooks_directory)?;
+    Hook::Start.run(config)?;
 
-            Ok(next_status)
-        }
+    Ok(next_status)
+}
+
+/// Finish the current break and start a Pomodoro right away
+///
+/// The new Pomodoro is saved before the break is archived, so if it can't be
+/// started, the break keeps running and nothing is added to the history.
+/// The stop hooks for the break run after that, followed by the start hook.
+pub fn finish_break_and_start(
+    config: &Config,
+    clock: &dyn Clock,
+    pomodoro: Pomodoro,
+) -> Result<Status> {
+    let status = Status::load(&config.state_file_path)?;
+    let (phase, rest) = match &status {
+        Status::ShortBreak(rest) => (Phase::ShortBreak, rest),
+        Status::LongBreak(rest) => (Phase::LongBreak, rest),
+        _ => return Err(TomateError::NoActiveBreak),
+    };
+
+    check_pomodoro_duration(config, &pomodoro)?;
+    let pomodoro = normalize_pomodoro_tags(config, pomodoro)?;
+    let next_status = Status::Inactive.start(pomodoro)?;
+    let entry = HistoryEntry::archive_break(phase, rest, clock.now());
+
+    save_status(config, &next_status).with_context(|| "Unable to save new Pomodoro")?;
+
+    append_history(config, &entry)?;
+
+    sound::play_for(config, phase);
+
+    Hook::Stop.run(config)?;
+    Hook::phase_end(phase).run(config)?;
+    Hook::Start.run(config)?;
+
+    Ok(next_status)
+}
+
+/// Discard the current Pomodoro and start it over from now
+///
+/// See [`Status::restart`]. Like [`start`], this fails if the restarted
+/// Pomodoro's duration is outside the limits set in the config.
+pub fn restart(config: &Config, clock: &dyn Clock, duration: Option<TimeDelta>) -> Result<Status> {
+    let status = Status::load(&config.state_file_path)?;
+    let next_status = status.restart(clock.now(), duration)?;
+    if let Status::Active(pom) = &next_status {
+        check_pomodoro_duration(config, pom)?;
+    }
+
+    save_status(config, &next_status).with_context(|| "Unable to save restarted Pomodoro")?;
+
+    Hook::Start.run(config)?;
+
+    Ok(next_status)
+}
+
+/// Archive the time spent on the current Pomodoro so far and keep going in a new one
+///
+/// See [`Status::split`]. Like [`start`], this fails if the new Pomodoro's
+/// duration is outside the limits set in the config, in which case nothing
+/// is archived.
+pub fn split(config: &Config, clock: &dyn Clock) -> Result<Status> {
+    let status = Status::load(&config.state_file_path)?;
+    let (next_status, finished) = status.split(clock.now())?;
+    if let Status::Active(pom) = &next_status {
+        check_pomodoro_duration(config, pom)?;
     }
+
+    append_history(
+        config,
+        &HistoryEntry::archive(&finished, config.history_rounding)?,
+    )?;
+
+    save_status(config, &next_status).with_context(|| "Unable to save split Pomodoro")?;
+
+    Hook::Start.run(config)?;
+
+    Ok(next_status)
+}
+
+/// Change the description and tags of the current Pomodoro
+///
+/// See [`Status::describe`].
+pub fn describe(
+    config: &Config,
+    description: Option<&str>,
+    tags: Option<Vec<String>>,
+) -> Result<Status> {
+    let tags = tags
+        .map(|tags| normalize_tags(tags, config.lowercase_tags))
+        .transpose()?;
+
+    let status = Status::load(&config.state_file_path)?;
+    let next_status = status.describe(description, tags)?;
+
+    save_status(config, &next_status).with_context(|| "Unable to save described Pomodoro")?;
+
+    Ok(next_status)
 }
 
 /// Start a short break timer
-pub fn take_short_break(config: &Config, timer: Timer) -> Result<()> {
+pub fn take_short_break(config: &Config, rest: impl Into<Break>) -> Result<()> {
     let status = Status::load(&config.state_file_path)?;
+    let next_status = status.start_short_break(rest)?;
 
-    match status {
-        Status::Active(_) => Err(anyhow!("Finish your current timer before taking a break")),
-        Status::ShortBreak(_) => Err(anyhow!("You are already taking a break")),
-        Status::LongBreak(_) => Err(anyhow!("You are already taking a break")),
-        Status::Inactive => {
-            let new_status = Status::ShortBreak(timer.clone());
-            new_status.save(&config.state_file_path)?;
+    save_status(config, &next_status)?;
 
-            hooks::run_break_hook(&config.hooks_directory)?;
+    Hook::Break.run(config)?;
+    Hook::ShortBreakStart.run(config)?;
 
-            Ok(())
-        }
-    }
+    Ok(())
 }
 
 /// Start a long break timer
-pub fn take_long_break(config: &Config, timer: Timer) -> Result<()> {
+pub fn take_long_break(config: &Config, rest: impl Into<Break>) -> Result<()> {
     let status = Status::load(&config.state_file_path)?;
+    let next_status = status.start_long_break(rest)?;
 
-    match status {
-        Status::Active(_) => Err(anyhow!("Finish your current timer before taking a break")),
-        Status::ShortBreak(_) => Err(anyhow!("You are already taking a break")),
-        Status::LongBreak(_) => Err(anyhow!("You are already taking a break")),
-        Status::Inactive => {
-            let new_status = Status::ShortBreak(timer.clone());
-            new_status.save(&config.state_file_path)?;
+    save_status(config, &next_status)?;
 
-            hooks::run_break_hook(&config.hooks_directory)?;
+    Hook::Break.run(config)?;
+    Hook::LongBreakStart.run(config)?;
 
-            Ok(())
+    Ok(())
+}
+
+/// When a finished timer is recorded as having stopped
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Default)]
+pub enum FinishTime {
+    /// The current time, for when the user stops a timer themselves
+    #[default]
+    Now,
+    /// The time the timer ran out, or the current time if it hasn't run out yet
+    ///
+    /// Use this when catching up on a timer that ended a while ago, so the
+    /// time in between isn't counted as part of it.
+    TimerEnd,
+}
+
+/// What happened when a timer was finished
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum FinishOutcome {
+    /// A Pomodoro was finished and added to the history as this entry
+    Archived(HistoryEntry),
+    /// A break of this kind was over, and added to the history too
+    BreakEnded(Phase),
+    /// There was no timer to finish, so nothing was changed
+    NothingActive,
+}
+
+/// Finish and archive a Pomodoro or break timer at the current time
+///
+/// See [`finish_at`].
+pub fn finish(config: &Config, clock: &dyn Clock) -> Result<FinishOutcome> {
+    finish_at(config, clock, FinishTime::Now)
+}
+
+/// Finish and archive a Pomodoro or break timer
+///
+/// `at` decides the time the timer is archived as having stopped.
+/// The stop hooks are run by [`clear`], which this calls once the timer is
+/// archived, so they aren't run here.
+pub fn finish_at(config: &Config, clock: &dyn Clock, at: FinishTime) -> Result<FinishOutcome> {
+    let mut status = Status::load(&config.state_file_path)?;
+    // Time spent away from a Pomodoro paused by `pause_when_idle` doesn't count
+    if let Status::Active(pom) = &mut status {
+        pom.timer_mut().resume(clock.now());
+    }
+    let Some(phase) = status.phase() else {
+        return Ok(FinishOutcome::NothingActive);
+    };
+    let now = match (at, &status) {
+        (FinishTime::TimerEnd, Status::Active(pom)) => clock.now().min(pom.timer().ends_at()),
+        (FinishTime::TimerEnd, Status::ShortBreak(rest) | Status::LongBreak(rest)) => {
+            clock.now().min(rest.timer().ends_at())
         }
+        _ => clock.now(),
+    };
+    let (_next_status, finished) = status.finish(now)?;
+
+    let entry = match (&status, finished) {
+        (_, Some(pom)) => Some(HistoryEntry::archive(&pom, config.history_rounding)?),
+        (Status::ShortBreak(rest), None) => {
+            Some(HistoryEntry::archive_break(Phase::ShortBreak, rest, now))
+        }
+        (Status::LongBreak(rest), None) => {
+            Some(HistoryEntry::archive_break(Phase::LongBreak, rest, now))
+        }
+        _ => None,
+    };
+
+    if let Some(entry) = &entry {
+        append_history(config, entry)?;
     }
+
+    clear(config)?;
+
+    sound::play_for(config, phase);
+
+    Ok(match (phase, entry) {
+        (Phase::Pomodoro, Some(entry)) => FinishOutcome::Archived(entry),
+        (phase, _) => FinishOutcome::BreakEnded(phase),
+    })
 }
 
-/// Finish and archive a Pomodoro or break timer
-pub fn finish(config: &Config) -> Result<()> {
-    let status = Status::load(&config.state_file_path)?;
+/// Finish the current Pomodoro or break if its timer has run out and `auto_finish_on_access` is on
+///
+/// This is a safety net for when the scheduled timer check never ran, for
+/// example because the computer was asleep when the timer ran out.
+/// Returns `true` if a timer was finished.
+pub fn auto_finish(config: &Config, clock: &dyn Clock) -> Result<bool> {
+    if !config.auto_finish_on_access {
+        return Ok(false);
+    }
+
+    let done = match Status::load(&config.state_file_path)? {
+        Status::Inactive => false,
+        Status::Active(pom) => pom.timer().done(clock.now()),
+        Status::ShortBreak(rest) | Status::LongBreak(rest) => rest.timer().done(clock.now()),
+    };
+
+    if done {
+        info!("Timer already ran out, finishing it now");
+        finish_at(config, clock, FinishTime::TimerEnd)?;
+    }
+
+    Ok(done)
+}
+
+/// Pause the current timer for the time the computer was suspended, if `pause_on_suspend` is on
+///
+/// This should be called from the periodic ticks, which run every `interval`
+/// of time the computer is awake. The time of each tick is kept in the state
+/// file, so if one runs more than `suspend_threshold` later than that, the
+/// computer is assumed to have been suspended since the tick was due.
+/// The timer is paused from then until now, moving its end later.
+/// Timers that are paused, or that ran out before the suspend, are left alone.
+/// Returns the time the timer was paused for, if any.
+pub fn pause_after_suspend(
+    config: &Config,
+    clock: &dyn Clock,
+    interval: TimeDelta,
+) -> Result<Option<TimeDelta>> {
+    if !config.pause_on_suspend {
+        return Ok(None);
+    }
+
+    let now = clock.now();
+    let mut status = Status::load(&config.state_file_path)?;
+    let Some(timer) = status.timer_mut() else {
+        return Ok(None);
+    };
+
+    let suspended_at = timer.last_checked_at().map(|last| last + interval);
+    timer.set_last_checked_at(now);
+
+    let suspended = match suspended_at {
+        Some(suspended_at)
+            if now - suspended_at > config.suspend_threshold
+                && timer.paused_at().is_none()
+                && !timer.done(suspended_at) =>
+        {
+            info!(
+                "Tick ran {} seconds late, pausing the timer for the time the computer was suspended",
+                (now - suspended_at).num_seconds()
+            );
+            timer.pause(suspended_at);
+            timer.resume(now);
+            Some(now - suspended_at)
+        }
+        _ => None,
+    };
+
+    save_status(config, &status)?;
+
+    Ok(suspended)
+}
+
+/// What an idle check did to the current Pomodoro
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum IdlePause {
+    /// The Pomodoro is paused, because the user is away
+    Paused,
+    /// The user is back, and the Pomodoro's end was moved later by this much
+    Resumed(TimeDelta),
+}
+
+/// Pause the current Pomodoro while the user is away, if `idle_timeout` is set
+///
+/// If the computer has been idle for at least `idle_timeout`, the Pomodoro is
+/// paused as of the last input. Once there has been input again, it is
+/// resumed as of that input. Breaks are never paused, and neither is a
+/// Pomodoro the user left after it had already run out.
+/// Returns `None` if nothing changed and the Pomodoro isn't paused.
+pub fn pause_when_idle(
+    config: &Config,
+    clock: &dyn Clock,
+    idle: &dyn idle::IdleSource,
+) -> Result<Option<IdlePause>> {
+    let Some(timeout) = config.idle_timeout else {
+        return Ok(None);
+    };
+
+    let mut status = Status::load(&config.state_file_path)?;
+    let Status::Active(pom) = &mut status else {
+        return Ok(None);
+    };
+    let Some(idle_time) = idle.idle_time()? else {
+        return Ok(None);
+    };
 
-    match status {
-        Status::Inactive => bail!("No active Pomodoro. Start one with \"tomate start\""),
-        Status::ShortBreak(_timer) => clear(config)?,
-        Status::LongBreak(_timer) => clear(config)?,
-        Status::Active(mut pom) => {
-            pom.finish(Local::now());
+    let now = clock.now();
+    let last_input = now - idle_time;
+    let timer = pom.timer_mut();
 
-            History::append(&pom, &config.history_file_path)?;
+    match timer.paused_at() {
+        Some(_) if idle_time >= timeout => Ok(Some(IdlePause::Paused)),
+        Some(paused_at) => {
+            let paused = (last_input - paused_at).max(TimeDelta::zero());
+            info!(
+                "Resuming the Pomodoro after {} seconds away",
+                paused.num_seconds()
+            );
+            timer.resume(last_input);
+            save_status(config, &status)?;
 
-            clear(config)?;
+            Ok(Some(IdlePause::Resumed(paused)))
         }
+        None if idle_time >= timeout && last_input < timer.ends_at() => {
+            info!(
+                "No input for {} seconds, pausing the Pomodoro",
+                idle_time.num_seconds()
+            );
+            timer.pause(last_input.max(timer.starts_at()));
+            save_status(config, &status)?;
+
+            Ok(Some(IdlePause::Paused))
+        }
+        None => Ok(None),
     }
+}
 
-    Ok(())
+/// Run the [`Hook::Reminder`] hook if the current timer has run out but isn't finished
+///
+/// Reminders are sent at most once every `reminder_interval`. The time of the
+/// last one is kept in the state file, so checks that fire more often than
+/// that don't send more. Does nothing if `reminder_interval` is not set.
+/// Returns `true` if a reminder was sent.
+pub fn remind(config: &Config, clock: &dyn Clock) -> Result<bool> {
+    let Some(interval) = config.reminder_interval else {
+        return Ok(false);
+    };
+
+    let now = clock.now();
+    let mut status = Status::load(&config.state_file_path)?;
+    let Some(timer) = status.timer_mut() else {
+        return Ok(false);
+    };
+
+    if !timer.done_within(now, config.timer_check_grace) || !timer.reminder_due(now, interval) {
+        return Ok(false);
+    }
+
+    timer.set_last_notified_at(now);
+    save_status(config, &status)?;
+    Hook::Reminder.run(config)?;
+
+    Ok(true)
+}
+
+/// Run the [`Hook::Tick`] hook if the Pomodoro with this id is still running
+///
+/// The hook gets the time left in the `TOMATE_REMAINING_SECONDS` environment
+/// variable. Returns `false` if that Pomodoro is no longer running, so the
+/// caller can stop ticking for it.
+pub fn tick(config: &Config, clock: &dyn Clock, id: Uuid) -> Result<bool> {
+    let now = clock.now();
+
+    let remaining = match Status::load(&config.state_file_path)? {
+        Status::Active(pom) if pom.id() == Some(id) && !pom.timer().done(now) => {
+            pom.timer().remaining(now)
+        }
+        _ => return Ok(false),
+    };
+
+    Hook::Tick.run_with_env(
+        config,
+        &[(
+            "TOMATE_REMAINING_SECONDS",
+            remaining.num_seconds().to_string(),
+        )],
+    )?;
+
+    Ok(true)
 }
 
 /// Clear the current state by deleting the state file
+///
+/// Runs the [`Hook::Stop`] hook, and the end hook for the kind of timer
+/// that was running, if the state file can still be read.
+/// This is the only place those hooks are run, so every way of stopping a
+/// timer runs each of them exactly once.
 pub fn clear(config: &Config) -> Result<()> {
     let state_file_path = &config.state_file_path;
 
     if state_file_path.exists() {
-        info!(
-            "Deleting current Pomodoro state file {}",
-            &config.state_file_path.display().to_string().cyan()
-        );
-        std::fs::remove_file(&config.state_file_path)?;
+        let phase = Status::load(state_file_path)
+            .ok()
+            .and_then(|status| status.phase());
+
+        remove_file(config, state_file_path)?;
 
-        hooks::run_stop_hook(&config.hooks_directory)?;
+        Hook::Stop.run(config)?;
+        if let Some(phase) = phase {
+            Hook::phase_end(phase).run(config)?;
+        }
     }
 
     Ok(())
@@ -223,72 +920,507 @@ pub fn clear(config: &Config) -> Result<()> {
 /// Delete the state and history files
 pub fn purge(config: &Config) -> Result<()> {
     if config.state_file_path.exists() {
-        info!(
-            "Removing current Pomodoro file at {}",
-            config.state_file_path.display().to_string().cyan()
-        );
-        std::fs::remove_file(&config.state_file_path)?;
+        remove_file(config, &config.state_file_path)?;
     }
 
-    if config.history_file_path.exists() {
-        info!(
-            "Removing history file at {}",
-            config.history_file_path.display().to_string().cyan()
-        );
-        std::fs::remove_file(&config.history_file_path)?;
+    backup_history(config)?;
+    for path in History::rotated_files(&config.history_file_path, config.history_rotation)? {
+        remove_file(config, &path)?;
     }
 
     Ok(())
 }
 
-#[cfg(test)]
-mod test {
-    use chrono::{prelude::*, TimeDelta};
-
-    use crate::{Pomodoro, Status};
+fn check_pomodoro_duration(config: &Config, pomodoro: &Pomodoro) -> Result<()> {
+    let duration = pomodoro.timer().duration();
 
-    #[test]
-    fn status_to_toml() {
-        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
-        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+    if let Some(minimum) = config.min_pomodoro_duration {
+        if duration < minimum {
+            return Err(TomateError::TooShort { minimum });
+        }
+    }
 
-        let mut pom = Pomodoro::new(dt, dur);
-        pom.set_description("test converting poms to toml");
-        pom.set_tags(vec!["test".to_string(), "toml".to_string()]);
+    if let Some(maximum) = config.max_pomodoro_duration {
+        if duration > maximum {
+            return Err(TomateError::TooLong { maximum });
+        }
+    }
 
-        let status = Status::Active(pom);
+    Ok(())
+}
 
-        let toml = toml::to_string(&status).unwrap();
-        let lines: Vec<&str> = toml.lines().collect();
+fn normalize_pomodoro_tags(config: &Config, mut pomodoro: Pomodoro) -> Result<Pomodoro> {
+    if let Some(tags) = pomodoro.tags() {
+        let tags = normalize_tags(tags.clone(), config.lowercase_tags)?;
+        pomodoro.set_tags(tags);
+    }
 
-        assert_eq!(lines[0], "[Active]");
+    Ok(pomodoro)
+}
 
-        assert_eq!(lines[1], "started_at = 1711562400");
-        assert_eq!(lines[2], "duration = 1500");
-        assert_eq!(lines[3], r#"description = "test converting poms to toml""#);
-        assert_eq!(lines[4], r#"tags = ["test", "toml"]"#);
+fn append_history(config: &Config, entry: &HistoryEntry) -> Result<()> {
+    if config.dry_run {
+        info!(
+            "Dry run: would archive {} to {}",
+            entry.phase(),
+            config.history_file_path.display().to_string().cyan()
+        );
+        return Ok(());
     }
 
-    #[test]
-    fn toml_to_pom() {
-        let pom: Pomodoro = toml::from_str(
-            r#"
-started_at = 1712346817
-duration = 1500
-description = "Do something cool"
-tags = ["work", "fun"]
-            "#,
-        )
-        .expect("Could not parse pomodoro from string");
+    History::append_rotated(entry, &config.history_file_path, config.history_rotation)?;
 
-        let dt: DateTime<Local> = "2024-04-05T13:53:37-06:00".parse().unwrap();
-        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+    Ok(())
+}
 
-        assert_eq!(pom.timer().starts_at(), dt);
-        assert_eq!(pom.timer().duration(), dur);
-        assert_eq!(pom.description(), Some("Do something cool"));
-        let tags = vec!["work".to_string(), "fun".to_string()];
-        assert_eq!(pom.tags().unwrap(), &tags);
+/// Load the history, from every file it is split across if rotation is on
+pub fn load_history(config: &Config) -> Result<History> {
+    Ok(History::load_rotated(
+        &config.history_file_path,
+        config.history_rotation,
+    )?)
+}
+
+fn save_status(config: &Config, status: &Status) -> Result<()> {
+    if config.dry_run {
+        info!(
+            "Dry run: would save state to {}",
+            config.state_file_path.display().to_string().cyan()
+        );
+        return Ok(());
+    }
+
+    status.save_as(&config.state_file_path, config.state_format)
+}
+
+/// Append entries imported from another file to the history file
+///
+/// If `skip_duplicates` is true, entries with the same start time and
+/// duration as one already in the history are left out.
+/// Returns the number of entries that were appended.
+pub fn import_history(config: &Config, imported: &History, skip_duplicates: bool) -> Result<usize> {
+    let mut history = load_history(config)?;
+    let mut count = 0;
+
+    for entry in imported.entries() {
+        if skip_duplicates && history.contains_duplicate(entry) {
+            info!(
+                "Skipping duplicate {} started at {}",
+                entry.phase(),
+                entry.started_at().to_rfc3339()
+            );
+            continue;
+        }
+
+        if config.dry_run {
+            info!(
+                "Dry run: would archive {} started at {} to {}",
+                entry.phase(),
+                entry.started_at().to_rfc3339(),
+                config.history_file_path.display().to_string().cyan()
+            );
+        } else {
+            History::append_rotated(entry, &config.history_file_path, config.history_rotation)?;
+        }
+
+        history.push(entry.clone());
+        count += 1;
+    }
+
+    Ok(count)
+}
+
+/// Remove history entries that started before `before`
+///
+/// The history file is backed up first, then rewritten atomically.
+/// Returns the number of entries that were removed, which is also reported
+/// without changing anything in a dry run.
+pub fn prune_history(config: &Config, before: DateTime<Local>) -> Result<usize> {
+    let mut history = load_history(config)?;
+    let count = history.prune(before);
+
+    if count == 0 {
+        return Ok(0);
+    }
+
+    backup_history(config)?;
+
+    if config.dry_run {
+        info!(
+            "Dry run: would save pruned history to {}",
+            config.history_file_path.display().to_string().cyan()
+        );
+    } else {
+        history.save_rotated(&config.history_file_path, config.history_rotation)?;
+    }
+
+    Ok(count)
+}
+
+/// Create the config file, the hooks directory, and example hooks, if they don't exist yet
+///
+/// An example for each hook is written next to where the hook would go, with
+/// an `.example` extension and without execute permissions, so it doesn't run
+/// until the user renames it. Nothing that already exists is overwritten.
+/// Returns the paths that were created.
+pub fn init(config_path: &Path) -> Result<Vec<PathBuf>> {
+    let mut created = Vec::new();
+
+    let config_existed = config_path
+        .try_exists()
+        .map_err(TomateError::io(config_path))?;
+    let config = Config::init(config_path)?;
+    if !config_existed {
+        created.push(config_path.to_path_buf());
+    }
+
+    if !config.hooks_directory.exists() {
+        info!(
+            "Creating hooks directory {}",
+            config.hooks_directory.display().to_string().cyan()
+        );
+        std::fs::create_dir_all(&config.hooks_directory)
+            .map_err(TomateError::io(&config.hooks_directory))?;
+        created.push(config.hooks_directory.clone());
+    }
+
+    for hook in Hook::all() {
+        let path = config
+            .hooks_directory
+            .join(format!("{}.example", hook.file_name()));
+
+        if path.exists() {
+            continue;
+        }
+
+        info!(
+            "Writing example {} hook to {}",
+            hook.file_name(),
+            path.display().to_string().cyan()
+        );
+        std::fs::write(&path, hook.example_script()).map_err(TomateError::io(&path))?;
+        created.push(path);
+    }
+
+    Ok(created)
+}
+
+/// Remove duplicate entries from the history
+///
+/// Entries that can't be read are dropped too, so the backup made before the
+/// file is rewritten is the only copy left of them.
+/// Returns the number of duplicates removed and the number of unreadable
+/// entries dropped, which are also reported without changing anything in a dry run.
+pub fn dedupe_history(config: &Config) -> Result<(usize, usize)> {
+    let (mut history, unreadable) =
+        History::load_lenient_rotated(&config.history_file_path, config.history_rotation)?;
+    let duplicates = history.dedupe();
+
+    if duplicates == 0 && unreadable == 0 {
+        return Ok((0, 0));
+    }
+
+    backup_history(config)?;
+
+    if config.dry_run {
+        info!(
+            "Dry run: would save deduplicated history to {}",
+            config.history_file_path.display().to_string().cyan()
+        );
+    } else {
+        history.save_rotated(&config.history_file_path, config.history_rotation)?;
+    }
+
+    Ok((duplicates, unreadable))
+}
+
+/// Back up the history files before they are deleted or rewritten
+///
+/// Does nothing if `keep_backups` is off or there is no history file yet.
+pub fn backup_history(config: &Config) -> Result<()> {
+    if !config.keep_backups {
+        return Ok(());
+    }
+
+    for path in History::rotated_files(&config.history_file_path, config.history_rotation)? {
+        if config.dry_run {
+            info!(
+                "Dry run: would back up {}",
+                path.display().to_string().cyan()
+            );
+            continue;
+        }
+
+        History::backup(&path)?;
+    }
+
+    Ok(())
+}
+
+fn remove_file(config: &Config, path: &Path) -> Result<()> {
+    if config.dry_run {
+        info!(
+            "Dry run: would remove {}",
+            path.display().to_string().cyan()
+        );
+        return Ok(());
+    }
+
+    info!("Removing {}", path.display().to_string().cyan());
+    std::fs::remove_file(path).map_err(TomateError::io(path))
+}
+
+/// Replace the contents of a file without ever leaving it partially written
+pub(crate) fn write_atomic(path: &Path, contents: &[u8]) -> anyhow::Result<()> {
+    write_atomic_with(path, |writer| {
+        writer.write_all(contents)?;
+        Ok(())
+    })
+}
+
+/// Replace the contents of a file with whatever `write` writes, without ever leaving it partially written
+///
+/// The contents are written to a temporary file in the same directory, which
+/// then replaces the original file. If `write` fails, the original file is
+/// left untouched.
+pub fn write_atomic_with<F>(path: &Path, write: F) -> anyhow::Result<()>
+where
+    F: FnOnce(&mut dyn Write) -> anyhow::Result<()>,
+{
+    let dir = path
+        .parent()
+        .with_context(|| format!("{} does not have a parent directory", path.display()))?;
+    let file_name = path
+        .file_name()
+        .with_context(|| format!("{} does not name a file", path.display()))?;
+
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+
+    let tmp_path = dir.join(format!(".{}.tmp", file_name.to_string_lossy()));
+
+    let result = std::fs::File::create(&tmp_path)
+        .map_err(anyhow::Error::from)
+        .and_then(|file| {
+            let mut writer = std::io::BufWriter::new(file);
+            write(&mut writer)?;
+            writer.flush()?;
+            Ok(())
+        });
+    if let Err(e) = result {
+        let _ = std::fs::remove_file(&tmp_path);
+        return Err(e.context(format!("Failed to write {}", tmp_path.display())));
+    }
+
+    std::fs::rename(&tmp_path, path)
+        .with_context(|| format!("Failed to replace {}", path.display()))?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use chrono::{prelude::*, TimeDelta};
+
+    use crate::{
+        next_break, Break, BreakKind, Clock, Config, FinishOutcome, FinishTime, History,
+        HistoryEntry, IdlePause, Phase, Pomodoro, StateFormat, Status, Timer, TomateError,
+    };
+
+    struct MockClock {
+        now: Cell<DateTime<Local>>,
+    }
+
+    impl MockClock {
+        fn new(now: DateTime<Local>) -> Self {
+            Self {
+                now: Cell::new(now),
+            }
+        }
+
+        fn advance(&self, delta: TimeDelta) {
+            self.now.set(self.now.get() + delta);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now(&self) -> DateTime<Local> {
+            self.now.get()
+        }
+    }
+
+    fn test_config(dir: &std::path::Path) -> Config {
+        Config {
+            state_file_path: dir.join("current.toml"),
+            history_file_path: dir.join("history.toml"),
+            hooks_directory: dir.join("hooks"),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn long_break_after_every_fourth_pomodoro() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let config = Config::default();
+        let mut history = History::default();
+
+        let mut breaks = Vec::new();
+        for i in 0..4 {
+            let mut pom = Pomodoro::new(dt + TimeDelta::minutes(30 * i), TimeDelta::minutes(25));
+            pom.finish(pom.timer().ends_at());
+            history.push(HistoryEntry::archive(&pom, None).unwrap());
+            breaks.push(next_break(&history, &config, pom.timer().ends_at()));
+        }
+
+        assert_eq!(breaks[0], BreakKind::Short);
+        assert_eq!(breaks[2], BreakKind::Short);
+        assert_eq!(breaks[3], BreakKind::Long);
+
+        let rest = Break::new(dt + TimeDelta::hours(2), TimeDelta::minutes(20));
+        history.push(HistoryEntry::archive_break(
+            Phase::LongBreak,
+            &rest,
+            rest.timer().ends_at(),
+        ));
+        assert_eq!(
+            next_break(&history, &config, rest.timer().ends_at()),
+            BreakKind::Short
+        );
+
+        let config = Config {
+            poms_before_long_break: 0,
+            ..Config::default()
+        };
+        assert_eq!(
+            next_break(&history, &config, rest.timer().ends_at()),
+            BreakKind::Short
+        );
+    }
+
+    #[test]
+    fn status_queries_for_each_variant() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let now = dt + TimeDelta::minutes(10);
+
+        let status = Status::Inactive;
+        assert!(!status.is_active());
+        assert!(!status.is_break());
+        assert_eq!(status.remaining(now), None);
+        assert_eq!(status.description(), None);
+
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.set_description("write tests");
+        let status = Status::Active(pom);
+        assert!(status.is_active());
+        assert!(!status.is_break());
+        assert_eq!(status.remaining(now), Some(TimeDelta::minutes(15)));
+        assert_eq!(status.description(), Some("write tests"));
+
+        let status = Status::ShortBreak(Break::new(dt, TimeDelta::minutes(5)));
+        assert!(!status.is_active());
+        assert!(status.is_break());
+        assert_eq!(status.remaining(now), Some(TimeDelta::zero()));
+        assert_eq!(status.description(), None);
+
+        let status = Status::LongBreak(Break::new(dt, TimeDelta::minutes(20)));
+        assert!(!status.is_active());
+        assert!(status.is_break());
+        assert_eq!(status.remaining(now), Some(TimeDelta::minutes(10)));
+        assert_eq!(status.description(), None);
+    }
+
+    #[test]
+    fn status_to_toml() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let mut pom = Pomodoro::new(dt, dur);
+        pom.set_description("test converting poms to toml");
+        pom.set_tags(vec!["test".to_string(), "toml".to_string()]);
+
+        let status = Status::Active(pom);
+
+        let toml = toml::to_string(&status).unwrap();
+        let lines: Vec<&str> = toml.lines().collect();
+
+        assert_eq!(lines[0], "[Active]");
+
+        assert_eq!(lines[1], "started_at = 1711562400");
+        assert_eq!(lines[2], "duration = 1500");
+        assert_eq!(lines[3], r#"description = "test converting poms to toml""#);
+        assert_eq!(lines[4], r#"tags = ["test", "toml"]"#);
+    }
+
+    #[test]
+    fn state_round_trips_in_both_formats() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.set_description("script against the state");
+        pom.set_tags(vec!["json".to_string()]);
+        let status = Status::Active(pom);
+
+        for format in [StateFormat::Toml, StateFormat::Json] {
+            let mut contents = Vec::new();
+            status.to_writer_as(&mut contents, format).unwrap();
+
+            assert_eq!(
+                contents.starts_with(b"{"),
+                format == StateFormat::Json,
+                "{:?} state starts with the wrong character",
+                format
+            );
+            assert_eq!(Status::from_reader(contents.as_slice()).unwrap(), status);
+        }
+
+        let mut toml = Vec::new();
+        status.to_writer(&mut toml).unwrap();
+        let toml = String::from_utf8(toml).unwrap();
+        assert_eq!(toml::from_str::<Status>(&toml).unwrap(), status);
+    }
+
+    #[test]
+    fn break_description_is_next_to_its_timer() {
+        let state =
+            "[ShortBreak]\nstarted_at = 1711562400\nduration = 300\ndescription = \"lunch\"\n";
+
+        let status = Status::from_reader(state.as_bytes()).unwrap();
+        let Status::ShortBreak(rest) = &status else {
+            panic!("expected a short break, got {:?}", status);
+        };
+        assert_eq!(rest.description(), Some("lunch"));
+        assert_eq!(rest.timer().duration(), TimeDelta::minutes(5));
+
+        let mut toml = Vec::new();
+        status.to_writer(&mut toml).unwrap();
+        assert_eq!(String::from_utf8(toml).unwrap(), state);
+
+        let mut json = Vec::new();
+        status.to_writer_as(&mut json, StateFormat::Json).unwrap();
+        assert_eq!(Status::from_reader(json.as_slice()).unwrap(), status);
+    }
+
+    #[test]
+    fn toml_to_pom() {
+        let pom: Pomodoro = toml::from_str(
+            r#"
+started_at = 1712346817
+duration = 1500
+description = "Do something cool"
+tags = ["work", "fun"]
+            "#,
+        )
+        .expect("Could not parse pomodoro from string");
+
+        let dt: DateTime<Local> = "2024-04-05T13:53:37-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        assert_eq!(pom.timer().starts_at(), dt);
+        assert_eq!(pom.timer().duration(), dur);
+        assert_eq!(pom.description(), Some("Do something cool"));
+        let tags = vec!["work".to_string(), "fun".to_string()];
+        assert_eq!(pom.tags().unwrap(), &tags);
     }
 
     #[test]
@@ -316,4 +1448,1179 @@ tags = ["work", "fun"]
 
         assert_eq!(pom.timer().remaining(dt_later), expected_remaining);
     }
+
+    #[test]
+    fn humanize_ago_boundaries() {
+        let cases = [
+            (TimeDelta::seconds(-30), "just now"),
+            (TimeDelta::seconds(59), "just now"),
+            (TimeDelta::seconds(60), "1m ago"),
+            (TimeDelta::minutes(59), "59m ago"),
+            (TimeDelta::minutes(60), "1h ago"),
+            (TimeDelta::hours(23), "23h ago"),
+            (TimeDelta::hours(24), "yesterday"),
+            (TimeDelta::hours(47), "yesterday"),
+            (TimeDelta::days(2), "2d ago"),
+            (TimeDelta::days(13), "13d ago"),
+            (TimeDelta::days(14), "2w ago"),
+            (TimeDelta::days(100), "14w ago"),
+        ];
+
+        for (delta, expected) in cases {
+            assert_eq!(crate::humanize_ago(delta), expected, "for {:?}", delta);
+        }
+    }
+
+    #[test]
+    fn prune_history_keeps_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        for started_at in ["2023-01-15T09:00:00-06:00", "2024-03-28T09:00:00-06:00"] {
+            let dt: DateTime<Local> = started_at.parse().unwrap();
+            let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+            pom.finish(dt + TimeDelta::minutes(25));
+            History::append(&pom, &config.history_file_path).unwrap();
+        }
+        let before = "2024-01-01T00:00:00-06:00".parse().unwrap();
+
+        let dry_run = Config {
+            dry_run: true,
+            ..test_config(dir.path())
+        };
+        assert_eq!(crate::prune_history(&dry_run, before).unwrap(), 1);
+        assert_eq!(
+            History::load(&config.history_file_path)
+                .unwrap()
+                .entries()
+                .len(),
+            2
+        );
+
+        assert_eq!(crate::prune_history(&config, before).unwrap(), 1);
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries().len(), 1);
+
+        let backups = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().path() != config.history_file_path)
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("history")
+            })
+            .count();
+        assert_eq!(backups, 1);
+    }
+
+    #[test]
+    fn dedupe_history_merges_duplicates() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        // An entry from before phases and ids were recorded, the same entry
+        // appended again with a description, one with a bad duration, and
+        // another Pomodoro
+        std::fs::write(
+            &config.history_file_path,
+            r#"[[pomodoros]]
+started_at = 1711558800
+duration = 1500
+
+[[pomodoros]]
+phase = "pomodoro"
+started_at = 1711558800
+duration = 1500
+description = "Write the report"
+
+[[pomodoros]]
+started_at = 1711560600
+duration = "a while"
+
+[[pomodoros]]
+started_at = 1711562400
+duration = 1500
+"#,
+        )
+        .unwrap();
+
+        let dry_run = Config {
+            dry_run: true,
+            ..test_config(dir.path())
+        };
+        assert_eq!(crate::dedupe_history(&dry_run).unwrap(), (1, 1));
+        assert!(History::load(&config.history_file_path).is_err());
+
+        assert_eq!(crate::dedupe_history(&config).unwrap(), (1, 1));
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries().len(), 2);
+        assert_eq!(history.entries()[0].description(), Some("Write the report"));
+
+        assert_eq!(crate::dedupe_history(&config).unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn init_leaves_existing_files_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let config = test_config(dir.path());
+        config.save(&config_path).unwrap();
+        std::fs::create_dir(&config.hooks_directory).unwrap();
+        let start_example = config.hooks_directory.join("start.example");
+        std::fs::write(&start_example, "mine").unwrap();
+
+        let created = crate::init(&config_path).unwrap();
+        assert!(!created.contains(&config_path));
+        assert!(!created.contains(&config.hooks_directory));
+        assert!(!created.contains(&start_example));
+        assert_eq!(created.len(), crate::Hook::all().count() - 1);
+        assert_eq!(std::fs::read_to_string(&start_example).unwrap(), "mine");
+
+        let stop_example = config.hooks_directory.join("stop.example");
+        assert!(created.contains(&stop_example));
+        assert!(!crate::doctor::is_executable(&stop_example));
+
+        // An example isn't run in place of the hook
+        crate::Hook::Stop.run(&config).unwrap();
+
+        assert!(crate::init(&config_path).unwrap().is_empty());
+    }
+
+    #[test]
+    fn purge_keeps_history_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.set_description("before the purge");
+        pom.finish(dt + TimeDelta::minutes(25));
+        History::append(&pom, &config.history_file_path).unwrap();
+
+        crate::purge(&config).unwrap();
+
+        assert!(!config.history_file_path.exists());
+
+        let backups: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+
+        let backup = History::load(&backups[0]).unwrap();
+        assert_eq!(backup.entries().len(), 1);
+        assert_eq!(backup.entries()[0].description(), Some("before the purge"));
+    }
+
+    #[test]
+    fn import_csv_merges_into_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.finish(dt + TimeDelta::minutes(25));
+        History::append(&pom, &config.history_file_path).unwrap();
+
+        let csv = "\
+started_at,duration,phase,description,tags
+2024-03-27T12:00:00-06:00,1500,,,
+2024-03-27T12:25:00-06:00,300,short_break,,
+2024-03-27T13:00:00-06:00,1500,pomodoro,Write docs,\"docs,work\"
+";
+        let imported = History::import_csv(csv.as_bytes()).unwrap();
+        assert_eq!(imported.entries().len(), 3);
+
+        let count = crate::import_history(&config, &imported, true).unwrap();
+        assert_eq!(count, 2);
+
+        let history = History::load(&config.history_file_path).unwrap();
+        let entries = history.entries();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[1].phase(), Phase::ShortBreak);
+        assert_eq!(entries[1].duration(), TimeDelta::minutes(5));
+        assert_eq!(entries[2].description(), Some("Write docs"));
+        assert_eq!(
+            entries[2].tags(),
+            Some(&vec!["docs".to_string(), "work".to_string()])
+        );
+    }
+
+    #[test]
+    fn progress() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let timer = Timer::new(dt, dur);
+
+        assert_eq!(timer.progress_ratio(dt), 0.0);
+        assert_eq!(timer.percent(dt), 0);
+
+        assert_eq!(timer.progress_ratio(dt + dur / 2), 0.5);
+        assert_eq!(timer.percent(dt + dur / 2), 50);
+
+        assert_eq!(timer.progress_ratio(dt + dur), 1.0);
+        assert_eq!(timer.percent(dt + dur), 100);
+
+        assert_eq!(timer.progress_ratio(dt + dur * 2), 1.0);
+        assert_eq!(timer.percent(dt + dur * 2), 100);
+
+        let empty = Timer::new(dt, TimeDelta::zero());
+        assert_eq!(empty.progress_ratio(dt), 1.0);
+        assert_eq!(empty.percent(dt), 100);
+    }
+
+    #[test]
+    fn done_within_grace() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+        let grace = TimeDelta::new(5, 0).unwrap();
+
+        let timer = Timer::new(dt, dur);
+
+        let early = dt + dur - TimeDelta::new(3, 0).unwrap();
+        let too_early = dt + dur - TimeDelta::new(6, 0).unwrap();
+
+        assert!(!timer.done(early));
+        assert!(timer.done_within(early, grace));
+        assert!(!timer.done_within(too_early, grace));
+        assert!(timer.done_within(dt + dur + grace, grace));
+    }
+
+    #[test]
+    fn elapsed_across_daylight_saving_change() {
+        use chrono_tz::America::New_York;
+
+        // Clocks in New York jumped from 02:00 to 03:00 on 2024-03-10
+        let started_at = New_York
+            .with_ymd_and_hms(2024, 3, 10, 1, 50, 0)
+            .unwrap()
+            .with_timezone(&Local);
+        let ended_at = New_York
+            .with_ymd_and_hms(2024, 3, 10, 3, 15, 0)
+            .unwrap()
+            .with_timezone(&Local);
+        let timer = Timer::new(started_at, TimeDelta::minutes(25));
+
+        assert_eq!(timer.ends_at(), ended_at);
+        assert_eq!(timer.elapsed(ended_at), TimeDelta::minutes(25));
+        assert_eq!(
+            timer.remaining(ended_at - TimeDelta::minutes(5)),
+            TimeDelta::minutes(5)
+        );
+        assert!(!timer.done(ended_at - TimeDelta::minutes(1)));
+
+        let toml = toml::to_string(&timer).unwrap();
+        assert_eq!(toml::from_str::<Timer>(&toml).unwrap(), timer);
+    }
+
+    #[test]
+    fn overtime_before_end() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dt_later: DateTime<Local> = "2024-03-27T12:20:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        assert_eq!(pom.timer().overtime(dt_later), TimeDelta::zero());
+    }
+
+    #[test]
+    fn overtime_after_end() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dt_later: DateTime<Local> = "2024-03-27T12:28:12-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let expected_overtime = TimeDelta::new(3 * 60 + 12, 0).unwrap();
+
+        assert_eq!(pom.timer().overtime(dt_later), expected_overtime);
+    }
+
+    #[test]
+    fn start_pomodoro_from_inactive() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let status = Status::Inactive
+            .start_pomodoro(dt, dur, Some("write tests"), Some(vec!["work".to_string()]))
+            .unwrap();
+
+        let Status::Active(pom) = status else {
+            panic!("Expected an active Pomodoro, got {:?}", status);
+        };
+
+        assert_eq!(pom.timer().starts_at(), dt);
+        assert_eq!(pom.timer().duration(), dur);
+        assert_eq!(pom.description(), Some("write tests"));
+        assert_eq!(pom.tags(), Some(&vec!["work".to_string()]));
+    }
+
+    #[test]
+    fn cannot_start_pomodoro_twice() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let status = Status::Active(Pomodoro::new(dt, dur));
+
+        assert!(status.start_pomodoro(dt, dur, None, None).is_err());
+    }
+
+    #[test]
+    fn cannot_start_pomodoro_during_break() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(5 * 60, 0).unwrap();
+
+        let status = Status::ShortBreak(Break::new(dt, dur));
+
+        assert!(status.start_pomodoro(dt, dur, None, None).is_err());
+    }
+
+    #[test]
+    fn restart_keeps_description_and_tags() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dt_later: DateTime<Local> = "2024-03-27T12:02:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let status = Status::Inactive
+            .start_pomodoro(dt, dur, Some("write tests"), Some(vec!["work".to_string()]))
+            .unwrap();
+
+        let restarted = status.restart(dt_later, None).unwrap();
+
+        let Status::Active(pom) = restarted else {
+            panic!("Expected an active Pomodoro, got {:?}", restarted);
+        };
+
+        assert_eq!(pom.timer().starts_at(), dt_later);
+        assert_eq!(pom.timer().duration(), dur);
+        assert_eq!(pom.description(), Some("write tests"));
+        assert_eq!(pom.tags(), Some(&vec!["work".to_string()]));
+    }
+
+    #[test]
+    fn restart_with_new_duration() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+        let new_dur = TimeDelta::new(50 * 60, 0).unwrap();
+
+        let status = Status::Active(Pomodoro::new(dt, dur));
+
+        let Status::Active(pom) = status.restart(dt, Some(new_dur)).unwrap() else {
+            panic!("Expected an active Pomodoro");
+        };
+
+        assert_eq!(pom.timer().duration(), new_dur);
+    }
+
+    #[test]
+    fn describe_keeps_timer_running() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let status = Status::Inactive
+            .start_pomodoro(dt, dur, Some("something"), None)
+            .unwrap();
+
+        let Status::Active(pom) = status
+            .describe(None, Some(vec!["work".to_string()]))
+            .unwrap()
+        else {
+            panic!("Expected an active Pomodoro");
+        };
+
+        assert_eq!(pom.timer().starts_at(), dt);
+        assert_eq!(pom.description(), Some("something"));
+        assert_eq!(pom.tags(), Some(&vec!["work".to_string()]));
+
+        assert!(Status::Inactive.describe(Some("nothing"), None).is_err());
+        assert!(Status::ShortBreak(Break::new(dt, dur))
+            .describe(Some("resting"), None)
+            .is_err());
+    }
+
+    #[test]
+    fn status_errors_can_be_matched() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::minutes(25);
+        let active = Status::Active(Pomodoro::new(dt, dur));
+        let on_break = Status::ShortBreak(Break::new(dt, TimeDelta::minutes(5)));
+
+        assert!(matches!(
+            active.start(Pomodoro::new(dt, dur)),
+            Err(TomateError::AlreadyActive)
+        ));
+        assert!(matches!(
+            active.start_long_break(Timer::new(dt, dur)),
+            Err(TomateError::AlreadyActive)
+        ));
+        assert!(matches!(
+            on_break.start(Pomodoro::new(dt, dur)),
+            Err(TomateError::OnBreak)
+        ));
+        assert!(matches!(
+            Status::Inactive.finish(dt),
+            Err(TomateError::NoActiveTimer)
+        ));
+        assert!(matches!(
+            active.split(dt + TimeDelta::hours(1)),
+            Err(TomateError::TimerRanOut)
+        ));
+        assert!(matches!(
+            Status::from_reader("not a state file".as_bytes()),
+            Err(TomateError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn cannot_restart_break_or_inactive() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(5 * 60, 0).unwrap();
+
+        assert!(Status::Inactive.restart(dt, None).is_err());
+        assert!(Status::ShortBreak(Break::new(dt, dur))
+            .restart(dt, None)
+            .is_err());
+    }
+
+    #[test]
+    fn start_long_break_from_inactive() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(20 * 60, 0).unwrap();
+        let timer = Timer::new(dt, dur);
+
+        let status = Status::Inactive.start_long_break(timer.clone()).unwrap();
+
+        assert_eq!(status, Status::LongBreak(timer.into()));
+    }
+
+    #[test]
+    fn finish_pomodoro() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dt_later: DateTime<Local> = "2024-03-27T12:25:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let status = Status::Active(Pomodoro::new(dt, dur));
+
+        let (next_status, finished) = status.finish(dt_later).unwrap();
+
+        assert_eq!(next_status, Status::Inactive);
+        assert_eq!(finished.unwrap().duration(), Some(dur));
+    }
+
+    #[test]
+    fn cannot_finish_when_inactive() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+
+        assert!(Status::Inactive.finish(dt).is_err());
+    }
+
+    #[test]
+    fn dry_run_start_does_not_touch_state() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            dry_run: true,
+            ..test_config(dir.path())
+        };
+
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        crate::start(&config, Pomodoro::new(dt, dur)).unwrap();
+
+        assert!(!config.state_file_path.exists());
+    }
+
+    #[test]
+    fn start_wait_and_finish() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        crate::start(&config, Pomodoro::new(clock.now(), dur)).unwrap();
+
+        clock.advance(TimeDelta::new(10 * 60, 0).unwrap());
+        let Status::Active(pom) = Status::load(&config.state_file_path).unwrap() else {
+            panic!("Expected an active Pomodoro");
+        };
+        assert!(!pom.timer().done(clock.now()));
+
+        clock.advance(TimeDelta::new(15 * 60 + 1, 0).unwrap());
+        assert!(pom.timer().done(clock.now()));
+
+        crate::finish(&config, &clock).unwrap();
+
+        assert_eq!(
+            Status::load(&config.state_file_path).unwrap(),
+            Status::Inactive
+        );
+
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(
+            history.entries()[0].duration(),
+            TimeDelta::new(25 * 60 + 1, 0).unwrap()
+        );
+        assert_eq!(history.entries()[0].phase(), Phase::Pomodoro);
+    }
+
+    #[test]
+    fn split_archives_elapsed_time() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+        let mut pom = Pomodoro::new(clock.now(), TimeDelta::minutes(25));
+        pom.set_description("long task");
+        pom.set_tags(vec!["work".to_string()]);
+        crate::start(&config, pom).unwrap();
+
+        clock.advance(TimeDelta::minutes(10));
+        let Status::Active(next) = crate::split(&config, &clock).unwrap() else {
+            panic!("Expected an active Pomodoro");
+        };
+
+        assert_eq!(next.timer().starts_at(), clock.now());
+        assert_eq!(next.timer().duration(), TimeDelta::minutes(15));
+        assert_eq!(next.description(), Some("long task"));
+        assert_eq!(next.tags(), Some(&vec!["work".to_string()]));
+        assert_eq!(
+            Status::load(&config.state_file_path).unwrap(),
+            Status::Active(next)
+        );
+
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(history.entries()[0].duration(), TimeDelta::minutes(10));
+        assert_eq!(history.entries()[0].description(), Some("long task"));
+
+        clock.advance(TimeDelta::minutes(20));
+        assert!(crate::split(&config, &clock).is_err());
+        assert!(Status::Inactive.split(clock.now()).is_err());
+    }
+
+    #[test]
+    fn finish_at_timer_end_ignores_time_since() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+        crate::start(&config, Pomodoro::new(clock.now(), TimeDelta::minutes(25))).unwrap();
+
+        clock.advance(TimeDelta::minutes(35));
+        let outcome = crate::finish_at(&config, &clock, FinishTime::TimerEnd).unwrap();
+        assert!(
+            matches!(outcome, FinishOutcome::Archived(entry) if entry.duration() == TimeDelta::minutes(25))
+        );
+
+        crate::take_short_break(&config, Timer::new(clock.now(), TimeDelta::minutes(5))).unwrap();
+        clock.advance(TimeDelta::minutes(3));
+        assert_eq!(
+            crate::finish_at(&config, &clock, FinishTime::TimerEnd).unwrap(),
+            FinishOutcome::BreakEnded(Phase::ShortBreak)
+        );
+
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries()[0].duration(), TimeDelta::minutes(25));
+        assert_eq!(history.entries()[1].duration(), TimeDelta::minutes(3));
+    }
+
+    #[test]
+    fn pomodoro_duration_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            min_pomodoro_duration: Some(TimeDelta::minutes(5)),
+            max_pomodoro_duration: Some(TimeDelta::minutes(90)),
+            ..test_config(dir.path())
+        };
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let start = |minutes: i64, seconds: i64| {
+            let result = crate::start(
+                &config,
+                Pomodoro::new(
+                    dt,
+                    TimeDelta::minutes(minutes) + TimeDelta::seconds(seconds),
+                ),
+            );
+            let _ = crate::clear(&config);
+            result
+        };
+
+        assert!(matches!(start(4, 59), Err(TomateError::TooShort { .. })));
+        assert!(start(5, 0).is_ok());
+        assert!(start(90, 0).is_ok());
+        assert!(matches!(start(90, 1), Err(TomateError::TooLong { .. })));
+        assert_eq!(
+            start(250, 0).unwrap_err().to_string(),
+            "A Pomodoro can last at most 90 minutes"
+        );
+    }
+
+    #[test]
+    fn restart_and_split_respect_duration_limits() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            min_pomodoro_duration: Some(TimeDelta::minutes(5)),
+            max_pomodoro_duration: Some(TimeDelta::minutes(90)),
+            ..test_config(dir.path())
+        };
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+        crate::start(&config, Pomodoro::new(clock.now(), TimeDelta::minutes(25))).unwrap();
+
+        assert!(matches!(
+            crate::restart(&config, &clock, Some(TimeDelta::minutes(120))),
+            Err(TomateError::TooLong { .. })
+        ));
+        assert!(matches!(
+            crate::restart(&config, &clock, Some(TimeDelta::minutes(1))),
+            Err(TomateError::TooShort { .. })
+        ));
+        assert!(crate::restart(&config, &clock, Some(TimeDelta::minutes(30))).is_ok());
+
+        // Only 3 minutes would be left for the new Pomodoro
+        clock.advance(TimeDelta::minutes(27));
+        assert!(matches!(
+            crate::split(&config, &clock),
+            Err(TomateError::TooShort { .. })
+        ));
+        assert!(History::load(&config.history_file_path)
+            .unwrap()
+            .entries()
+            .is_empty());
+
+        let Status::Active(pom) = Status::load(&config.state_file_path).unwrap() else {
+            panic!("Pomodoro should still be running");
+        };
+        assert_eq!(pom.timer().duration(), TimeDelta::minutes(30));
+    }
+
+    #[test]
+    fn finishing_break_archives_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+        let dur = TimeDelta::new(5 * 60, 0).unwrap();
+
+        crate::take_short_break(&config, Timer::new(clock.now(), dur)).unwrap();
+
+        clock.advance(TimeDelta::new(4 * 60, 0).unwrap());
+        crate::finish(&config, &clock).unwrap();
+
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(history.entries()[0].phase(), Phase::ShortBreak);
+        assert_eq!(
+            history.entries()[0].duration(),
+            TimeDelta::new(4 * 60, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn auto_finish_archives_done_pomodoro() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = test_config(dir.path());
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+
+        crate::start(&config, Pomodoro::new(clock.now(), TimeDelta::minutes(25))).unwrap();
+        clock.advance(TimeDelta::minutes(30));
+
+        assert!(!crate::auto_finish(&config, &clock).unwrap());
+        assert!(History::load(&config.history_file_path)
+            .unwrap()
+            .entries()
+            .is_empty());
+
+        config.auto_finish_on_access = true;
+        assert!(crate::auto_finish(&config, &clock).unwrap());
+
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(
+            Status::load(&config.state_file_path).unwrap(),
+            Status::Inactive
+        );
+    }
+
+    #[test]
+    fn auto_finish_leaves_paused_pomodoro_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            auto_finish_on_access: true,
+            idle_timeout: Some(TimeDelta::minutes(5)),
+            ..test_config(dir.path())
+        };
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        crate::start(&config, Pomodoro::new(dt, TimeDelta::minutes(25))).unwrap();
+
+        // Away since 12:12
+        let clock = MockClock::new(dt + TimeDelta::minutes(20));
+        let idle = MockIdle(Cell::new(Some(TimeDelta::minutes(8))));
+        crate::pause_when_idle(&config, &clock, &idle).unwrap();
+
+        // Long after the Pomodoro would have run out
+        clock.advance(TimeDelta::minutes(30));
+        assert!(!crate::auto_finish(&config, &clock).unwrap());
+
+        let Status::Active(pom) = Status::load(&config.state_file_path).unwrap() else {
+            panic!("Pomodoro should still be running");
+        };
+        assert_eq!(pom.timer().remaining(clock.now()), TimeDelta::minutes(13));
+        assert_eq!(pom.timer().percent(clock.now()), 48);
+        assert!(History::load(&config.history_file_path)
+            .unwrap()
+            .entries()
+            .is_empty());
+    }
+
+    #[test]
+    fn finish_break_and_start_pomodoro() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+
+        crate::take_short_break(&config, Timer::new(clock.now(), TimeDelta::minutes(5))).unwrap();
+        clock.advance(TimeDelta::minutes(5));
+
+        let pom = Pomodoro::new(clock.now(), TimeDelta::minutes(25));
+        crate::finish_break_and_start(&config, &clock, pom.clone()).unwrap();
+
+        assert_eq!(
+            Status::load(&config.state_file_path).unwrap(),
+            Status::Active(pom)
+        );
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries().len(), 1);
+        assert_eq!(history.entries()[0].phase(), Phase::ShortBreak);
+    }
+
+    /// Fill the hooks directory with hooks that write their name to a log, and return the log's path
+    #[cfg(unix)]
+    fn recording_hooks(config: &Config) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+
+        let log = config.hooks_directory.with_file_name("hooks.log");
+        std::fs::create_dir_all(&config.hooks_directory).unwrap();
+
+        for hook in crate::Hook::all() {
+            let path = hook.path(&config.hooks_directory);
+            std::fs::write(
+                &path,
+                format!(
+                    "#!/bin/sh\necho {} >> '{}'\n",
+                    hook.file_name(),
+                    log.display()
+                ),
+            )
+            .unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        log
+    }
+
+    /// Take the hooks that have run from a log written by [`recording_hooks`]
+    #[cfg(unix)]
+    fn take_hook_log(log: &std::path::Path) -> Vec<String> {
+        let lines = std::fs::read_to_string(log)
+            .unwrap_or_default()
+            .lines()
+            .map(str::to_owned)
+            .collect();
+        let _ = std::fs::remove_file(log);
+        lines
+    }
+
+    #[test]
+    fn pause_after_suspend_moves_timer() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            pause_on_suspend: true,
+            ..test_config(dir.path())
+        };
+        let interval = TimeDelta::minutes(1);
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        crate::start(&config, Pomodoro::new(dt, TimeDelta::minutes(25))).unwrap();
+
+        // The first tick only records when it ran
+        let clock = MockClock::new(dt + TimeDelta::minutes(1));
+        assert_eq!(
+            crate::pause_after_suspend(&config, &clock, interval).unwrap(),
+            None
+        );
+
+        // A tick that's only a little late is not a suspend
+        clock.advance(TimeDelta::seconds(90));
+        assert_eq!(
+            crate::pause_after_suspend(&config, &clock, interval).unwrap(),
+            None
+        );
+
+        // Suspended for an hour after the tick that was due at 12:03:30
+        clock.advance(TimeDelta::hours(1) + TimeDelta::minutes(1));
+        assert_eq!(
+            crate::pause_after_suspend(&config, &clock, interval).unwrap(),
+            Some(TimeDelta::hours(1))
+        );
+
+        let Status::Active(pom) = Status::load(&config.state_file_path).unwrap() else {
+            panic!("Pomodoro should still be running");
+        };
+        assert_eq!(pom.timer().starts_at(), dt);
+        assert_eq!(pom.timer().paused_at(), None);
+        assert_eq!(
+            pom.timer().remaining(clock.now()),
+            TimeDelta::minutes(21) + TimeDelta::seconds(30)
+        );
+
+        clock.advance(TimeDelta::hours(1));
+        crate::finish_at(&config, &clock, FinishTime::TimerEnd).unwrap();
+        let history = History::load(&config.history_file_path).unwrap();
+        let entry = &history.entries()[0];
+        assert_eq!(entry.started_at(), dt);
+        assert_eq!(entry.duration(), TimeDelta::minutes(25));
+    }
+
+    #[test]
+    fn archived_paused_pomodoro_keeps_its_start() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            idle_timeout: Some(TimeDelta::minutes(5)),
+            ..test_config(dir.path())
+        };
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        crate::start(&config, Pomodoro::new(dt, TimeDelta::minutes(25))).unwrap();
+
+        // Away from 12:10 to 12:30
+        let clock = MockClock::new(dt + TimeDelta::minutes(30));
+        let idle = MockIdle(Cell::new(Some(TimeDelta::minutes(20))));
+        crate::pause_when_idle(&config, &clock, &idle).unwrap();
+        idle.0.set(Some(TimeDelta::zero()));
+        crate::pause_when_idle(&config, &clock, &idle).unwrap();
+
+        clock.advance(TimeDelta::minutes(5));
+        crate::finish(&config, &clock).unwrap();
+
+        let history = History::load(&config.history_file_path).unwrap();
+        let entry = &history.entries()[0];
+        assert_eq!(entry.started_at(), dt);
+        assert_eq!(entry.duration(), TimeDelta::minutes(15));
+    }
+
+    struct MockIdle(Cell<Option<TimeDelta>>);
+
+    impl crate::idle::IdleSource for MockIdle {
+        fn idle_time(&self) -> crate::Result<Option<TimeDelta>> {
+            Ok(self.0.get())
+        }
+    }
+
+    #[test]
+    fn pause_when_idle_and_resume_on_return() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            idle_timeout: Some(TimeDelta::minutes(5)),
+            ..test_config(dir.path())
+        };
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        crate::start(&config, Pomodoro::new(dt, TimeDelta::minutes(25))).unwrap();
+        let clock = MockClock::new(dt + TimeDelta::minutes(10));
+        let idle = MockIdle(Cell::new(Some(TimeDelta::minutes(1))));
+
+        assert_eq!(
+            crate::pause_when_idle(&config, &clock, &idle).unwrap(),
+            None
+        );
+
+        // Away since 12:12
+        clock.advance(TimeDelta::minutes(10));
+        idle.0.set(Some(TimeDelta::minutes(8)));
+        assert_eq!(
+            crate::pause_when_idle(&config, &clock, &idle).unwrap(),
+            Some(IdlePause::Paused)
+        );
+
+        clock.advance(TimeDelta::minutes(10));
+        idle.0.set(Some(TimeDelta::minutes(18)));
+        assert_eq!(
+            crate::pause_when_idle(&config, &clock, &idle).unwrap(),
+            Some(IdlePause::Paused)
+        );
+
+        // Back at 12:39
+        clock.advance(TimeDelta::minutes(10));
+        idle.0.set(Some(TimeDelta::minutes(1)));
+        assert_eq!(
+            crate::pause_when_idle(&config, &clock, &idle).unwrap(),
+            Some(IdlePause::Resumed(TimeDelta::minutes(27)))
+        );
+
+        let Status::Active(pom) = Status::load(&config.state_file_path).unwrap() else {
+            panic!("Pomodoro should still be running");
+        };
+        assert_eq!(pom.timer().paused_at(), None);
+        assert_eq!(pom.timer().starts_at(), dt);
+        assert_eq!(pom.timer().ends_at(), dt + TimeDelta::minutes(52));
+        assert_eq!(
+            crate::pause_when_idle(&config, &clock, &idle).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn pause_when_idle_leaves_breaks_and_unknown_idle_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            idle_timeout: Some(TimeDelta::minutes(5)),
+            ..test_config(dir.path())
+        };
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let clock = MockClock::new(dt + TimeDelta::minutes(10));
+
+        crate::start(&config, Pomodoro::new(dt, TimeDelta::minutes(25))).unwrap();
+        let unknown = MockIdle(Cell::new(None));
+        assert_eq!(
+            crate::pause_when_idle(&config, &clock, &unknown).unwrap(),
+            None
+        );
+        crate::clear(&config).unwrap();
+
+        crate::take_short_break(&config, Timer::new(dt, TimeDelta::minutes(15))).unwrap();
+        let away = MockIdle(Cell::new(Some(TimeDelta::minutes(8))));
+        assert_eq!(
+            crate::pause_when_idle(&config, &clock, &away).unwrap(),
+            None
+        );
+
+        let no_timeout = test_config(dir.path());
+        assert_eq!(
+            crate::pause_when_idle(&no_timeout, &clock, &away).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn pause_after_suspend_is_opt_in() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        crate::start(&config, Pomodoro::new(dt, TimeDelta::minutes(25))).unwrap();
+
+        let clock = MockClock::new(dt + TimeDelta::minutes(1));
+        let interval = TimeDelta::minutes(1);
+        crate::pause_after_suspend(&config, &clock, interval).unwrap();
+        clock.advance(TimeDelta::hours(8));
+        assert_eq!(
+            crate::pause_after_suspend(&config, &clock, interval).unwrap(),
+            None
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tick_passes_remaining_time() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let log = dir.path().join("ticks.log");
+        std::fs::create_dir_all(&config.hooks_directory).unwrap();
+        let hook = crate::Hook::Tick.path(&config.hooks_directory);
+        std::fs::write(
+            &hook,
+            format!(
+                "#!/bin/sh\necho $TOMATE_REMAINING_SECONDS >> '{}'\n",
+                log.display()
+            ),
+        )
+        .unwrap();
+        std::fs::set_permissions(&hook, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        let id = pom.id().unwrap();
+        crate::start(&config, pom).unwrap();
+
+        let tick = |minutes: i64, id| {
+            let clock = crate::FixedClock(dt + TimeDelta::minutes(minutes));
+            crate::tick(&config, &clock, id).unwrap()
+        };
+
+        assert!(tick(1, id));
+        assert!(tick(24, id));
+        assert!(!tick(10, uuid::Uuid::new_v4()));
+        assert!(!tick(26, id));
+        assert_eq!(std::fs::read_to_string(&log).unwrap(), "1440\n60\n");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reminders_are_rate_limited() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = Config {
+            reminder_interval: Some(TimeDelta::minutes(2)),
+            timer_check_grace: TimeDelta::zero(),
+            ..test_config(dir.path())
+        };
+        let log = recording_hooks(&config);
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        crate::start(&config, Pomodoro::new(dt, TimeDelta::minutes(25))).unwrap();
+        take_hook_log(&log);
+
+        let remind = |minutes: i64| {
+            let clock = crate::FixedClock(dt + TimeDelta::minutes(minutes));
+            crate::remind(&config, &clock).unwrap()
+        };
+
+        assert!(!remind(24));
+        assert!(remind(25));
+        assert!(!remind(26));
+        assert!(remind(27));
+        assert_eq!(take_hook_log(&log), ["reminder", "reminder"]);
+
+        let status = Status::load(&config.state_file_path).unwrap();
+        assert!(status.is_active());
+        let Status::Active(pom) = status else {
+            unreachable!()
+        };
+        assert_eq!(
+            pom.timer().last_notified_at(),
+            Some(dt + TimeDelta::minutes(27))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn each_transition_runs_its_hooks_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let log = recording_hooks(&config);
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+
+        crate::start(&config, Pomodoro::new(clock.now(), TimeDelta::minutes(25))).unwrap();
+        assert_eq!(take_hook_log(&log), ["start"]);
+
+        clock.advance(TimeDelta::minutes(25));
+        crate::finish(&config, &clock).unwrap();
+        assert_eq!(take_hook_log(&log), ["stop", "pomodoro-end"]);
+
+        crate::take_short_break(&config, Timer::new(clock.now(), TimeDelta::minutes(5))).unwrap();
+        assert_eq!(take_hook_log(&log), ["break", "short-break-start"]);
+
+        clock.advance(TimeDelta::minutes(5));
+        let pom = Pomodoro::new(clock.now(), TimeDelta::minutes(25));
+        crate::finish_break_and_start(&config, &clock, pom).unwrap();
+        assert_eq!(take_hook_log(&log), ["stop", "short-break-end", "start"]);
+
+        crate::clear(&config).unwrap();
+        assert_eq!(take_hook_log(&log), ["stop", "pomodoro-end"]);
+
+        crate::take_long_break(&config, Timer::new(clock.now(), TimeDelta::minutes(15))).unwrap();
+        assert_eq!(take_hook_log(&log), ["break", "long-break-start"]);
+
+        clock.advance(TimeDelta::minutes(20));
+        assert!(crate::auto_finish(
+            &Config {
+                auto_finish_on_access: true,
+                ..config.clone()
+            },
+            &clock
+        )
+        .unwrap());
+        assert_eq!(take_hook_log(&log), ["stop", "long-break-end"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn clear_and_finish_run_end_hooks_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+        let log = recording_hooks(&config);
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+
+        crate::take_short_break(&config, Timer::new(clock.now(), TimeDelta::minutes(5))).unwrap();
+        take_hook_log(&log);
+        crate::clear(&config).unwrap();
+        assert_eq!(take_hook_log(&log), ["stop", "short-break-end"]);
+
+        crate::take_short_break(&config, Timer::new(clock.now(), TimeDelta::minutes(5))).unwrap();
+        take_hook_log(&log);
+        crate::finish(&config, &clock).unwrap();
+        assert_eq!(take_hook_log(&log), ["stop", "short-break-end"]);
+
+        assert_eq!(
+            crate::finish(&config, &clock).unwrap(),
+            FinishOutcome::NothingActive
+        );
+        crate::clear(&config).unwrap();
+        assert!(take_hook_log(&log).is_empty());
+    }
+
+    #[test]
+    fn bad_pomodoro_keeps_break_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+        let timer = Timer::new(clock.now(), TimeDelta::minutes(5));
+
+        crate::take_short_break(&config, timer.clone()).unwrap();
+
+        let mut pom = Pomodoro::new(clock.now(), TimeDelta::minutes(25));
+        pom.set_tags(vec!["a,b".to_string()]);
+        assert!(crate::finish_break_and_start(&config, &clock, pom).is_err());
+
+        assert_eq!(
+            Status::load(&config.state_file_path).unwrap(),
+            Status::ShortBreak(timer.into())
+        );
+        assert!(!config.history_file_path.exists());
+    }
+
+    #[test]
+    fn failed_save_keeps_break_running() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = test_config(dir.path());
+
+        let clock = MockClock::new("2024-03-27T12:00:00-06:00".parse().unwrap());
+        let timer = Timer::new(clock.now(), TimeDelta::minutes(5));
+
+        crate::take_short_break(&config, timer.clone()).unwrap();
+        // A directory where the new state is written first makes saving fail
+        std::fs::create_dir(dir.path().join(".current.toml.tmp")).unwrap();
+
+        let pom = Pomodoro::new(clock.now(), TimeDelta::minutes(25));
+        assert!(crate::finish_break_and_start(&config, &clock, pom).is_err());
+
+        assert_eq!(
+            Status::load(&config.state_file_path).unwrap(),
+            Status::ShortBreak(timer.into())
+        );
+        assert!(!config.history_file_path.exists());
+    }
+
+    #[test]
+    fn failed_atomic_write_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("export.jsonl");
+        std::fs::write(&path, "original").unwrap();
+
+        let result = crate::write_atomic_with(&path, |writer| {
+            writer.write_all(b"partial")?;
+            anyhow::bail!("serialization failed")
+        });
+
+        assert!(result.is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "original");
+        assert!(!dir.path().join(".export.jsonl.tmp").exists());
+    }
 }
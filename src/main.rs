@@ -1,17 +1,32 @@
 use std::{
-    io::{self, Write},
-    path::PathBuf,
+    collections::BTreeSet,
+    ffi::OsString,
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
 };
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::{prelude::*, TimeDelta};
-use clap::{Parser, Subcommand};
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
 use colored::Colorize;
 use human_panic::setup_panic;
+use log::info;
 use prettytable::{color, format, Attr, Cell, Row, Table};
 
 use regex::Regex;
-use tomate::{Config, History, Pomodoro, Status, Timer};
+use tomate::doctor::CheckStatus;
+use tomate::duration;
+use tomate::idle::SessionIdle;
+use tomate::{
+    Break, BreakKind, Clock, Config, FinishOutcome, FinishTime, FixedClock, History, HistoryEntry,
+    Hook, IdlePause, Phase, Pomodoro, Status, Streaks, SystemClock, Timer, Totals,
+};
+
+mod scheduler;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -21,10 +36,54 @@ struct Args {
     /// Config file to use. [default: ${XDG_CONFIG_DIR}/tomate/config.toml]
     #[arg(short, long)]
     config: Option<PathBuf>,
+    /// Config profile to use instead of the default values
+    #[arg(short, long, global = true, env = "TOMATE_PROFILE")]
+    profile: Option<String>,
+    /// Session to use, which has its own state file but shares the history
+    #[arg(long, global = true, env = "TOMATE_SESSION")]
+    session: Option<String>,
+    /// Pretend that the current time is this RFC 3339 timestamp
+    #[arg(long, global = true, hide = true, value_parser = datetime_from_rfc3339)]
+    now: Option<DateTime<Local>>,
+    /// Log what would change instead of changing any files, running hooks, or scheduling timers
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// How to write log messages to stderr
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = LogFormat::Text,
+        env = "TOMATE_LOG_FORMAT"
+    )]
+    log_format: LogFormat,
+    /// When to color the output
+    ///
+    /// With `auto`, colors are used if stdout is a terminal and `NO_COLOR` isn't set.
+    #[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto)]
+    color: ColorChoice,
     #[command(flatten)]
     verbose: clap_verbosity_flag::Verbosity,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum ColorChoice {
+    /// Color the output if it goes to a terminal
+    Auto,
+    /// Always color the output
+    Always,
+    /// Never color the output
+    Never,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum LogFormat {
+    /// Plain text, like "[INFO  tomate] Saving history"
+    Text,
+    /// One JSON object per line, with a timestamp, level, target, and message
+    Json,
+}
+
 #[derive(Debug, Subcommand)]
 enum Command {
     /// Get the current Pomodoro
@@ -37,10 +96,18 @@ enum Command {
         ///
         /// %t - tags, comma-separated
         ///
+        /// %i - unique id of the Pomodoro
+        ///
         /// %r - remaining time, in mm:ss format (or hh:mm:ss if longer than an hour)
         ///
         /// %R - remaining time in seconds
         ///
+        /// %l - elapsed time, in mm:ss format (or hh:mm:ss if longer than an hour)
+        ///
+        /// %L - elapsed time in seconds
+        ///
+        /// %p - percent of the timer that has elapsed, from 0 to 100
+        ///
         /// %s - start time in RFC 3339 format
         ///
         /// %S - start time as a Unix timestamp
@@ -48,14 +115,119 @@ enum Command {
         /// %e - end time in RFC 3339 format
         ///
         /// %E - end time as a Unix timestamp
-        #[arg(short, long)]
+        ///
+        /// %f - end time on the local clock, like 3:25pm
+        ///
+        /// %F - start time on the local clock, like 3:00pm
+        ///
+        /// %o - time since the timer ran out, in mm:ss format (or hh:mm:ss if longer than an hour)
+        ///
+        /// %D - "done" if the timer has run out, or "active" if it hasn't
+        ///
+        /// %I - icon for the kind of timer, set with phase_icons in the config file
+        ///
+        /// %P - kind of timer: pomodoro, short-break, or long-break
+        ///
+        /// %% - a literal percent sign
+        ///
+        /// Any other % sequence is printed as-is.
+        ///
+        /// Nothing is printed if there is no timer, unless the format uses %P,
+        /// which is then "inactive" while every other token is empty.
+        #[arg(short, long, conflicts_with = "porcelain")]
         format: Option<String>,
+        /// Print the status as one line of tab-separated fields, for scripts
+        ///
+        /// The fields are the phase (pomodoro, short_break, or long_break),
+        /// the remaining seconds, the duration in seconds, the description,
+        /// and the comma-separated tags. Fields that don't apply are empty,
+        /// and tabs and newlines in the description are replaced with spaces.
+        /// Prints "inactive" if there is no timer.
+        /// This format will not change between versions, except to add fields to the end.
+        #[arg(long)]
+        porcelain: bool,
+        /// Print the status on one line, like "🍅 Write the report 12:34", for shell prompts
+        ///
+        /// The line is the icon for the kind of timer, the start of the
+        /// description, and the remaining time.
+        /// Prints nothing if there is no timer.
+        #[arg(long, conflicts_with_all = ["format", "porcelain"])]
+        compact: bool,
+        /// Keep redrawing the status every second until the timer runs out
+        ///
+        /// The status stays at the top of the terminal instead of scrolling.
+        /// Press Ctrl-C to stop watching.
+        #[arg(short, long, conflicts_with_all = ["format", "porcelain", "compact"])]
+        watch: bool,
     },
     /// Start a Pomodoro
     Start {
-        /// Length of the Pomodoro to start
-        #[arg(short, long, value_parser = duration_from_human)]
-        duration: Option<TimeDelta>,
+        #[command(flatten)]
+        duration: DurationArgs,
+        /// When the Pomodoro should end, as an RFC 3339 timestamp or a time of day like 15:00
+        ///
+        /// A time of day means the next time the clock shows it, which may be tomorrow.
+        #[arg(long, value_parser = end_time_from_arg, conflicts_with = "DurationArgs")]
+        until: Option<EndTime>,
+        /// Description of the task you're focusing on, or - to read it from stdin
+        description: Option<String>,
+        /// Tags to categorize the work you're doing, comma-separated
+        #[arg(short, long)]
+        tags: Option<String>,
+        /// Write the description, and optionally tags, in $EDITOR
+        ///
+        /// The first line is the description, and each line after it is a tag.
+        /// Tags given with --tags take precedence over the ones in the editor.
+        #[arg(long, conflicts_with = "description")]
+        edit: bool,
+        /// When you actually started, as an RFC 3339 timestamp or a time ago like -5m
+        #[arg(long, allow_hyphen_values = true, value_parser = start_time_from_arg)]
+        at: Option<StartTime>,
+        /// Name of a template in the config file to take the duration, description, and tags from
+        ///
+        /// Values given on the command line take precedence over the template's,
+        /// except that a description is added after the template's description.
+        #[arg(long)]
+        template: Option<String>,
+        /// Show a countdown and exit once the Pomodoro is finished
+        ///
+        /// The Pomodoro is finished when its timer runs out, instead of by a scheduled timer check.
+        #[arg(long, conflicts_with = "now")]
+        wait: bool,
+        /// What to do with the Pomodoro if waiting is interrupted with Ctrl-C
+        #[arg(long, value_enum, default_value_t = Interrupt::Finish, requires = "wait")]
+        on_interrupt: Interrupt,
+        /// Don't print the status after starting the timer
+        ///
+        /// Unlike the global --quiet, this keeps warnings and log messages.
+        #[arg(long)]
+        no_status: bool,
+    },
+    /// Start the current Pomodoro over from zero, keeping its description and tags
+    ///
+    /// The restarted Pomodoro is as long as the current one, unless a length is given.
+    Restart {
+        #[command(flatten)]
+        duration: DurationArgs,
+        /// Don't print the status after starting the timer
+        ///
+        /// Unlike the global --quiet, this keeps warnings and log messages.
+        #[arg(long)]
+        no_status: bool,
+    },
+    /// Log the time spent on the current Pomodoro so far, and continue the task in a new one
+    ///
+    /// The new Pomodoro keeps the description and tags, and lasts for the time that was left.
+    Split {
+        /// Don't print the status after starting the timer
+        ///
+        /// Unlike the global --quiet, this keeps warnings and log messages.
+        #[arg(long)]
+        no_status: bool,
+    },
+    /// Change the description or tags of the current Pomodoro
+    #[command(arg_required_else_help = true)]
+    Describe {
         /// Description of the task you're focusing on
         description: Option<String>,
         /// Tags to categorize the work you're doing, comma-separated
@@ -65,15 +237,44 @@ enum Command {
     /// Remove the existing Pomodoro, if any
     Clear,
     /// Finish a Pomodoro
-    Finish,
+    #[command(group(
+        clap::ArgGroup::new("then_start_args")
+            .args(["duration", "seconds"])
+            .multiple(true)
+            .requires("then_start")
+    ))]
+    Finish {
+        /// Start a Pomodoro as soon as the current break is finished
+        #[arg(long)]
+        then_start: bool,
+        #[command(flatten)]
+        duration: DurationArgs,
+        /// Description of the task you're focusing on
+        #[arg(requires = "then_start")]
+        description: Option<String>,
+        /// Tags to categorize the work you're doing, comma-separated
+        #[arg(short, long, requires = "then_start")]
+        tags: Option<String>,
+        /// Don't print the status after starting the timer
+        ///
+        /// Unlike the global --quiet, this keeps warnings and log messages.
+        #[arg(long, requires = "then_start")]
+        no_status: bool,
+    },
     /// Take a break
     Break {
-        /// Length of the break to start
-        #[arg(short, long, value_parser = duration_from_human)]
-        duration: Option<TimeDelta>,
+        #[command(flatten)]
+        duration: DurationArgs,
         /// Take a long break instead of a short break
         #[arg(short, long, default_value_t = false)]
         long: bool,
+        /// What the break is for, like "lunch", which is kept in the history
+        description: Option<String>,
+        /// Don't print the status after starting the timer
+        ///
+        /// Unlike the global --quiet, this keeps warnings and log messages.
+        #[arg(long)]
+        no_status: bool,
     },
     /// Interact with system timers
     Timer {
@@ -81,161 +282,811 @@ enum Command {
         command: TimerCommand,
     },
     /// Print a list of all logged Pomodoros
-    History,
+    History {
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+        #[command(flatten)]
+        options: HistoryOptions,
+    },
+    /// List every tag used in the history
+    Tags {
+        /// Also show how many entries use each tag
+        #[arg(long)]
+        count: bool,
+    },
+    /// Show totals of the time spent in Pomodoros and breaks
+    Stats {
+        /// Compare this week so far to the same part of last week
+        #[arg(long)]
+        compare: bool,
+        /// Print the figures as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show how many days in a row you've done at least one Pomodoro
+    Streak,
+    /// Draw a timeline of today's Pomodoros and breaks, from the first Pomodoro until now
+    Today,
+    /// Create the config file and hooks directory, with an example of each hook
+    ///
+    /// The examples aren't executable, and each explains how to turn it
+    /// into a hook. Files that already exist are left alone.
+    Init,
+    /// Inspect the hooks that tomate runs
+    Hooks {
+        #[command(subcommand)]
+        command: HooksCommand,
+    },
+    /// Print a shell completion script
+    Completions {
+        /// Shell to complete commands for
+        shell: clap_complete::Shell,
+    },
+    /// Print a man page in roff format
+    #[command(hide = true)]
+    Man {
+        /// Subcommand to print the man page for, instead of tomate itself
+        command: Option<String>,
+    },
+    /// Print a JSON Schema for one of tomate's files, for editor autocompletion
+    #[command(hide = true)]
+    Schema {
+        /// File to describe
+        file: SchemaFile,
+    },
+    /// Stream the current status as JSON over a Unix socket until interrupted
+    Serve,
     /// Delete all state and configuration files
-    Purge,
+    Purge {
+        /// Delete the files without asking for confirmation first
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Check for problems with tomate's environment and configuration
+    Doctor,
+}
+
+/// Length of a timer to start, shared by every command that starts one
+#[derive(Debug, clap::Args)]
+#[group(multiple = false)]
+struct DurationArgs {
+    /// Length of the timer, like 25m, 1h30m, 90s, or 1.5h (a bare number is minutes)
+    #[arg(short, long, value_parser = duration_from_human)]
+    duration: Option<TimeDelta>,
+    /// Length of the timer as a whole number of seconds, for scripts
+    #[arg(long, value_name = "SECONDS")]
+    seconds: Option<u32>,
+}
+
+impl DurationArgs {
+    /// Get the length that was given, if any
+    fn get(&self) -> Option<TimeDelta> {
+        self.duration.or_else(|| {
+            self.seconds
+                .map(|seconds| TimeDelta::seconds(seconds.into()))
+        })
+    }
+}
+
+#[derive(Debug, clap::Args)]
+struct HistoryOptions {
+    /// Show start dates relative to now, like "2h ago"
+    #[arg(long)]
+    relative: bool,
+    /// Print each entry as a line of JSON
+    #[arg(long)]
+    jsonl: bool,
+    /// Only show entries for this kind of timer
+    #[arg(long, value_enum)]
+    phase: Option<PhaseArg>,
+    /// Only show entries with this tag, or with any tag in a namespace like project:*
+    #[arg(long)]
+    tag: Option<String>,
+    /// Only show the last N entries, after any other filters
+    #[arg(short = 'n', long, value_name = "N")]
+    count: Option<usize>,
+    /// Show the newest entries first
+    #[arg(long)]
+    reverse: bool,
+    /// End the table with a row counting the entries and adding up the time spent in Pomodoros
+    #[arg(long)]
+    total: bool,
+    /// Write the history to this file instead of stdout
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+}
+
+#[derive(Debug, Subcommand)]
+enum HistoryCommand {
+    /// Change the description or tags of the most recent Pomodoro
+    #[command(alias = "edit")]
+    Amend {
+        /// New description for the Pomodoro
+        #[arg(short, long)]
+        description: Option<String>,
+        /// Tag to add to the Pomodoro, can be given multiple times
+        #[arg(long = "add-tag")]
+        add_tags: Vec<String>,
+        /// Tag to remove from the Pomodoro, can be given multiple times
+        #[arg(long = "remove-tag")]
+        remove_tags: Vec<String>,
+    },
+    /// Remove old entries from the history, keeping a backup of the file
+    Clear {
+        /// Remove entries that started before this date, like 2024-01-01, or RFC 3339 timestamp
+        #[arg(long, value_parser = datetime_from_date_arg)]
+        before: DateTime<Local>,
+    },
+    /// Remove duplicate entries, like ones imported twice
+    ///
+    /// Entries with the same id, or the same start time and duration, are
+    /// merged into the first one. Entries that can't be read are dropped.
+    /// The history file is backed up before it is rewritten.
+    Dedupe,
+    /// Write the history in a format other tools can read
+    Export {
+        /// Write an iCalendar file with one event per entry
+        #[arg(long, required = true)]
+        ics: bool,
+        /// Write to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Append entries exported from another tool to the history
+    Import {
+        /// File to import
+        file: PathBuf,
+        /// Format of the file, guessed from its extension if not given
+        #[arg(long, value_enum)]
+        format: Option<ImportFormat>,
+        /// Leave out entries with the same start time and duration as one already in the history
+        #[arg(long)]
+        skip_duplicates: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ImportFormat {
+    Json,
+    Csv,
+}
+
+/// What to do with a timer when `--wait` is interrupted
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+enum Interrupt {
+    /// Archive the time spent so far, like "tomate finish"
+    Finish,
+    /// Discard the timer, like "tomate clear"
+    Clear,
+}
+
+#[derive(Debug, Subcommand)]
+enum HooksCommand {
+    /// List every hook, and whether an executable for it is in the hooks directory
+    List,
 }
 
 #[derive(Debug, Subcommand)]
 enum TimerCommand {
     /// Check and execute any completed timers
+    ///
+    /// A timer that ran out a while ago, like while the computer was asleep,
+    /// is archived as stopping when it ran out instead of now.
     Check,
+    /// Show when the current timer should be checked, and the systemd timers scheduled to do it
+    List,
+    /// Run the tick hook if a Pomodoro is still running
+    ///
+    /// This is run by a repeating systemd timer if tick_interval is set,
+    /// which is stopped once the Pomodoro is over.
+    Tick {
+        /// Id of the Pomodoro to tick for
+        id: uuid::Uuid,
+    },
+    /// Play the sound that is configured for when a timer finishes
+    TestSound {
+        /// Kind of timer to play the sound for
+        #[arg(long, value_enum, default_value_t = PhaseArg::Pomodoro)]
+        phase: PhaseArg,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum SchemaFile {
+    /// The config file
+    Config,
+    /// The state file holding the current timer
+    State,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum PhaseArg {
+    Pomodoro,
+    ShortBreak,
+    LongBreak,
+}
+
+impl From<PhaseArg> for Phase {
+    fn from(phase: PhaseArg) -> Self {
+        match phase {
+            PhaseArg::Pomodoro => Phase::Pomodoro,
+            PhaseArg::ShortBreak => Phase::ShortBreak,
+            PhaseArg::LongBreak => Phase::LongBreak,
+        }
+    }
 }
 
 fn main() -> Result<()> {
     setup_panic!();
-    env_logger::builder().format_timestamp(None).init();
 
     let args = Args::parse();
 
-    let config_path = if let Some(conf_path) = args.config {
-        conf_path
+    let mut logger = env_logger::builder();
+    logger.filter_level(args.verbose.log_level_filter());
+    match args.log_format {
+        LogFormat::Text => {
+            logger.format_timestamp(None);
+        }
+        LogFormat::Json => {
+            logger.format(|buf, record| {
+                writeln!(
+                    buf,
+                    "{}",
+                    json_log_line(
+                        Utc::now(),
+                        record.level(),
+                        record.target(),
+                        &record.args().to_string()
+                    )
+                )
+            });
+        }
+    }
+    logger.init();
+
+    let quiet = args.verbose.is_silent();
+    let color = use_color(
+        args.color,
+        quiet,
+        std::env::var_os("NO_COLOR"),
+        io::stdout().is_terminal(),
+    );
+    colored::control::set_override(color);
+
+    if let Command::Completions { shell } = &args.command {
+        write_completions(*shell, &mut io::stdout());
+        return Ok(());
+    }
+
+    if let Command::Man { command } = &args.command {
+        return write_man_page(command.as_deref(), &mut io::stdout());
+    }
+
+    if let Command::Schema { file } = &args.command {
+        return write_schema(*file, &mut io::stdout());
+    }
+
+    let config_path = if let Some(conf_path) = &args.config {
+        conf_path.clone()
     } else {
         tomate::default_config_path().with_context(|| "Unable to find default config path")?
     };
 
-    let config = Config::init(&config_path).with_context(|| "Failed to initialize config file")?;
+    if let Command::Init = &args.command {
+        if args.dry_run {
+            info!("Dry run: would create the config file and hooks");
+            return Ok(());
+        }
+
+        let created = tomate::init(&config_path)?;
+
+        if !quiet {
+            if created.is_empty() {
+                println!("Everything is already set up");
+            }
+            for path in created {
+                println!("Created {}", path.display().to_string().cyan());
+            }
+        }
+
+        return Ok(());
+    }
+
+    let mut config = if args.dry_run {
+        Config::load(&config_path)?.unwrap_or_default()
+    } else {
+        Config::init(&config_path).with_context(|| "Failed to initialize config file")?
+    };
+    if let Some(profile) = &args.profile {
+        config = config.profile(profile)?;
+    }
+    if let Some(session) = &args.session {
+        config = config.session(session)?;
+    }
+    config.dry_run = args.dry_run;
+
+    let clock: Box<dyn Clock> = match args.now {
+        Some(now) => Box::new(FixedClock(now)),
+        None => Box::new(SystemClock),
+    };
+
+    if matches!(
+        args.command,
+        Command::Status { .. } | Command::Start { .. } | Command::Break { .. }
+    ) {
+        tomate::auto_finish(&config, clock.as_ref())?;
+    }
 
     match &args.command {
-        Command::Status { format } => {
-            print_status(&config, format.clone())?;
+        Command::Status { watch: true, .. } => {
+            watch_status(&config, clock.as_ref(), quiet)?;
+        }
+        Command::Status {
+            format,
+            porcelain,
+            compact,
+            ..
+        } => {
+            let output = match format {
+                _ if *porcelain => StatusOutput::Porcelain,
+                _ if *compact => StatusOutput::Compact,
+                Some(format) => StatusOutput::Format(format.clone()),
+                None => StatusOutput::Full,
+            };
+
+            print_status(&config, clock.as_ref(), output, quiet)?;
         }
         Command::Start {
             duration,
+            until,
             description,
             tags,
+            edit,
+            at,
+            template,
+            wait,
+            on_interrupt,
+            no_status,
         } => {
-            let dur = duration.unwrap_or(config.pomodoro_duration);
-            let timer_seconds = dur.num_seconds();
+            let (description, tags) = if *edit {
+                let (description, edited_tags) = edit_description()?;
+                let tags = tags
+                    .clone()
+                    .or_else(|| (!edited_tags.is_empty()).then(|| edited_tags.join(",")));
+                (description, tags)
+            } else if description.as_deref() == Some("-") {
+                let mut input = String::new();
+                io::stdin()
+                    .read_to_string(&mut input)
+                    .with_context(|| "Failed to read description from stdin")?;
+                let input = input.trim();
+                ((!input.is_empty()).then(|| input.to_string()), tags.clone())
+            } else {
+                (description.clone(), tags.clone())
+            };
+
+            let now = clock.now();
+            let starts_at = match at {
+                Some(at) => at.resolve(now)?,
+                None => now,
+            };
+            let duration = match until {
+                Some(until) => Some(until.resolve(now)? - starts_at),
+                None => duration.get(),
+            };
+
+            let pom = new_pomodoro(
+                &config,
+                starts_at,
+                duration,
+                description.as_deref(),
+                tags.as_deref(),
+                template.as_deref(),
+            )?;
+            let timer = pom.timer().clone();
+            let id = pom.id();
+
+            if let Some(outcome) = start_pomodoro(&config, clock.as_ref(), pom.clone())? {
+                if !quiet {
+                    print_finish_outcome(&outcome);
+                }
 
-            let mut pom = Pomodoro::new(Local::now(), dur);
-            if let Some(desc) = description {
-                pom.set_description(desc);
+                return Ok(());
+            } else if *wait {
+                wait_for_pomodoro(&config, clock.as_ref(), id, *on_interrupt, !quiet)?;
+
+                return Ok(());
+            } else {
+                scheduler::schedule_finish(
+                    &args,
+                    &config,
+                    timer.remaining(now).num_seconds().max(1),
+                )?;
+                scheduler::schedule_ticks(&args, &config, &pom)?;
             }
 
-            if let Some(tags) = tags {
-                let tags: Vec<String> = tags.split(',').map(|s| s.to_string()).collect();
+            if !quiet && !no_status {
+                print_status(&config, clock.as_ref(), StatusOutput::Full, quiet)?;
+            }
+        }
+        Command::Restart {
+            duration,
+            no_status,
+        } => {
+            let status = tomate::restart(&config, clock.as_ref(), duration.get())?;
+
+            if let Status::Active(pom) = status {
+                scheduler::schedule_finish(&args, &config, pom.timer().duration().num_seconds())?;
+                scheduler::schedule_ticks(&args, &config, &pom)?;
+            }
 
-                pom.set_tags(tags);
+            if !quiet && !no_status {
+                print_status(&config, clock.as_ref(), StatusOutput::Full, quiet)?;
             }
+        }
+        Command::Split { no_status } => {
+            let status = tomate::split(&config, clock.as_ref())?;
 
-            tomate::start(&config, pom)?;
+            if let Status::Active(pom) = status {
+                scheduler::schedule_finish(&args, &config, pom.timer().duration().num_seconds())?;
+                scheduler::schedule_ticks(&args, &config, &pom)?;
+            }
 
-            let systemd_output = std::process::Command::new("systemd-run")
-                .args([
-                    "--user".to_string(),
-                    format!("--on-active={}", timer_seconds),
-                    "--timer-property=AccuracySec=100ms".to_string(),
-                    std::env::current_exe()?.to_str().unwrap().to_string(),
-                    "timer".to_string(),
-                    "check".to_string(),
-                ])
-                .output()
-                .with_context(|| "Failed to schedule systemd timer")?;
+            if !quiet && !no_status {
+                print_status(&config, clock.as_ref(), StatusOutput::Full, quiet)?;
+            }
+        }
+        Command::Finish {
+            then_start: false, ..
+        } => {
+            let outcome = tomate::finish(&config, clock.as_ref())?;
 
-            io::stdout().write_all(&systemd_output.stderr)?;
+            if outcome == FinishOutcome::NothingActive {
+                return Err(tomate::TomateError::NoActiveTimer.into());
+            }
 
-            print_status(&config, None)?;
+            if !quiet {
+                print_finish_outcome(&outcome);
+            }
+        }
+        Command::Finish {
+            then_start: true,
+            duration,
+            description,
+            tags,
+            no_status,
+        } => {
+            let pom = new_pomodoro(
+                &config,
+                clock.now(),
+                duration.get(),
+                description.as_deref(),
+                tags.as_deref(),
+                None,
+            )?;
+            let timer_seconds = pom.timer().duration().num_seconds();
+
+            tomate::finish_break_and_start(&config, clock.as_ref(), pom.clone())?;
+
+            scheduler::schedule_finish(&args, &config, timer_seconds)?;
+            scheduler::schedule_ticks(&args, &config, &pom)?;
+
+            if !quiet && !no_status {
+                print_status(&config, clock.as_ref(), StatusOutput::Full, quiet)?;
+            }
         }
-        Command::Finish => {
-            tomate::finish(&config)?;
+        Command::Describe { description, tags } => {
+            let tags = tags
+                .as_deref()
+                .map(|tags| tags.split(',').map(|s| s.to_string()).collect());
+
+            tomate::describe(&config, description.as_deref(), tags)?;
+
+            if !quiet {
+                print_status(&config, clock.as_ref(), StatusOutput::Full, quiet)?;
+            }
         }
         Command::Clear => {
             tomate::clear(&config)?;
         }
-        Command::Break { duration, long } => {
-            let timer = if *long {
-                let dur = duration.unwrap_or(config.long_break_duration);
-                let timer = Timer::new(Local::now(), dur);
-
-                tomate::take_long_break(&config, timer.clone())?;
-                timer
-            } else {
-                let dur = duration.unwrap_or(config.short_break_duration);
-                let timer = Timer::new(Local::now(), dur);
-
-                tomate::take_short_break(&config, timer.clone())?;
-
-                timer
-            };
-
-            println!();
-            print_progress_bar(&timer);
+        Command::Break {
+            duration,
+            long,
+            description,
+            no_status,
+        } => {
+            let rest = start_break(
+                &config,
+                clock.as_ref(),
+                duration.get(),
+                *long,
+                description.as_deref(),
+                |seconds| scheduler::schedule_finish(&args, &config, seconds),
+            )?;
+
+            if !quiet && !no_status {
+                println!();
+                print_progress_bar(rest.timer(), clock.now());
+            }
         }
         Command::Timer { command } => match command {
             TimerCommand::Check => {
+                if check_idle(&args, &config, clock.as_ref())? {
+                    // The repeating ticks keep checking until the user is back
+                    return Ok(());
+                }
                 let status = Status::load(&config.state_file_path)?;
 
-                match status {
-                    Status::Active(pom) => {
-                        if pom.timer().done(Local::now()) {
-                            tomate::finish(&config)?;
-                        }
+                let grace = config.timer_check_grace;
+
+                let done = match &status {
+                    Status::Active(pom) => pom.timer().done_within(clock.now(), grace),
+                    Status::ShortBreak(rest) | Status::LongBreak(rest) => {
+                        rest.timer().done_within(clock.now(), grace)
                     }
-                    Status::ShortBreak(timer) => {
-                        if timer.done(Local::now()) {
-                            tomate::finish(&config)?;
+                    Status::Inactive => {
+                        println!("No timers active");
+                        false
+                    }
+                };
+
+                if done {
+                    match config.reminder_interval {
+                        // Leave the timer for the user to finish, and check back until they do
+                        Some(interval) => {
+                            tomate::remind(&config, clock.as_ref())?;
+                            scheduler::schedule_finish(
+                                &args,
+                                &config,
+                                interval.num_seconds().max(1),
+                            )?;
+                        }
+                        None => {
+                            let outcome =
+                                tomate::finish_at(&config, clock.as_ref(), FinishTime::TimerEnd)?;
+                            if !quiet {
+                                print_finish_outcome(&outcome);
+                            }
                         }
                     }
-                    Status::LongBreak(timer) => {
-                        if timer.done(Local::now()) {
-                            tomate::finish(&config)?;
+                }
+            }
+            TimerCommand::List => {
+                print_timer_checks(&config, clock.as_ref())?;
+            }
+            TimerCommand::Tick { id } => {
+                if let Some(interval) = scheduler::tick_interval(&config) {
+                    check_suspend(&args, &config, clock.as_ref(), interval)?;
+                }
+                if check_idle(&args, &config, clock.as_ref())? {
+                    return Ok(());
+                }
+                if !tomate::tick(&config, clock.as_ref(), *id)? {
+                    scheduler::stop_ticks(&config, *id)?;
+                }
+            }
+            TimerCommand::TestSound { phase } => {
+                let path = config
+                    .sound_for((*phase).into())
+                    .with_context(|| "No sound file is configured")?;
+
+                tomate::play_sound(path)?;
+            }
+        },
+        Command::History { command, options } => match command {
+            None => {
+                print_history(&config, clock.as_ref(), options, color)?;
+            }
+            Some(HistoryCommand::Amend {
+                description,
+                add_tags,
+                remove_tags,
+            }) => {
+                let mut history = tomate::load_history(&config)?;
+                let entry = history
+                    .last_mut()
+                    .with_context(|| "There are no Pomodoros in the history to amend")?;
+
+                if let Some(desc) = description {
+                    entry.set_description(desc);
+                }
+
+                if !add_tags.is_empty() || !remove_tags.is_empty() {
+                    let mut tags = entry.tags().cloned().unwrap_or_default();
+                    for tag in add_tags {
+                        if !tags.contains(tag) {
+                            tags.push(tag.clone());
                         }
                     }
-                    Status::Inactive => {
-                        println!("No timers active");
+                    tags.retain(|tag| !remove_tags.contains(tag));
+                    entry.set_tags(tomate::normalize_tags(tags, config.lowercase_tags)?);
+                }
+
+                if config.dry_run {
+                    info!(
+                        "Dry run: would save amended history to {}",
+                        config.history_file_path.display().to_string().cyan()
+                    );
+                } else {
+                    tomate::backup_history(&config)?;
+                    history.save_rotated(&config.history_file_path, config.history_rotation)?;
+                }
+            }
+            Some(HistoryCommand::Clear { before }) => {
+                let count = tomate::prune_history(&config, *before)?;
+
+                if !quiet {
+                    if config.dry_run {
+                        println!("Would remove {} entries", count);
+                    } else {
+                        println!("Removed {} entries", count);
                     }
                 }
             }
-        },
-        Command::History => {
-            if !config.history_file_path.exists() {
-                return Ok(());
+            Some(HistoryCommand::Dedupe) => {
+                let (duplicates, unreadable) = tomate::dedupe_history(&config)?;
+
+                if !quiet {
+                    let verb = if config.dry_run {
+                        "Would remove"
+                    } else {
+                        "Removed"
+                    };
+                    println!("{} {} duplicate entries", verb, duplicates);
+                    if unreadable > 0 {
+                        println!("{} {} unreadable entries", verb, unreadable);
+                    }
+                }
+            }
+            Some(HistoryCommand::Export { ics: _, output }) => {
+                let history = tomate::load_history(&config)?;
+                let now = clock.now();
+
+                match output {
+                    Some(output) => {
+                        tomate::write_atomic_with(output, |writer| history.to_ics(writer, now))?
+                    }
+                    None => history.to_ics(io::BufWriter::new(io::stdout().lock()), now)?,
+                }
             }
+            Some(HistoryCommand::Import {
+                file,
+                format,
+                skip_duplicates,
+            }) => {
+                let format = format.unwrap_or_else(|| {
+                    if file.extension().is_some_and(|ext| ext == "csv") {
+                        ImportFormat::Csv
+                    } else {
+                        ImportFormat::Json
+                    }
+                });
+                let reader = std::fs::File::open(file)
+                    .with_context(|| format!("Failed to open {}", file.display()))?;
+                let imported = match format {
+                    ImportFormat::Json => History::import_json(io::BufReader::new(reader)),
+                    ImportFormat::Csv => History::import_csv(reader),
+                }
+                .with_context(|| format!("Failed to import {}", file.display()))?;
+
+                let count = tomate::import_history(&config, &imported, *skip_duplicates)?;
 
-            let history = History::load(&config.history_file_path)?;
+                if !quiet {
+                    println!(
+                        "Imported {} of {} entries from {}",
+                        count,
+                        imported.entries().len(),
+                        file.display().to_string().cyan()
+                    );
+                }
+            }
+        },
+        Command::Tags { count } => {
+            let history = tomate::load_history(&config)?;
 
-            let mut table = Table::new();
+            if *count {
+                for (tag, count) in history.tag_counts() {
+                    println!("{}\t{}", tag, count);
+                }
+            } else {
+                for tag in history.all_tags() {
+                    println!("{}", tag);
+                }
+            }
+        }
+        Command::Streak => {
+            let history = tomate::load_history(&config)?;
+            let today = clock.now().date_naive();
 
-            table.set_titles(Row::new(vec![
-                Cell::new("Date Started").with_style(Attr::Underline(true)),
-                Cell::new("Duration").with_style(Attr::Underline(true)),
-                Cell::new("Tags").with_style(Attr::Underline(true)),
-                Cell::new("Description").with_style(Attr::Underline(true)),
-            ]));
+            print_streaks(&history.streaks(today), today);
+        }
+        Command::Today => {
+            let now = clock.now();
+            let history = tomate::load_history(&config)?;
+            let mut entries: Vec<HistoryEntry> = history
+                .entries_for_day(now.date_naive())
+                .into_iter()
+                .cloned()
+                .collect();
+
+            // Show the running timer up to now too
+            match Status::load(&config.state_file_path)? {
+                Status::Active(pom) => {
+                    let mut pom = pom.clone();
+                    pom.finish(now);
+                    entries.push(HistoryEntry::archive(&pom, None)?);
+                }
+                Status::ShortBreak(rest) => {
+                    entries.push(HistoryEntry::archive_break(Phase::ShortBreak, &rest, now));
+                }
+                Status::LongBreak(rest) => {
+                    entries.push(HistoryEntry::archive_break(Phase::LongBreak, &rest, now));
+                }
+                Status::Inactive => {}
+            }
 
-            for pom in history.pomodoros().iter() {
-                let date = pom.timer().starts_at().format("%d %b %R").to_string();
-                let dur = to_human(&pom.timer().duration());
-                let tags = pom.tags().unwrap_or(&vec!["-".to_string()]).join(",");
-                let desc = pom.description().unwrap_or("-");
+            let width = crossterm::terminal::size()
+                .map(|(columns, _)| columns as usize)
+                .unwrap_or(80);
+            print_timeline(&entries, now, width, color);
+        }
+        Command::Stats { compare, json } => {
+            let history = tomate::load_history(&config)?;
 
-                table.add_row(Row::new(vec![
-                    Cell::new(&date).with_style(Attr::ForegroundColor(color::BLUE)),
-                    Cell::new(&dur)
-                        .style_spec("r")
-                        .with_style(Attr::ForegroundColor(color::CYAN)),
-                    Cell::new(&tags),
-                    Cell::new(desc),
-                ]));
+            if *compare {
+                print_week_comparison(&history, clock.now(), *json)?;
+            } else {
+                print_totals(&history.totals(), *json)?;
+            }
+        }
+        Command::Hooks { command } => match command {
+            HooksCommand::List => {
+                print_hooks(&config, color)?;
             }
-            table.set_format(*format::consts::FORMAT_CLEAN);
-            table.printstd();
+        },
+        Command::Completions { .. }
+        | Command::Man { .. }
+        | Command::Schema { .. }
+        | Command::Init => {
+            unreachable!(
+                "completions, man pages, schemas, and init are handled before loading config"
+            )
         }
-        Command::Purge => {
+        #[cfg(unix)]
+        Command::Serve => {
+            tomate::serve::serve(&config, clock.as_ref())?;
+        }
+        #[cfg(not(unix))]
+        Command::Serve => {
+            bail!("tomate serve needs Unix domain sockets, which this platform does not have");
+        }
+        Command::Purge { yes } => {
+            if !yes && !config.dry_run {
+                let mut paths = vec![config.state_file_path.clone()];
+                paths.extend(History::rotated_files(
+                    &config.history_file_path,
+                    config.history_rotation,
+                )?);
+                paths.push(config_path.clone());
+                let files: Vec<&Path> = paths
+                    .iter()
+                    .map(PathBuf::as_path)
+                    .filter(|path| path.exists())
+                    .collect();
+
+                if !files.is_empty() && !confirm_purge(&files)? {
+                    bail!("Purge cancelled, nothing was deleted");
+                }
+            }
+
             tomate::purge(&config)?;
 
-            if config_path.exists() {
+            if config.dry_run {
+                info!(
+                    "Dry run: would remove config file at {}",
+                    config_path.display().to_string().cyan()
+                );
+            } else if config_path.exists() {
                 println!(
                     "Removing config file at {}",
                     config_path.display().to_string().cyan()
@@ -243,46 +1094,423 @@ fn main() -> Result<()> {
                 std::fs::remove_file(&config_path)?;
             }
         }
-    }
-
-    Ok(())
-}
+        Command::Doctor => {
+            println!("Config file: {}", config_path.display().to_string().cyan());
+            println!(
+                "State file: {}",
+                config.state_file_path.display().to_string().cyan()
+            );
+            println!(
+                "History file: {}",
+                config.history_file_path.display().to_string().cyan()
+            );
+            println!(
+                "Hooks directory: {}",
+                config.hooks_directory.display().to_string().cyan()
+            );
+            println!();
 
-fn print_status(config: &Config, format: Option<String>) -> Result<()> {
-    let status = Status::load(&config.state_file_path)?;
+            let checks = tomate::doctor::run_checks(&config, &config_path);
 
-    if let Some(format) = format {
-        match status {
-            Status::Active(pom) => {
-                println!("{}", format_pomodoro(&pom, &format, Local::now()));
-            }
-            Status::ShortBreak(timer) | Status::LongBreak(timer) => {
-                println!("{}", format_timer(&timer, &format, Local::now()));
+            for check in &checks {
+                let label = match check.status {
+                    CheckStatus::Pass => "pass".green(),
+                    CheckStatus::Warn => "warn".yellow(),
+                    CheckStatus::Fail => "fail".red(),
+                };
+                println!("[{}] {}", label.bold(), check.message);
             }
-            Status::Inactive => {
-                // nothing!
+
+            let failures = checks
+                .iter()
+                .filter(|check| check.status == CheckStatus::Fail)
+                .count();
+            if failures > 0 {
+                bail!("{} check(s) failed", failures);
             }
         }
-
-        return Ok(());
     }
 
-    match status {
-        Status::Active(pom) => {
-            if let Some(format) = format {
-                println!("{}", format_pomodoro(&pom, &format, Local::now()));
+    Ok(())
+}
 
-                return Ok(());
+/// Ask the user on stdin whether to delete `files`
+///
+/// Fails if stdin is not a terminal, since nobody is there to answer.
+fn confirm_purge(files: &[&Path]) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        bail!(
+            "Refusing to purge without confirmation, use --yes to delete files non-interactively"
+        );
+    }
+
+    println!("This will permanently delete:");
+    for file in files {
+        println!("\t- {}", file.display().to_string().cyan());
+    }
+    print!("Delete these files? [y/N] ");
+    io::stdout().flush()?;
+
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+
+    Ok(is_yes(&answer))
+}
+
+fn is_yes(answer: &str) -> bool {
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn write_completions<W: Write>(shell: clap_complete::Shell, writer: &mut W) {
+    clap_complete::generate(shell, &mut Args::command(), "tomate", writer);
+}
+
+fn write_man_page<W: Write>(command: Option<&str>, writer: &mut W) -> Result<()> {
+    let mut cmd = Args::command();
+
+    if let Some(name) = command {
+        cmd = cmd
+            .find_subcommand(name)
+            .with_context(|| format!("No such command \"{}\"", name))?
+            .clone()
+            .display_name(format!("tomate-{}", name))
+            .bin_name(format!("tomate {}", name))
+            .version(env!("CARGO_PKG_VERSION"));
+    }
+
+    clap_mangen::Man::new(cmd)
+        .render(writer)
+        .with_context(|| "Failed to write man page")
+}
+
+fn write_schema<W: Write>(file: SchemaFile, writer: &mut W) -> Result<()> {
+    let schema = match file {
+        SchemaFile::Config => schemars::schema_for!(Config),
+        SchemaFile::State => schemars::schema_for!(Status),
+    };
+
+    serde_json::to_writer_pretty(&mut *writer, &schema)
+        .with_context(|| "Failed to write schema")?;
+    writeln!(writer).with_context(|| "Failed to write schema")
+}
+
+const EDIT_INSTRUCTIONS: &str = "
+# Write a description for the Pomodoro on the first line,
+# and any tags on the lines after it, one per line.
+# Lines starting with '#' are ignored, and an empty file means no description.
+";
+
+/// Open `$EDITOR` to write a Pomodoro's description and tags
+fn edit_description() -> Result<(Option<String>, Vec<String>)> {
+    let editor = std::env::var("EDITOR")
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .with_context(|| "Set $EDITOR to write the description in an editor")?;
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap_or_default();
+
+    let path = std::env::temp_dir().join(format!(
+        "tomate-description-{}.txt",
+        uuid::Uuid::new_v4().simple()
+    ));
+    std::fs::write(&path, EDIT_INSTRUCTIONS)
+        .with_context(|| format!("Failed to create {}", path.display()))?;
+
+    let result = std::process::Command::new(program)
+        .args(words)
+        .arg(&path)
+        .status()
+        .with_context(|| format!("Failed to run editor {}", editor))
+        .and_then(|status| {
+            if !status.success() {
+                bail!("Editor {} exited with {}", editor, status);
+            }
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read {}", path.display()))
+        });
+    let _ = std::fs::remove_file(&path);
+
+    Ok(parse_edited_description(&result?))
+}
+
+/// Split text written in an editor into a description and tags
+///
+/// The first line is the description and the rest are tags. Blank lines and
+/// lines starting with `#` are skipped.
+fn parse_edited_description(text: &str) -> (Option<String>, Vec<String>) {
+    let mut lines = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'));
+
+    let description = lines.next().map(str::to_string);
+    let tags = lines.map(str::to_string).collect();
+
+    (description, tags)
+}
+
+/// Start a short or long break, and schedule a check for when it runs out
+///
+/// `schedule` is given the number of seconds until the check should run.
+fn start_break<F>(
+    config: &Config,
+    clock: &dyn Clock,
+    duration: Option<TimeDelta>,
+    long: bool,
+    description: Option<&str>,
+    schedule: F,
+) -> Result<Break>
+where
+    F: FnOnce(i64) -> Result<()>,
+{
+    let default_duration = if long {
+        config.long_break_duration
+    } else {
+        config.short_break_duration
+    };
+    let mut rest = Break::new(clock.now(), duration.unwrap_or(default_duration));
+    if let Some(description) = description {
+        rest.set_description(description);
+    }
+
+    if long {
+        tomate::take_long_break(config, rest.clone())?;
+    } else {
+        tomate::take_short_break(config, rest.clone())?;
+    }
+
+    schedule(rest.timer().duration().num_seconds().max(1))?;
+
+    Ok(rest)
+}
+
+/// Start a Pomodoro, finishing it right away if it has already run out
+///
+/// A Pomodoro started far enough in the past is archived as having stopped
+/// when its timer ended, and the outcome of finishing it is returned.
+fn start_pomodoro(
+    config: &Config,
+    clock: &dyn Clock,
+    pom: Pomodoro,
+) -> Result<Option<FinishOutcome>> {
+    let done = pom.timer().done(clock.now());
+
+    tomate::start(config, pom)?;
+
+    if !done {
+        return Ok(None);
+    }
+
+    info!("Pomodoro already ended, finishing it now");
+    let outcome = tomate::finish_at(config, clock, FinishTime::TimerEnd)?;
+
+    Ok(Some(outcome))
+}
+
+/// Create a Pomodoro from command-line arguments
+///
+/// Arguments that aren't given are taken from the named template, if any,
+/// and then from the config. See [`Config::pomodoro_builder`].
+fn new_pomodoro(
+    config: &Config,
+    starts_at: DateTime<Local>,
+    duration: Option<TimeDelta>,
+    description: Option<&str>,
+    tags: Option<&str>,
+    template: Option<&str>,
+) -> Result<Pomodoro> {
+    let mut builder = config.pomodoro_builder(template)?.started_at(starts_at);
+
+    if let Some(duration) = duration {
+        builder = builder.duration(duration);
+    }
+    if let Some(description) = description {
+        builder = builder.description(description);
+    }
+    if let Some(tags) = tags {
+        builder = builder.tags(tags.split(','));
+    }
+
+    builder.build()
+}
+
+fn print_timer_checks(config: &Config, clock: &dyn Clock) -> Result<()> {
+    let now = clock.now();
+
+    match Status::load(&config.state_file_path)? {
+        Status::Inactive => println!("No timers active"),
+        Status::Active(pom) => print_expected_check(pom.timer(), now),
+        Status::ShortBreak(rest) | Status::LongBreak(rest) => {
+            print_expected_check(rest.timer(), now)
+        }
+    }
+
+    let checks = match scheduler::scheduled_checks() {
+        Ok(checks) => checks,
+        Err(e) => {
+            println!("{} {:#}", "Unable to list systemd timers:".yellow(), e);
+            return Ok(());
+        }
+    };
+
+    if checks.is_empty() {
+        println!("No systemd timers are scheduled");
+    }
+
+    for check in checks {
+        match check.next {
+            Some(next) => println!(
+                "Systemd timer {} fires at {}",
+                check.unit.cyan(),
+                next.format("%R:%S")
+            ),
+            None => println!("Systemd timer {} has already fired", check.unit.cyan()),
+        }
+    }
+
+    Ok(())
+}
+
+fn print_expected_check(timer: &Timer, now: DateTime<Local>) {
+    if timer.done(now) {
+        println!(
+            "Timer ran out at {} ({})",
+            timer.ends_at().format("%R:%S"),
+            overtime_note(timer, now)
+        );
+    } else {
+        println!(
+            "Timer should be checked at {} (in {})",
+            timer.ends_at().format("%R:%S"),
+            to_kitchen(&timer.remaining(now))
+        );
+    }
+}
+
+/// Format a log message as a line of JSON
+///
+/// Colors are stripped from the message, since they are only useful to people.
+fn json_log_line(
+    timestamp: DateTime<Utc>,
+    level: log::Level,
+    target: &str,
+    message: &str,
+) -> String {
+    let ansi_escape = Regex::new("\x1b\\[[0-9;]*m").unwrap();
+
+    serde_json::json!({
+        "timestamp": timestamp.to_rfc3339_opts(SecondsFormat::Millis, true),
+        "level": level.as_str(),
+        "target": target,
+        "message": ansi_escape.replace_all(message, ""),
+    })
+    .to_string()
+}
+
+/// Decide whether output should be colored
+///
+/// `--color always` and `--color never` decide by themselves. Otherwise,
+/// colors are left out for `--quiet`, when `NO_COLOR` is set to anything,
+/// and when stdout is not a terminal, like when it's piped to a file.
+fn use_color(
+    choice: ColorChoice,
+    quiet: bool,
+    no_color: Option<OsString>,
+    is_terminal: bool,
+) -> bool {
+    match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            let no_color = no_color.is_some_and(|value| !value.is_empty());
+
+            !quiet && !no_color && is_terminal
+        }
+    }
+}
+
+/// How `tomate status` should show the current status
+enum StatusOutput {
+    /// A human-readable description with a progress bar
+    Full,
+    /// A line in a user-defined format
+    Format(String),
+    /// A stable, tab-separated line for scripts
+    Porcelain,
+    /// A short line for shell prompts
+    Compact,
+}
+
+fn print_status(
+    config: &Config,
+    clock: &dyn Clock,
+    output: StatusOutput,
+    quiet: bool,
+) -> Result<()> {
+    let status = Status::load(&config.state_file_path)?;
+    let now = clock.now();
+
+    if let StatusOutput::Porcelain = output {
+        println!("{}", porcelain_line(&status, now));
+
+        return Ok(());
+    }
+
+    if let StatusOutput::Compact = output {
+        if let Some(line) = compact_line(config, &status, now) {
+            println!("{}", line);
+        }
+
+        return Ok(());
+    }
+
+    if let StatusOutput::Format(format) = output {
+        let context = FormatContext {
+            config: Some(config),
+        };
+
+        match status {
+            Status::Active(pom) => {
+                println!("{}", format_pomodoro(&context, &pom, &format, now));
+            }
+            Status::ShortBreak(timer) => {
+                println!(
+                    "{}",
+                    format_timer(&context, &timer, Phase::ShortBreak, &format, now)
+                );
+            }
+            Status::LongBreak(timer) => {
+                println!(
+                    "{}",
+                    format_timer(&context, &timer, Phase::LongBreak, &format, now)
+                );
+            }
+            Status::Inactive => {
+                if let Some(line) = format_inactive(&format) {
+                    println!("{}", line);
+                }
             }
+        }
+
+        return Ok(());
+    }
 
+    match status {
+        Status::Active(pom) => {
             if let Some(desc) = pom.description() {
                 println!("Current Pomodoro: {}", desc.yellow());
             } else {
                 println!("Current Pomodoro");
             }
 
-            if pom.timer().done(Local::now()) {
-                println!("Status: {}", "Done".red().bold());
+            if pom.timer().paused_at().is_some() {
+                println!("Status: {} (you seem to be away)", "Paused".yellow().bold());
+            } else if pom.is_overdue(now) {
+                println!(
+                    "Status: {} ({})",
+                    "Done".red().bold(),
+                    overtime_note(pom.timer(), now)
+                );
             } else {
                 println!("Status: {}", "Active".magenta().bold());
             }
@@ -295,7 +1523,12 @@ fn print_status(config: &Config, format: Option<String>) -> Result<()> {
             }
             println!();
 
-            print_progress_bar(pom.timer());
+            print_progress_bar(pom.timer(), now);
+
+            if quiet {
+                return Ok(());
+            }
+
             println!();
             println!(
                 "{}",
@@ -308,15 +1541,53 @@ fn print_status(config: &Config, format: Option<String>) -> Result<()> {
         }
         Status::Inactive => {
             println!("No current Pomodoro");
+
+            let history = tomate::load_history(config)?;
+
+            if config.show_recent {
+                if let Some(note) = history.last().and_then(recent_note) {
+                    println!("{}", note);
+                }
+            }
+
+            if quiet {
+                return Ok(());
+            }
+
             println!();
+
+            if tomate::next_break(&history, config, clock.now()) == BreakKind::Long {
+                let poms = tomate::poms_toward_long_break(&history, config, clock.now());
+                println!(
+                    "You've done {} Pomodoros since your last long break, take a long break!",
+                    poms.to_string().yellow()
+                );
+                println!(
+                    "{}",
+                    "(use \"tomate break --long\" to take a long break)".dimmed()
+                );
+            }
+
             println!("{}", "(use \"tomate start\" to start a Pomodoro)".dimmed());
             println!("{}", "(use \"tomate break\" to take a break)".dimmed());
         }
-        Status::ShortBreak(timer) => {
-            println!("Taking a short break");
+        Status::ShortBreak(rest) => {
+            if let Some(desc) = rest.description() {
+                println!("Taking a short break: {}", desc.yellow());
+            } else {
+                println!("Taking a short break");
+            }
             println!();
 
-            print_progress_bar(&timer);
+            print_progress_bar(rest.timer(), now);
+            if rest.timer().done(now) {
+                println!("{}", overtime_note(rest.timer(), now).red());
+            }
+
+            if quiet {
+                return Ok(());
+            }
+
             println!();
 
             println!(
@@ -324,11 +1595,23 @@ fn print_status(config: &Config, format: Option<String>) -> Result<()> {
                 "(use \"tomate finish\" to finish this break)".dimmed()
             );
         }
-        Status::LongBreak(timer) => {
-            println!("Taking a long break");
+        Status::LongBreak(rest) => {
+            if let Some(desc) = rest.description() {
+                println!("Taking a long break: {}", desc.yellow());
+            } else {
+                println!("Taking a long break");
+            }
             println!();
 
-            print_progress_bar(&timer);
+            print_progress_bar(rest.timer(), now);
+            if rest.timer().done(now) {
+                println!("{}", overtime_note(rest.timer(), now).red());
+            }
+
+            if quiet {
+                return Ok(());
+            }
+
             println!();
 
             println!(
@@ -341,159 +1624,1101 @@ fn print_status(config: &Config, format: Option<String>) -> Result<()> {
     Ok(())
 }
 
-fn duration_from_human(input: &str) -> Result<TimeDelta> {
-    let re = Regex::new(r"^(?:([0-9])h)?(?:([0-9]+)m)?(?:([0-9]+)s)?$").unwrap();
-    let caps = re.captures(input)
-    .with_context(|| "Failed to parse duration string, format is <HOURS>h<MINUTES>m<SECONDS>s (each section is optional) example: 22m30s")?;
+/// Describe the most recent history entry if it is a Pomodoro, like "Last: wrote tests (25m)"
+///
+/// Returns `None` for breaks, since there's nothing left to suggest once a break is over.
+fn recent_note(entry: &HistoryEntry) -> Option<String> {
+    if entry.phase() != Phase::Pomodoro {
+        return None;
+    }
 
-    let hours: i64 = caps.get(1).map_or("0", |c| c.as_str()).parse()?;
-    let minutes: i64 = caps.get(2).map_or("0", |c| c.as_str()).parse()?;
-    let seconds: i64 = caps.get(3).map_or("0", |c| c.as_str()).parse()?;
+    Some(format!(
+        "Last: {} ({}) — take a break?",
+        entry.description().unwrap_or("Pomodoro").yellow(),
+        to_human(&entry.duration()).cyan()
+    ))
+}
 
-    let total_seconds = (hours * 3600) + (minutes * 60) + seconds;
+fn print_history(
+    config: &Config,
+    clock: &dyn Clock,
+    options: &HistoryOptions,
+    color: bool,
+) -> Result<()> {
+    if History::rotated_files(&config.history_file_path, config.history_rotation)?.is_empty() {
+        return Ok(());
+    }
 
-    Ok(TimeDelta::new(total_seconds, 0).expect("Expected duration to be nonzero."))
-}
+    let mut history = tomate::load_history(config)?;
 
-fn to_human(duration: &TimeDelta) -> String {
-    use std::fmt::Write;
+    if let Some(phase) = options.phase {
+        let phase = Phase::from(phase);
+        history.retain(|entry| entry.phase() == phase);
+    }
 
-    if duration.is_zero() {
-        return "0s".to_string();
+    if let Some(tag) = &options.tag {
+        history.retain(|entry| entry.has_tag(tag));
     }
 
-    let hours = duration.num_seconds() / 3600;
-    let minutes = (duration.num_seconds() / 60) - (hours * 60);
-    let seconds = duration.num_seconds() % 60;
+    if let Some(count) = options.count {
+        history.keep_last(count);
+    }
 
-    let mut acc = String::new();
+    if options.reverse {
+        history.reverse();
+    }
 
-    if hours > 0 {
-        write!(acc, "{}h", hours).unwrap();
+    if let Some(output) = &options.output {
+        let dir = match output.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        if !dir.is_dir() {
+            bail!(
+                "Cannot write to {}, directory {} does not exist",
+                output.display(),
+                dir.display()
+            );
+        }
+
+        return tomate::write_atomic_with(output, |writer| {
+            write_history(
+                &history,
+                clock.now(),
+                options,
+                &config.history_date_format,
+                writer,
+            )
+        });
     }
 
-    if minutes > 0 {
-        write!(acc, "{}m", minutes).unwrap();
+    if options.jsonl || !color {
+        return write_history(
+            &history,
+            clock.now(),
+            options,
+            &config.history_date_format,
+            &mut io::BufWriter::new(io::stdout().lock()),
+        );
     }
 
-    if seconds > 0 {
-        write!(acc, "{}s", seconds).unwrap();
+    let mut table = history_table(
+        history.entries(),
+        clock.now(),
+        options.relative,
+        &config.history_date_format,
+    );
+    if options.total {
+        table.add_row(total_row(history.entries()));
     }
+    table.print_tty(true)?;
 
-    acc
+    Ok(())
 }
 
-pub fn to_kitchen(duration: &TimeDelta) -> String {
-    let hours = duration.num_seconds() / 3600;
-    let minutes = (duration.num_seconds() / 60) - (hours * 60);
-    let seconds = duration.num_seconds() % 60;
+fn write_history<W>(
+    history: &History,
+    now: DateTime<Local>,
+    options: &HistoryOptions,
+    date_format: &str,
+    writer: &mut W,
+) -> Result<()>
+where
+    W: Write + ?Sized,
+{
+    if options.jsonl {
+        return history.write_jsonl(writer);
+    }
 
-    if hours > 0 {
-        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
-    } else {
-        format!("{:02}:{:02}", minutes, seconds)
+    let mut table = history_table(history.entries(), now, options.relative, date_format);
+    if options.total {
+        table.add_row(total_row(history.entries()));
+    }
+    table.print(writer)?;
+
+    Ok(())
+}
+
+/// Pause or resume the Pomodoro depending on whether the user is away
+///
+/// A resumed Pomodoro ends later than its scheduled check, so a new check is
+/// scheduled for its new end. Returns `true` if the Pomodoro is paused.
+fn check_idle(args: &Args, config: &Config, clock: &dyn Clock) -> Result<bool> {
+    match tomate::pause_when_idle(config, clock, &SessionIdle)? {
+        Some(IdlePause::Paused) => Ok(true),
+        Some(IdlePause::Resumed(_)) => {
+            if let Status::Active(pom) = Status::load(&config.state_file_path)? {
+                let remaining = pom.timer().remaining(clock.now());
+                scheduler::schedule_finish(args, config, remaining.num_seconds().max(1))?;
+            }
+            Ok(false)
+        }
+        None => Ok(false),
     }
 }
 
-fn format_pomodoro(pomodoro: &Pomodoro, f: &str, now: DateTime<Local>) -> String {
-    let output = f
-        .replace("%d", pomodoro.description().unwrap_or(""))
-        .replace(
-            "%t",
-            &pomodoro.tags().unwrap_or(&Vec::<String>::new()).join(","),
-        )
-        .replace("%r", &to_kitchen(&pomodoro.timer().remaining(now)))
-        .replace(
-            "%R",
-            &pomodoro.timer().remaining(now).num_seconds().to_string(),
-        )
-        .replace("%s", &pomodoro.timer().starts_at().to_rfc3339())
-        .replace("%S", &pomodoro.timer().starts_at().timestamp().to_string())
-        .replace("%e", &pomodoro.timer().ends_at().to_rfc3339())
-        .replace("%E", &pomodoro.timer().ends_at().timestamp().to_string());
+/// Pause the current timer if the computer was suspended since the last tick
+///
+/// Like after being idle, the paused timer ends later than its scheduled
+/// check, so a new check is scheduled for its new end.
+fn check_suspend(
+    args: &Args,
+    config: &Config,
+    clock: &dyn Clock,
+    interval: TimeDelta,
+) -> Result<()> {
+    if tomate::pause_after_suspend(config, clock, interval)?.is_some() {
+        if let Status::Active(pom) = Status::load(&config.state_file_path)? {
+            let remaining = pom.timer().remaining(clock.now());
+            scheduler::schedule_finish(args, config, remaining.num_seconds().max(1))?;
+        }
+    }
 
-    output
+    Ok(())
 }
 
-fn format_timer(timer: &Timer, f: &str, now: DateTime<Local>) -> String {
-    f.replace("%r", &to_kitchen(&timer.remaining(now)))
-        .replace("%R", &timer.remaining(now).num_seconds().to_string())
-        .replace("%s", &timer.starts_at().to_rfc3339())
-        .replace("%S", &timer.starts_at().timestamp().to_string())
-        .replace("%e", &timer.ends_at().to_rfc3339())
-        .replace("%E", &timer.ends_at().timestamp().to_string())
+fn print_finish_outcome(outcome: &FinishOutcome) {
+    match outcome {
+        FinishOutcome::Archived(entry) => {
+            println!("Finished a {} Pomodoro", to_human(&entry.duration()).cyan());
+        }
+        FinishOutcome::BreakEnded(phase) => {
+            println!("Your {} is over", phase);
+        }
+        FinishOutcome::NothingActive => {
+            println!("No timers active");
+        }
+    }
 }
 
-fn print_progress_bar(pom: &Timer) {
-    let now = Local::now();
-    let elapsed_ratio =
-        pom.elapsed(now).num_milliseconds() as f32 / pom.duration().num_milliseconds() as f32;
+fn print_totals(totals: &Totals, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string(&totals_json(totals))?);
+        return Ok(());
+    }
 
-    let bar_width = 40.0;
+    println!("Pomodoros: {}", totals.pomodoros.to_string().yellow());
+    println!("Focus time: {}", to_human(&totals.focus).cyan());
+    println!("Break time: {}", to_human(&totals.breaks).cyan());
 
-    let filled_count = (bar_width * elapsed_ratio).round() as usize;
-    let unfilled_count = (bar_width * (1.0 - elapsed_ratio)).round() as usize;
+    Ok(())
+}
 
-    let filled_bar = vec!["█"; filled_count].join("");
-    let unfilled_bar = vec!["░"; unfilled_count].join("");
+fn print_streaks(streaks: &Streaks, today: NaiveDate) {
+    let Some(last_day) = streaks.last_day else {
+        println!("No Pomodoros yet. Start one with {}", "tomate start".cyan());
+        return;
+    };
 
-    println!(
-        "{} {}{} {}",
-        to_kitchen(&pom.elapsed(now)),
-        filled_bar,
-        unfilled_bar,
-        to_kitchen(&pom.remaining(now)),
-    );
+    println!("Current streak: {}", days(streaks.current).yellow());
+    println!("Longest streak: {}", days(streaks.longest).yellow());
+
+    let since = (today - last_day).num_days();
+    match since {
+        0 => println!("Last Pomodoro: {}", "today".cyan()),
+        1 => println!("Last Pomodoro: {}", "yesterday".cyan()),
+        _ => println!("Last Pomodoro: {} ago", days(since as usize).cyan()),
+    }
 }
 
-#[cfg(test)]
-mod test {
-    use chrono::{prelude::*, TimeDelta};
+/// What a column of the `tomate today` timeline shows
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum Slot {
+    /// No timer was running
+    Empty,
+    /// A break was running
+    Break,
+    /// The Pomodoro at this index in the entries was running
+    Pomodoro(usize),
+}
+
+/// Map entries onto `width` columns spanning `start` to `end`
+///
+/// Each entry fills every column its time overlaps, and at least one, so
+/// short entries don't disappear. Pomodoros are drawn over breaks.
+fn layout_timeline(
+    entries: &[HistoryEntry],
+    start: DateTime<Local>,
+    end: DateTime<Local>,
+    width: usize,
+) -> Vec<Slot> {
+    let mut slots = vec![Slot::Empty; width];
+    let span = (end - start).num_seconds();
+    if width == 0 || span <= 0 {
+        return slots;
+    }
 
-    use crate::{format_pomodoro, Pomodoro};
+    let column = |at: DateTime<Local>| {
+        let offset = (at - start).num_seconds().clamp(0, span);
+        offset as f64 / span as f64 * width as f64
+    };
 
-    #[test]
-    fn pomodoro_format_wallclock() {
-        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
-        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+    let mut ordered: Vec<(usize, &HistoryEntry)> = entries.iter().enumerate().collect();
+    ordered.sort_by_key(|(_, entry)| entry.phase() == Phase::Pomodoro);
 
-        let pom = Pomodoro::new(dt, dur);
+    for (index, entry) in ordered {
+        let entry_end = entry.started_at() + entry.duration();
+        if entry_end < start || entry.started_at() > end {
+            continue;
+        }
 
-        let actual_format = format_pomodoro(&pom, "%r", dt);
+        let first = (column(entry.started_at()) as usize).min(width - 1);
+        let last = (column(entry_end).ceil() as usize).clamp(first + 1, width);
+        let slot = match entry.phase() {
+            Phase::Pomodoro => Slot::Pomodoro(index),
+            Phase::ShortBreak | Phase::LongBreak => Slot::Break,
+        };
 
-        assert_eq!(actual_format, "25:00");
+        slots[first..last].fill(slot);
     }
 
-    #[test]
-    fn pomodoro_format_description() {
-        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
-        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+    slots
+}
 
-        let mut pom = Pomodoro::new(dt, dur);
-        pom.set_description("hello :)");
+/// Pick a color for a tag, the same one every time
+fn tag_color(tag: &str) -> colored::Color {
+    use colored::Color;
+
+    const PALETTE: [Color; 6] = [
+        Color::Red,
+        Color::Green,
+        Color::Yellow,
+        Color::Blue,
+        Color::Magenta,
+        Color::Cyan,
+    ];
+
+    let sum: usize = tag.bytes().map(usize::from).sum();
+    PALETTE[sum % PALETTE.len()]
+}
 
-        let actual_format = format_pomodoro(&pom, "%d", dt);
+fn print_timeline(entries: &[HistoryEntry], now: DateTime<Local>, width: usize, color: bool) {
+    let Some(start) = entries
+        .iter()
+        .filter(|entry| entry.phase() == Phase::Pomodoro)
+        .map(HistoryEntry::started_at)
+        .min()
+    else {
+        println!(
+            "No Pomodoros yet today. Start one with {}",
+            "tomate start".cyan()
+        );
+        return;
+    };
 
-        assert_eq!(actual_format, "hello :)");
+    let slots = layout_timeline(entries, start, now, width);
+    let line: String = slots
+        .iter()
+        .map(|slot| match slot {
+            Slot::Empty => " ".normal(),
+            Slot::Break => "░".normal(),
+            Slot::Pomodoro(index) => {
+                let tag = entries[*index].tags().and_then(|tags| tags.first());
+                match tag {
+                    Some(tag) if color => "█".color(tag_color(tag)),
+                    _ => "█".normal(),
+                }
+            }
+        })
+        .map(|block| block.to_string())
+        .collect();
+    println!("{}", line);
+
+    let start_label = start.format("%H:%M").to_string();
+    let end_label = now.format("%H:%M").to_string();
+    let padding = width.saturating_sub(start_label.len() + end_label.len());
+    println!("{}{}{}", start_label, " ".repeat(padding), end_label);
+
+    if color {
+        let tags: BTreeSet<&String> = entries.iter().filter_map(|e| e.tags()?.first()).collect();
+        if !tags.is_empty() {
+            println!();
+            for tag in tags {
+                println!("{} {}", "█".color(tag_color(tag)), tag);
+            }
+        }
     }
+}
 
-    #[test]
-    fn pomodoro_format_remaining() {
-        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
-        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+fn days(count: usize) -> String {
+    if count == 1 {
+        "1 day".to_string()
+    } else {
+        format!("{} days", count)
+    }
+}
 
-        let pom = Pomodoro::new(dt, dur);
+fn print_week_comparison(history: &History, now: DateTime<Local>, json: bool) -> Result<()> {
+    let last_week_now = now - TimeDelta::weeks(1);
+    let this_week = history.week_totals(now.iso_week(), now);
+    let last_week = history.week_totals(last_week_now.iso_week(), last_week_now);
+    let change = percent_change(last_week.focus, this_week.focus);
+
+    if json {
+        let value = serde_json::json!({
+            "this_week": totals_json(&this_week),
+            "last_week": totals_json(&last_week),
+            "focus_change_percent": change,
+        });
+        println!("{}", serde_json::to_string(&value)?);
+        return Ok(());
+    }
 
-        let actual_format = format_pomodoro(&pom, "%R", dt);
+    println!(
+        "This week: {} ({} Pomodoros)",
+        to_human(&this_week.focus).cyan(),
+        this_week.pomodoros
+    );
+    println!(
+        "Last week: {} ({} Pomodoros)",
+        to_human(&last_week.focus).cyan(),
+        last_week.pomodoros
+    );
 
-        assert_eq!(actual_format, "1500");
+    match change {
+        Some(change) if change >= 0 => println!("Change: {}", format!("+{}%", change).green()),
+        Some(change) => println!("Change: {}", format!("{}%", change).red()),
+        None => println!("Change: -"),
     }
 
-    #[test]
-    fn pomodoro_format_start_iso() {
-        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
-        let dur = TimeDelta::new(25 * 60, 0).unwrap();
-
+    Ok(())
+}
+
+fn totals_json(totals: &Totals) -> serde_json::Value {
+    serde_json::json!({
+        "pomodoros": totals.pomodoros,
+        "focus_seconds": totals.focus.num_seconds(),
+        "break_seconds": totals.breaks.num_seconds(),
+    })
+}
+
+/// Percent change from `before` to `after`, or `None` if `before` is zero
+fn percent_change(before: TimeDelta, after: TimeDelta) -> Option<i64> {
+    let before = before.num_seconds();
+    if before == 0 {
+        return None;
+    }
+
+    let change = (after.num_seconds() - before) as f64 / before as f64 * 100.0;
+
+    Some(change.round() as i64)
+}
+
+fn print_hooks(config: &Config, color: bool) -> Result<()> {
+    let mut table = Table::new();
+
+    table.set_titles(Row::new(vec![
+        Cell::new("Hook").with_style(Attr::Underline(true)),
+        Cell::new("Source").with_style(Attr::Underline(true)),
+        Cell::new("Runs").with_style(Attr::Underline(true)),
+        Cell::new("Exists").with_style(Attr::Underline(true)),
+        Cell::new("Executable").with_style(Attr::Underline(true)),
+    ]));
+
+    for hook in Hook::all() {
+        // A command in the config takes the place of the file in the hooks directory
+        let (source, runs, exists, executable) = match config.hooks.get(&hook) {
+            Some(command_line) => ("config", command_line.clone(), "-", "-"),
+            None => {
+                let path = hook.path(&config.hooks_directory);
+                let exists = if path.exists() { "yes" } else { "no" };
+                let executable = if tomate::doctor::is_executable(&path) {
+                    "yes"
+                } else {
+                    "no"
+                };
+                ("file", path.display().to_string(), exists, executable)
+            }
+        };
+
+        table.add_row(Row::new(vec![
+            Cell::new(hook.file_name()),
+            Cell::new(source),
+            Cell::new(&runs).with_style(Attr::ForegroundColor(color::CYAN)),
+            Cell::new(exists),
+            Cell::new(executable),
+        ]));
+    }
+    table.set_format(*format::consts::FORMAT_CLEAN);
+
+    if color {
+        table.print_tty(true)?;
+    } else {
+        table.print(&mut io::stdout())?;
+    }
+
+    Ok(())
+}
+
+/// Render history entries as a table
+///
+/// Start dates are shown relative to `now` if `relative` is true,
+/// or else formatted with the chrono format string `date_format`.
+fn history_table(
+    entries: &[HistoryEntry],
+    now: DateTime<Local>,
+    relative: bool,
+    date_format: &str,
+) -> Table {
+    let mut table = Table::new();
+
+    table.set_titles(Row::new(vec![
+        Cell::new("Date Started").with_style(Attr::Underline(true)),
+        Cell::new("Phase").with_style(Attr::Underline(true)),
+        Cell::new("Duration").with_style(Attr::Underline(true)),
+        Cell::new("Tags").with_style(Attr::Underline(true)),
+        Cell::new("Description").with_style(Attr::Underline(true)),
+    ]));
+
+    for entry in entries {
+        let date = if relative {
+            tomate::humanize_ago(now - entry.started_at())
+        } else {
+            entry.started_at().format(date_format).to_string()
+        };
+        let dur = to_human(&entry.duration());
+        let tags = entry.tags().unwrap_or(&vec!["-".to_string()]).join(",");
+        let desc = entry.description().unwrap_or("-");
+
+        table.add_row(Row::new(vec![
+            Cell::new(&date).with_style(Attr::ForegroundColor(color::BLUE)),
+            Cell::new(&entry.phase().to_string()),
+            Cell::new(&dur)
+                .style_spec("r")
+                .with_style(Attr::ForegroundColor(color::CYAN)),
+            Cell::new(&tags),
+            Cell::new(desc),
+        ]));
+    }
+    table.set_format(*format::consts::FORMAT_CLEAN);
+
+    table
+}
+
+/// A footer row for the history table, counting the entries and adding up the focus time
+fn total_row(entries: &[HistoryEntry]) -> Row {
+    let totals: Totals = entries.iter().collect();
+    let count = match entries.len() {
+        1 => "1 entry".to_string(),
+        n => format!("{} entries", n),
+    };
+
+    Row::new(vec![
+        Cell::new("Total").with_style(Attr::Bold),
+        Cell::new(&count),
+        Cell::new(&to_human(&totals.focus))
+            .style_spec("r")
+            .with_style(Attr::Bold),
+        Cell::new(""),
+        Cell::new(""),
+    ])
+}
+
+fn datetime_from_rfc3339(input: &str) -> Result<DateTime<Local>> {
+    let dt = DateTime::parse_from_rfc3339(input).with_context(|| {
+        "Failed to parse timestamp, expected RFC 3339 format like 2024-03-27T12:00:00-06:00"
+    })?;
+
+    Ok(dt.with_timezone(&Local))
+}
+
+/// A time given on the command line, either exactly or relative to now
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum StartTime {
+    At(DateTime<Local>),
+    Ago(TimeDelta),
+}
+
+impl StartTime {
+    /// Get the time this refers to, which can't be after `now`
+    fn resolve(self, now: DateTime<Local>) -> Result<DateTime<Local>> {
+        let time = match self {
+            Self::At(time) => time,
+            Self::Ago(delta) => now - delta,
+        };
+
+        if time > now {
+            bail!(
+                "Cannot start a Pomodoro in the future, {} is after the current time",
+                time.to_rfc3339()
+            );
+        }
+
+        Ok(time)
+    }
+}
+
+/// A time given on the command line for something to end, either exactly or as a time of day
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EndTime {
+    At(DateTime<Local>),
+    TimeOfDay(NaiveTime),
+}
+
+impl EndTime {
+    /// Get the time this refers to, which has to be after `now`
+    ///
+    /// A time of day is the next time the clock shows it after `now`.
+    fn resolve(self, now: DateTime<Local>) -> Result<DateTime<Local>> {
+        let time = match self {
+            Self::At(time) => time,
+            Self::TimeOfDay(time) => {
+                let mut date = now.date_naive();
+                loop {
+                    let candidate = date
+                        .and_time(time)
+                        .and_local_timezone(Local)
+                        .earliest()
+                        .filter(|candidate| *candidate > now);
+                    if let Some(candidate) = candidate {
+                        break candidate;
+                    }
+                    date = date
+                        .succ_opt()
+                        .with_context(|| "End time is out of range")?;
+                }
+            }
+        };
+
+        if time <= now {
+            bail!(
+                "Cannot end a Pomodoro in the past, {} is before the current time",
+                time.to_rfc3339()
+            );
+        }
+
+        Ok(time)
+    }
+}
+
+fn datetime_from_date_arg(input: &str) -> Result<DateTime<Local>> {
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return date
+            .and_time(NaiveTime::MIN)
+            .and_local_timezone(Local)
+            .earliest()
+            .with_context(|| format!("{} has no local midnight", date));
+    }
+
+    datetime_from_rfc3339(input)
+        .with_context(|| "Expected a date like 2024-01-01 or an RFC 3339 timestamp")
+}
+
+fn end_time_from_arg(input: &str) -> Result<EndTime> {
+    if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
+        return Ok(EndTime::TimeOfDay(time));
+    }
+
+    let time = DateTime::parse_from_rfc3339(input).with_context(|| {
+        "Failed to parse end time, expected a time of day like 15:00 or an RFC 3339 timestamp like 2024-03-27T15:00:00-06:00"
+    })?;
+
+    Ok(EndTime::At(time.with_timezone(&Local)))
+}
+
+fn start_time_from_arg(input: &str) -> Result<StartTime> {
+    match input.strip_prefix('-') {
+        Some(ago) => Ok(StartTime::Ago(duration_from_human(ago)?)),
+        None => Ok(StartTime::At(datetime_from_rfc3339(input)?)),
+    }
+}
+
+/// Parse a duration like `25m`, `1h30m`, `22m30s`, or `1.5h`
+///
+/// The grammar is `[<HOURS>h][<MINUTES>m][<SECONDS>s][<MILLISECONDS>ms]`, with the parts in that
+/// order and at least one of them given. Each number can have a decimal part.
+/// A bare number with no unit, like `25`, is a number of minutes.
+/// The result is rounded to the nearest second.
+fn duration_from_human(input: &str) -> Result<TimeDelta> {
+    let duration = duration::human::parse(input).map_err(anyhow::Error::msg)?;
+    let seconds = (duration.num_milliseconds() as f64 / 1000.0).round() as i64;
+
+    Ok(TimeDelta::seconds(seconds))
+}
+
+/// Describe a duration like `1h30m`, leaving out fractions of a second
+fn to_human(duration: &TimeDelta) -> String {
+    duration::human::format(&TimeDelta::seconds(duration.num_seconds()))
+}
+
+pub fn to_kitchen(duration: &TimeDelta) -> String {
+    let hours = duration.num_seconds() / 3600;
+    let minutes = (duration.num_seconds() / 60) - (hours * 60);
+    let seconds = duration.num_seconds() % 60;
+
+    if hours > 0 {
+        format!("{:02}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{:02}:{:02}", minutes, seconds)
+    }
+}
+
+/// Config values that status format tokens depend on, like the icons `%I` prints
+///
+/// The default context has no config, so it uses the default icons.
+#[derive(Clone, Copy, Debug, Default)]
+struct FormatContext<'a> {
+    config: Option<&'a Config>,
+}
+
+impl FormatContext<'_> {
+    /// Get the icon `%I` prints for a kind of timer
+    fn icon_for(&self, phase: Phase) -> &str {
+        match self.config {
+            Some(config) => config.icon_for(phase),
+            None => phase.default_icon(),
+        }
+    }
+}
+
+fn format_pomodoro(
+    context: &FormatContext,
+    pomodoro: &Pomodoro,
+    f: &str,
+    now: DateTime<Local>,
+) -> String {
+    expand_format(f, |token| match token {
+        'I' => Some(context.icon_for(Phase::Pomodoro).to_string()),
+        'P' => Some(phase_token(Some(Phase::Pomodoro)).to_string()),
+        'D' => Some(done_token(pomodoro.is_overdue(now)).to_string()),
+        'd' => Some(pomodoro.description().unwrap_or("").to_string()),
+        'i' => Some(pomodoro.id().map(|id| id.to_string()).unwrap_or_default()),
+        't' => Some(
+            pomodoro
+                .tags()
+                .map(|tags| tags.join(","))
+                .unwrap_or_default(),
+        ),
+        _ => timer_token(pomodoro.timer(), token, now),
+    })
+}
+
+/// How the `%f` and `%F` format tokens show a time, like 3:25pm
+const CLOCK_FORMAT: &str = "%-I:%M%P";
+
+/// Longest description `status --compact` shows before cutting it off
+const COMPACT_DESCRIPTION_LENGTH: usize = 20;
+
+/// Describe a status as a short line for `status --compact`, or `None` if there is no timer
+fn compact_line(config: &Config, status: &Status, now: DateTime<Local>) -> Option<String> {
+    let (phase, timer) = match status {
+        Status::Inactive => return None,
+        Status::Active(pom) => (Phase::Pomodoro, pom.timer()),
+        Status::ShortBreak(rest) => (Phase::ShortBreak, rest.timer()),
+        Status::LongBreak(rest) => (Phase::LongBreak, rest.timer()),
+    };
+
+    let mut parts = vec![config.icon_for(phase).to_string()];
+
+    if let Some(description) = status.description() {
+        let description = description.lines().next().unwrap_or_default().trim();
+        if description.chars().count() > COMPACT_DESCRIPTION_LENGTH {
+            let cut: String = description
+                .chars()
+                .take(COMPACT_DESCRIPTION_LENGTH - 1)
+                .collect();
+            parts.push(format!("{}…", cut.trim_end()));
+        } else if !description.is_empty() {
+            parts.push(description.to_string());
+        }
+    }
+
+    parts.push(to_kitchen(&timer.remaining(now)));
+
+    Some(parts.join(" "))
+}
+
+/// Describe a status as one line of tab-separated fields for `status --porcelain`
+fn porcelain_line(status: &Status, now: DateTime<Local>) -> String {
+    let (phase, timer) = match status {
+        Status::Inactive => return "inactive".to_string(),
+        Status::Active(pom) => ("pomodoro", pom.timer()),
+        Status::ShortBreak(rest) => ("short_break", rest.timer()),
+        Status::LongBreak(rest) => ("long_break", rest.timer()),
+    };
+    let pom = match status {
+        Status::Active(pom) => Some(pom),
+        _ => None,
+    };
+
+    let description = status
+        .description()
+        .unwrap_or_default()
+        .replace(['\t', '\n', '\r'], " ");
+    let tags = pom
+        .and_then(Pomodoro::tags)
+        .map(|tags| tags.join(","))
+        .unwrap_or_default();
+
+    format!(
+        "{}\t{}\t{}\t{}\t{}",
+        phase,
+        timer.remaining(now).num_seconds(),
+        timer.duration().num_seconds(),
+        description,
+        tags
+    )
+}
+
+/// Format a break, which is described by `phase`
+fn format_timer(
+    context: &FormatContext,
+    rest: &Break,
+    phase: Phase,
+    f: &str,
+    now: DateTime<Local>,
+) -> String {
+    debug_assert_ne!(phase, Phase::Pomodoro, "use format_pomodoro for Pomodoros");
+
+    expand_format(f, |token| match token {
+        'I' => Some(context.icon_for(phase).to_string()),
+        'P' => Some(phase_token(Some(phase)).to_string()),
+        'd' => Some(rest.description().unwrap_or("").to_string()),
+        _ => timer_token(rest.timer(), token, now),
+    })
+}
+
+/// The `%D` token for whether a timer has run out
+fn done_token(done: bool) -> &'static str {
+    if done {
+        "done"
+    } else {
+        "active"
+    }
+}
+
+/// Format the status when no timer is active, if the format uses `%P`
+fn format_inactive(f: &str) -> Option<String> {
+    let mut uses_phase = false;
+    let line = expand_format(f, |token| match token {
+        'P' => {
+            uses_phase = true;
+            Some(phase_token(None).to_string())
+        }
+        _ => Some(String::new()),
+    });
+
+    uses_phase.then_some(line)
+}
+
+/// Name a kind of timer for the `%P` format token
+fn phase_token(phase: Option<Phase>) -> &'static str {
+    match phase {
+        None => "inactive",
+        Some(Phase::Pomodoro) => "pomodoro",
+        Some(Phase::ShortBreak) => "short-break",
+        Some(Phase::LongBreak) => "long-break",
+    }
+}
+
+fn timer_token(timer: &Timer, token: char, now: DateTime<Local>) -> Option<String> {
+    let value = match token {
+        'r' => to_kitchen(&timer.remaining(now)),
+        'R' => timer.remaining(now).num_seconds().to_string(),
+        'l' => to_kitchen(&timer.elapsed(now)),
+        'L' => timer.elapsed(now).num_seconds().to_string(),
+        'p' => timer.percent(now).to_string(),
+        's' => timer.starts_at().to_rfc3339(),
+        'S' => timer.starts_at().timestamp().to_string(),
+        'e' => timer.ends_at().to_rfc3339(),
+        'E' => timer.ends_at().timestamp().to_string(),
+        'f' => timer.ends_at().format(CLOCK_FORMAT).to_string(),
+        'F' => timer.starts_at().format(CLOCK_FORMAT).to_string(),
+        'o' => to_kitchen(&timer.overtime(now)),
+        'D' => done_token(timer.done(now)).to_string(),
+        _ => return None,
+    };
+
+    Some(value)
+}
+
+/// Expand the `%` tokens in a format string in a single pass
+///
+/// `expand` returns the text for a token character, or `None` if the token
+/// is unknown, in which case it is copied to the output as-is. `%%` is a
+/// literal `%`. Expanded text is never scanned for more tokens.
+fn expand_format<F>(f: &str, mut expand: F) -> String
+where
+    F: FnMut(char) -> Option<String>,
+{
+    let mut output = String::with_capacity(f.len());
+    let mut chars = f.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('%') => output.push('%'),
+            Some(token) => match expand(token) {
+                Some(value) => output.push_str(&value),
+                None => {
+                    output.push('%');
+                    output.push(token);
+                }
+            },
+            None => output.push('%'),
+        }
+    }
+
+    output
+}
+
+fn overtime_note(timer: &Timer, now: DateTime<Local>) -> String {
+    format!("+{} over", to_human(&timer.overtime(now)))
+}
+
+/// Pick the color of the progress bar from the fraction of the timer that is left
+///
+/// Green while more than half is left, yellow down to a fifth, and red after that.
+fn progress_color(remaining_ratio: f64) -> colored::Color {
+    if remaining_ratio > 0.5 {
+        colored::Color::Green
+    } else if remaining_ratio >= 0.2 {
+        colored::Color::Yellow
+    } else {
+        colored::Color::Red
+    }
+}
+
+/// Show a countdown until the Pomodoro with `id` is done, then finish it
+///
+/// Returns early if the Pomodoro is finished or replaced some other way,
+/// like by running "tomate finish" in another terminal. Ctrl-C does what
+/// `on_interrupt` says before returning.
+fn wait_for_pomodoro(
+    config: &Config,
+    clock: &dyn Clock,
+    id: Option<uuid::Uuid>,
+    on_interrupt: Interrupt,
+    render: bool,
+) -> Result<()> {
+    if config.dry_run {
+        info!("Dry run: would wait for the Pomodoro to finish");
+        return Ok(());
+    }
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .with_context(|| "Failed to set up signal handler")?;
+    }
+
+    wait_until_done(config, clock, id, on_interrupt, &interrupted, render)
+}
+
+/// Poll the Pomodoro with `id` until it's done or `interrupted` is set
+///
+/// This is the loop behind [`wait_for_pomodoro`], which sets `interrupted`
+/// when the user presses Ctrl-C.
+fn wait_until_done(
+    config: &Config,
+    clock: &dyn Clock,
+    id: Option<uuid::Uuid>,
+    on_interrupt: Interrupt,
+    interrupted: &AtomicBool,
+    render: bool,
+) -> Result<()> {
+    loop {
+        let now = clock.now();
+        let timer = match Status::load(&config.state_file_path)? {
+            Status::Active(pom) if pom.id() == id => pom.timer().clone(),
+            _ => break,
+        };
+
+        if interrupted.load(Ordering::SeqCst) {
+            match on_interrupt {
+                Interrupt::Finish => {
+                    tomate::finish(config, clock)?;
+                }
+                Interrupt::Clear => tomate::clear(config)?,
+            }
+            break;
+        }
+
+        if timer.done(now) {
+            tomate::finish_at(config, clock, FinishTime::TimerEnd)?;
+            break;
+        }
+
+        if render {
+            print!("\r{}\x1b[K", progress_line(&timer, now));
+            io::stdout().flush()?;
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(250));
+    }
+
+    if render {
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Redraw the full status every second, until the timer runs out or the user presses Ctrl-C
+fn watch_status(config: &Config, clock: &dyn Clock, quiet: bool) -> Result<()> {
+    use crossterm::{cursor, execute, terminal};
+
+    let interrupted = Arc::new(AtomicBool::new(false));
+    {
+        let interrupted = interrupted.clone();
+        ctrlc::set_handler(move || interrupted.store(true, Ordering::SeqCst))
+            .with_context(|| "Failed to set up signal handler")?;
+    }
+
+    let mut stdout = io::stdout();
+    execute!(stdout, cursor::Hide)?;
+
+    let result = (|| -> Result<()> {
+        loop {
+            execute!(
+                stdout,
+                cursor::MoveTo(0, 0),
+                terminal::Clear(terminal::ClearType::All)
+            )?;
+            print_status(config, clock, StatusOutput::Full, quiet)?;
+            stdout.flush()?;
+
+            let done = match Status::load(&config.state_file_path)? {
+                Status::Inactive => true,
+                Status::Active(pom) => pom.timer().done(clock.now()),
+                Status::ShortBreak(rest) | Status::LongBreak(rest) => {
+                    rest.timer().done(clock.now())
+                }
+            };
+
+            if done {
+                return Ok(());
+            }
+
+            // Sleep in short steps so Ctrl-C doesn't wait for the next redraw
+            for _ in 0..4 {
+                if interrupted.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+                std::thread::sleep(std::time::Duration::from_millis(250));
+            }
+        }
+    })();
+
+    execute!(stdout, cursor::Show)?;
+
+    result
+}
+
+fn print_progress_bar(pom: &Timer, now: DateTime<Local>) {
+    println!("{}", progress_line(pom, now));
+}
+
+/// Render a timer's elapsed time, progress bar, and remaining time on one line
+fn progress_line(pom: &Timer, now: DateTime<Local>) -> String {
+    let elapsed_ratio = pom.progress_ratio(now);
+
+    let bar_width = 40.0;
+
+    let filled_count = (bar_width * elapsed_ratio).round() as usize;
+    let unfilled_count = (bar_width * (1.0 - elapsed_ratio)).round() as usize;
+
+    let filled_bar = vec!["█"; filled_count]
+        .join("")
+        .color(progress_color(1.0 - elapsed_ratio));
+    let unfilled_bar = vec!["░"; unfilled_count].join("");
+
+    format!(
+        "{} {}{} {}",
+        to_kitchen(&pom.elapsed(now)),
+        filled_bar,
+        unfilled_bar,
+        to_kitchen(&pom.remaining(now)),
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use std::{path::Path, sync::atomic::AtomicBool};
+
+    use chrono::{prelude::*, TimeDelta};
+    use clap::{CommandFactory, Parser};
+
+    use colored::Colorize;
+
+    use tomate::{Break, Config, FinishOutcome, FixedClock, History, HistoryEntry, Phase, Status};
+
+    use crate::{
+        compact_line, duration_from_human, end_time_from_arg, format_inactive, history_table,
+        is_yes, json_log_line, layout_timeline, new_pomodoro, overtime_note,
+        parse_edited_description, percent_change, porcelain_line, progress_color, progress_line,
+        recent_note, start_break, start_pomodoro, start_time_from_arg, total_row, use_color,
+        wait_until_done, write_completions, write_man_page, write_schema, Args, ColorChoice,
+        Command, FormatContext, Interrupt, Pomodoro, SchemaFile, Slot, StartTime, Timer,
+    };
+
+    /// Config that keeps its state, history, and hooks in `dir`
+    fn temp_config(dir: &Path) -> Config {
+        Config {
+            state_file_path: dir.join("current.toml"),
+            history_file_path: dir.join("history.toml"),
+            hooks_directory: dir.join("hooks"),
+            ..Config::try_default().unwrap()
+        }
+    }
+
+    /// Format a Pomodoro with the default icons
+    fn format_pomodoro(pomodoro: &Pomodoro, f: &str, now: DateTime<Local>) -> String {
+        crate::format_pomodoro(&FormatContext::default(), pomodoro, f, now)
+    }
+
+    /// Format a break with the default icons
+    fn format_timer(rest: &Break, phase: Phase, f: &str, now: DateTime<Local>) -> String {
+        crate::format_timer(&FormatContext::default(), rest, phase, f, now)
+    }
+
+    #[test]
+    fn pomodoro_format_wallclock() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let actual_format = format_pomodoro(&pom, "%r", dt);
+
+        assert_eq!(actual_format, "25:00");
+    }
+
+    #[test]
+    fn pomodoro_format_description() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let mut pom = Pomodoro::new(dt, dur);
+        pom.set_description("hello :)");
+
+        let actual_format = format_pomodoro(&pom, "%d", dt);
+
+        assert_eq!(actual_format, "hello :)");
+    }
+
+    #[test]
+    fn pomodoro_format_remaining() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let actual_format = format_pomodoro(&pom, "%R", dt);
+
+        assert_eq!(actual_format, "1500");
+    }
+
+    #[test]
+    fn pomodoro_format_start_iso() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
         let pom = Pomodoro::new(dt, dur);
 
         let actual_format = format_pomodoro(&pom, "%s", dt);
@@ -527,6 +2752,18 @@ mod test {
         assert_eq!(actual_format, "a,b,c");
     }
 
+    #[test]
+    fn pomodoro_format_id() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let actual_format = format_pomodoro(&pom, "%i", dt);
+
+        assert_eq!(actual_format, pom.id().unwrap().to_string());
+    }
+
     #[test]
     fn pomodoro_format_eta() {
         let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
@@ -551,4 +2788,780 @@ mod test {
 
         assert_eq!(actual_format, "1711563900");
     }
+
+    #[test]
+    fn pomodoro_format_overtime() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let actual_format =
+            format_pomodoro(&pom, "%o", dt + TimeDelta::new(28 * 60 + 12, 0).unwrap());
+
+        assert_eq!(actual_format, "03:12");
+    }
+
+    #[test]
+    fn pomodoro_format_overtime_not_done() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let actual_format = format_pomodoro(&pom, "%o", dt);
+
+        assert_eq!(actual_format, "00:00");
+    }
+
+    #[test]
+    fn pomodoro_format_elapsed() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let actual_format =
+            format_pomodoro(&pom, "%l %L", dt + TimeDelta::new(10 * 60 + 5, 0).unwrap());
+
+        assert_eq!(actual_format, "10:05 605");
+    }
+
+    #[test]
+    fn pomodoro_format_percent() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        assert_eq!(format_pomodoro(&pom, "%p", dt), "0");
+        assert_eq!(
+            format_pomodoro(&pom, "%p", dt + TimeDelta::new(10 * 60, 0).unwrap()),
+            "40"
+        );
+        assert_eq!(
+            format_pomodoro(&pom, "%p", dt + TimeDelta::new(30 * 60, 0).unwrap()),
+            "100"
+        );
+    }
+
+    #[test]
+    fn format_icon_token() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        let rest = Break::new(dt, TimeDelta::minutes(5));
+
+        assert_eq!(format_pomodoro(&pom, "%I %r", dt), "🍅 25:00");
+        assert_eq!(
+            format_timer(&rest, Phase::ShortBreak, "%I %r", dt),
+            "☕ 05:00"
+        );
+
+        let mut config = Config::try_default().unwrap();
+        config
+            .phase_icons
+            .insert(Phase::ShortBreak, "SB".to_string());
+        let context = FormatContext {
+            config: Some(&config),
+        };
+        assert_eq!(
+            crate::format_timer(&context, &rest, Phase::ShortBreak, "%I %r", dt),
+            "SB 05:00"
+        );
+        assert_eq!(crate::format_pomodoro(&context, &pom, "%I", dt), "🍅");
+    }
+
+    #[test]
+    fn format_phase_token() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        let rest = Break::new(dt, TimeDelta::minutes(5));
+
+        assert_eq!(format_pomodoro(&pom, "%P", dt), "pomodoro");
+        assert_eq!(
+            format_timer(&rest, Phase::ShortBreak, "%P", dt),
+            "short-break"
+        );
+        assert_eq!(
+            format_timer(&rest, Phase::LongBreak, "%P", dt),
+            "long-break"
+        );
+        assert_eq!(format_inactive("%P %r").as_deref(), Some("inactive "));
+        assert_eq!(format_inactive("%r"), None);
+    }
+
+    #[test]
+    fn format_done_token() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        let rest = Break::new(dt, TimeDelta::minutes(5));
+        let pom_end = dt + TimeDelta::minutes(25);
+        let break_end = dt + TimeDelta::minutes(5);
+        let second = TimeDelta::seconds(1);
+
+        assert_eq!(format_pomodoro(&pom, "%D", pom_end - second), "active");
+        assert_eq!(format_pomodoro(&pom, "%D", pom_end + second), "done");
+        assert_eq!(
+            format_timer(&rest, Phase::ShortBreak, "%D", break_end - second),
+            "active"
+        );
+        assert_eq!(
+            format_timer(&rest, Phase::ShortBreak, "%D", break_end + second),
+            "done"
+        );
+    }
+
+    #[test]
+    fn format_clock_times() {
+        let dt = Local.with_ymd_and_hms(2024, 3, 27, 15, 0, 0).unwrap();
+        let pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        let rest = Break::new(dt - TimeDelta::hours(3), TimeDelta::minutes(5));
+
+        assert_eq!(format_pomodoro(&pom, "%F to %f", dt), "3:00pm to 3:25pm");
+        assert_eq!(
+            format_timer(&rest, Phase::ShortBreak, "ends at %f", dt),
+            "ends at 12:05pm"
+        );
+    }
+
+    #[test]
+    fn timer_format_percent_zero_duration() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+
+        let rest = Break::new(dt, TimeDelta::zero());
+
+        assert_eq!(format_timer(&rest, Phase::ShortBreak, "%p", dt), "100");
+    }
+
+    #[test]
+    fn pomodoro_format_percent_escape() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let actual_format = format_pomodoro(&pom, "%p%% %%r", dt + TimeDelta::minutes(5));
+
+        assert_eq!(actual_format, "20% %r");
+    }
+
+    #[test]
+    fn pomodoro_format_description_with_token() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let mut pom = Pomodoro::new(dt, dur);
+        pom.set_description("fix %s bug");
+
+        let actual_format = format_pomodoro(&pom, "%d (%R)", dt);
+
+        assert_eq!(actual_format, "fix %s bug (1500)");
+    }
+
+    #[test]
+    fn pomodoro_format_description_not_reexpanded() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let mut pom = Pomodoro::new(dt, dur);
+        pom.set_description("100% done %r");
+        pom.set_tags(vec!["%d".to_string()]);
+
+        let actual_format = format_pomodoro(&pom, "%d [%t] %r", dt);
+
+        assert_eq!(actual_format, "100% done %r [%d] 25:00");
+    }
+
+    #[test]
+    fn pomodoro_format_unknown_token() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let dur = TimeDelta::new(25 * 60, 0).unwrap();
+
+        let pom = Pomodoro::new(dt, dur);
+
+        let actual_format = format_pomodoro(&pom, "%q %r %", dt);
+
+        assert_eq!(actual_format, "%q 25:00 %");
+    }
+
+    #[test]
+    fn start_time_relative() {
+        let now: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+
+        let start = start_time_from_arg("-5m").unwrap();
+
+        assert_eq!(start, StartTime::Ago(TimeDelta::minutes(5)));
+        assert_eq!(start.resolve(now).unwrap(), now - TimeDelta::minutes(5));
+    }
+
+    #[test]
+    fn start_time_in_future_is_rejected() {
+        let now: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+
+        let start = start_time_from_arg("2024-03-27T12:05:00-06:00").unwrap();
+
+        assert!(start.resolve(now).is_err());
+    }
+
+    #[test]
+    fn end_time_of_day_is_next_occurrence() {
+        let now = Local.with_ymd_and_hms(2024, 3, 27, 12, 0, 0).unwrap();
+
+        let later_today = end_time_from_arg("15:00").unwrap().resolve(now).unwrap();
+        let tomorrow = end_time_from_arg("09:30").unwrap().resolve(now).unwrap();
+
+        assert_eq!(
+            later_today,
+            Local.with_ymd_and_hms(2024, 3, 27, 15, 0, 0).unwrap()
+        );
+        assert_eq!(
+            tomorrow,
+            Local.with_ymd_and_hms(2024, 3, 28, 9, 30, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn end_time_in_past_is_rejected() {
+        let now: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+
+        let end = end_time_from_arg("2024-03-27T11:00:00-06:00").unwrap();
+
+        assert!(end.resolve(now).is_err());
+        assert!(end_time_from_arg("3pm").is_err());
+    }
+
+    #[test]
+    fn json_log_line_strips_colors() {
+        let timestamp = "2024-03-27T18:00:00Z".parse().unwrap();
+
+        let line = json_log_line(
+            timestamp,
+            log::Level::Info,
+            "tomate",
+            &format!("Saving history to {}", "/tmp/history.toml".cyan()),
+        );
+
+        assert_eq!(
+            line,
+            r#"{"level":"INFO","message":"Saving history to /tmp/history.toml","target":"tomate","timestamp":"2024-03-27T18:00:00.000Z"}"#
+        );
+    }
+
+    #[test]
+    fn verify_cli() {
+        Args::command().debug_assert();
+    }
+
+    #[test]
+    fn bash_completions() {
+        let mut output = Vec::new();
+
+        write_completions(clap_complete::Shell::Bash, &mut output);
+
+        let script = String::from_utf8(output).unwrap();
+        assert!(script.contains("tomate"));
+        assert!(script.contains("history"));
+    }
+
+    #[test]
+    fn man_page() {
+        let mut output = Vec::new();
+        write_man_page(None, &mut output).unwrap();
+
+        let roff = String::from_utf8(output).unwrap();
+        assert!(roff.contains(".TH tomate"));
+        assert!(roff.contains("history"));
+
+        let mut output = Vec::new();
+        write_man_page(Some("history"), &mut output).unwrap();
+
+        let roff = String::from_utf8(output).unwrap();
+        assert!(roff.contains(".TH tomate-history"));
+        assert!(roff.contains("relative"));
+
+        assert!(write_man_page(Some("nope"), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn schemas() {
+        let mut output = Vec::new();
+        write_schema(SchemaFile::Config, &mut output).unwrap();
+
+        let schema: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        let properties = &schema["properties"];
+        assert!(properties["pomodoro_duration"].is_object());
+        assert!(properties["history_file_path"].is_object());
+        assert!(properties["unknown"].is_null());
+
+        let mut output = Vec::new();
+        write_schema(SchemaFile::State, &mut output).unwrap();
+
+        let schema = String::from_utf8(output).unwrap();
+        assert!(schema.contains("started_at"));
+        assert!(schema.contains("ShortBreak"));
+    }
+
+    #[test]
+    fn purge_confirmation_answers() {
+        assert!(is_yes("y\n"));
+        assert!(is_yes(" YES \n"));
+        assert!(!is_yes("\n"));
+        assert!(!is_yes("no\n"));
+        assert!(!is_yes("yep\n"));
+    }
+
+    #[test]
+    fn no_color_disables_escape_sequences() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let timer = Timer::new(dt, TimeDelta::minutes(5));
+
+        assert!(!use_color(ColorChoice::Auto, false, Some("1".into()), true));
+        colored::control::set_override(false);
+
+        let note = overtime_note(&timer, dt + TimeDelta::minutes(7))
+            .red()
+            .to_string();
+
+        assert_eq!(note, "+2m over");
+        assert!(!note.contains('\x1b'));
+    }
+
+    #[test]
+    fn progress_color_thresholds() {
+        use colored::Color;
+
+        assert_eq!(progress_color(1.0), Color::Green);
+        assert_eq!(progress_color(0.51), Color::Green);
+        assert_eq!(progress_color(0.5), Color::Yellow);
+        assert_eq!(progress_color(0.2), Color::Yellow);
+        assert_eq!(progress_color(0.19), Color::Red);
+        assert_eq!(progress_color(0.0), Color::Red);
+
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let instant = Timer::new(dt, TimeDelta::zero());
+        assert_eq!(progress_color(1.0 - instant.progress_ratio(dt)), Color::Red);
+    }
+
+    #[test]
+    fn recent_note_for_pomodoros_only() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.set_description("wrote tests");
+        pom.finish(dt + TimeDelta::minutes(25));
+
+        colored::control::set_override(false);
+
+        assert_eq!(
+            recent_note(&HistoryEntry::archive(&pom, None).unwrap()).unwrap(),
+            "Last: wrote tests (25m) — take a break?"
+        );
+
+        let rest = Break::new(dt, TimeDelta::minutes(5));
+        let entry = HistoryEntry::archive_break(Phase::ShortBreak, &rest, rest.timer().ends_at());
+        assert!(recent_note(&entry).is_none());
+    }
+
+    #[test]
+    fn porcelain_fields() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.set_description("tabs\tand\nnewlines");
+        pom.set_tags(vec!["work".to_string(), "code".to_string()]);
+        let now = dt + TimeDelta::minutes(10);
+
+        assert_eq!(
+            porcelain_line(&Status::Active(pom), now),
+            "pomodoro\t900\t1500\ttabs and newlines\twork,code"
+        );
+        assert_eq!(
+            porcelain_line(
+                &Status::LongBreak(Break::new(dt, TimeDelta::minutes(15))),
+                now
+            ),
+            "long_break\t300\t900\t\t"
+        );
+        assert_eq!(porcelain_line(&Status::Inactive, now), "inactive");
+    }
+
+    #[test]
+    fn compact_status() {
+        let config = Config::default();
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let now = dt + TimeDelta::minutes(12) + TimeDelta::seconds(26);
+
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        assert_eq!(
+            compact_line(&config, &Status::Active(pom.clone()), now).as_deref(),
+            Some("🍅 12:34")
+        );
+
+        pom.set_description("wrote-tests");
+        assert_eq!(
+            compact_line(&config, &Status::Active(pom.clone()), now).as_deref(),
+            Some("🍅 wrote-tests 12:34")
+        );
+
+        pom.set_description("Write the quarterly report\nwith charts");
+        assert_eq!(
+            compact_line(&config, &Status::Active(pom), now).as_deref(),
+            Some("🍅 Write the quarterly… 12:34")
+        );
+
+        let rest = Break::new(dt, TimeDelta::minutes(15));
+        assert_eq!(
+            compact_line(&config, &Status::LongBreak(rest), now).as_deref(),
+            Some("🛋 02:34")
+        );
+
+        assert_eq!(compact_line(&config, &Status::Inactive, now), None);
+    }
+
+    #[test]
+    fn timeline_layout() {
+        let start: DateTime<Local> = "2024-03-27T09:00:00-06:00".parse().unwrap();
+        let end = start + TimeDelta::hours(2);
+        let pom_at = |minutes: i64, length: i64| {
+            let started_at = start + TimeDelta::minutes(minutes);
+            let mut pom = Pomodoro::new(started_at, TimeDelta::minutes(length));
+            pom.finish(started_at + TimeDelta::minutes(length));
+            HistoryEntry::archive(&pom, None).unwrap()
+        };
+        let break_at = |minutes: i64, length: i64| {
+            let rest = Break::new(
+                start + TimeDelta::minutes(minutes),
+                TimeDelta::minutes(length),
+            );
+            HistoryEntry::archive_break(Phase::ShortBreak, &rest, rest.timer().ends_at())
+        };
+
+        // 12 columns of 10 minutes each
+        let entries = vec![pom_at(0, 25), break_at(25, 15), pom_at(60, 1)];
+        let slots = layout_timeline(&entries, start, end, 12);
+        let p = Slot::Pomodoro;
+        assert_eq!(
+            slots,
+            vec![
+                p(0),
+                p(0),
+                p(0),
+                Slot::Break,
+                Slot::Empty,
+                Slot::Empty,
+                p(2),
+                Slot::Empty,
+                Slot::Empty,
+                Slot::Empty,
+                Slot::Empty,
+                Slot::Empty,
+            ]
+        );
+
+        assert_eq!(layout_timeline(&[], start, end, 4), vec![Slot::Empty; 4]);
+        assert!(layout_timeline(&entries, start, end, 0).is_empty());
+        assert_eq!(
+            layout_timeline(&entries, start, start, 3),
+            vec![Slot::Empty; 3]
+        );
+    }
+
+    #[test]
+    fn edited_description_and_tags() {
+        let text = "Write the report\n\n# instructions\nwork\n  writing  \n";
+
+        let (description, tags) = parse_edited_description(text);
+
+        assert_eq!(description.as_deref(), Some("Write the report"));
+        assert_eq!(tags, ["work", "writing"]);
+        assert_eq!(
+            parse_edited_description(super::EDIT_INSTRUCTIONS),
+            (None, vec![])
+        );
+    }
+
+    #[test]
+    fn no_status_keeps_the_log_level() {
+        let args = Args::try_parse_from(["tomate", "start", "--no-status"]).unwrap();
+
+        assert!(matches!(
+            args.command,
+            Command::Start {
+                no_status: true,
+                ..
+            }
+        ));
+        assert!(!args.verbose.is_silent());
+
+        assert!(Args::try_parse_from(["tomate", "split", "--no-status"]).is_ok());
+        assert!(Args::try_parse_from(["tomate", "finish", "--no-status"]).is_err());
+    }
+
+    #[test]
+    fn duration_flags_are_shared() {
+        for command in ["start", "restart", "break"] {
+            let args = Args::try_parse_from(["tomate", command, "--seconds", "1500"]).unwrap();
+            let duration = match args.command {
+                Command::Start { duration, .. }
+                | Command::Restart { duration, .. }
+                | Command::Break { duration, .. } => duration,
+                _ => unreachable!(),
+            };
+            assert_eq!(
+                duration.get(),
+                Some(TimeDelta::minutes(25)),
+                "for {}",
+                command
+            );
+
+            assert!(
+                Args::try_parse_from(["tomate", command, "-d", "25m", "--seconds", "1500"])
+                    .is_err()
+            );
+        }
+
+        assert!(Args::try_parse_from(["tomate", "finish", "--seconds", "1500"]).is_err());
+        assert!(
+            Args::try_parse_from(["tomate", "finish", "--then-start", "--seconds", "1500"]).is_ok()
+        );
+    }
+
+    #[test]
+    fn watch_only_redraws_the_full_status() {
+        assert!(Args::try_parse_from(["tomate", "status", "--watch"]).is_ok());
+        assert!(Args::try_parse_from(["tomate", "status", "--watch", "--porcelain"]).is_err());
+        assert!(Args::try_parse_from(["tomate", "status", "-w", "-f", "%r"]).is_err());
+    }
+
+    #[test]
+    fn waiting_finishes_the_pomodoro_when_it_runs_out() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = temp_config(dir.path());
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        let id = pom.id();
+        tomate::start(&config, pom).unwrap();
+
+        let clock = FixedClock(dt + TimeDelta::minutes(30));
+        let interrupted = AtomicBool::new(false);
+        wait_until_done(&config, &clock, id, Interrupt::Clear, &interrupted, false).unwrap();
+
+        assert_eq!(
+            Status::load(&config.state_file_path).unwrap(),
+            Status::Inactive
+        );
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries()[0].duration(), TimeDelta::minutes(25));
+    }
+
+    #[test]
+    fn waiting_handles_interrupts() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let clock = FixedClock(dt + TimeDelta::minutes(10));
+
+        for (on_interrupt, archived) in [(Interrupt::Finish, 1), (Interrupt::Clear, 0)] {
+            let dir = tempfile::tempdir().unwrap();
+            let config = temp_config(dir.path());
+            let pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+            let id = pom.id();
+            tomate::start(&config, pom).unwrap();
+
+            let interrupted = AtomicBool::new(true);
+            wait_until_done(&config, &clock, id, on_interrupt, &interrupted, false).unwrap();
+
+            assert_eq!(
+                Status::load(&config.state_file_path).unwrap(),
+                Status::Inactive,
+                "for {:?}",
+                on_interrupt
+            );
+            let history = History::load(&config.history_file_path).unwrap();
+            assert_eq!(history.entries().len(), archived, "for {:?}", on_interrupt);
+            if let Some(entry) = history.entries().first() {
+                assert_eq!(entry.duration(), TimeDelta::minutes(10));
+            }
+        }
+    }
+
+    #[test]
+    fn starting_in_the_past_archives_the_full_duration() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = temp_config(dir.path());
+        let clock = FixedClock("2024-03-27T12:00:00-06:00".parse().unwrap());
+        let args = Args::try_parse_from(["tomate", "start", "--at", "-40m", "-d", "25m"]).unwrap();
+        let Command::Start { at, duration, .. } = args.command else {
+            unreachable!()
+        };
+        let starts_at = at.unwrap().resolve(clock.0).unwrap();
+        let pom = new_pomodoro(&config, starts_at, duration.get(), None, None, None).unwrap();
+
+        let outcome = start_pomodoro(&config, &clock, pom).unwrap();
+
+        assert!(matches!(
+            outcome,
+            Some(FinishOutcome::Archived(ref entry)) if entry.duration() == TimeDelta::minutes(25)
+        ));
+        assert_eq!(
+            Status::load(&config.state_file_path).unwrap(),
+            Status::Inactive
+        );
+        let history = History::load(&config.history_file_path).unwrap();
+        assert_eq!(history.entries()[0].duration(), TimeDelta::minutes(25));
+    }
+
+    #[test]
+    fn breaks_schedule_a_check() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = temp_config(dir.path());
+        let clock = FixedClock("2024-03-27T12:00:00-06:00".parse().unwrap());
+
+        for (long, expected) in [(false, 300), (true, 1200)] {
+            let mut scheduled = None;
+            start_break(&config, &clock, None, long, Some("lunch"), |seconds| {
+                scheduled = Some(seconds);
+                Ok(())
+            })
+            .unwrap();
+            let status = Status::load(&config.state_file_path).unwrap();
+            assert_eq!(status.description(), Some("lunch"));
+            tomate::finish(&config, &clock).unwrap();
+
+            assert_eq!(scheduled, Some(expected));
+        }
+
+        let history = History::load(&config.history_file_path).unwrap();
+        assert!(history
+            .entries()
+            .iter()
+            .all(|entry| entry.description() == Some("lunch")));
+    }
+
+    #[test]
+    fn color_only_on_terminals() {
+        assert!(use_color(ColorChoice::Auto, false, None, true));
+        assert!(use_color(ColorChoice::Auto, false, Some("".into()), true));
+        assert!(!use_color(ColorChoice::Auto, false, None, false));
+        assert!(!use_color(ColorChoice::Auto, true, None, true));
+    }
+
+    #[test]
+    fn color_flag_overrides_environment() {
+        assert!(use_color(
+            ColorChoice::Always,
+            false,
+            Some("1".into()),
+            false
+        ));
+        assert!(!use_color(ColorChoice::Never, false, None, true));
+    }
+
+    #[test]
+    fn color_never_strips_escape_sequences() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let timer = Timer::new(dt, TimeDelta::minutes(25));
+
+        colored::control::set_override(use_color(ColorChoice::Never, false, None, true));
+
+        let line = progress_line(&timer, dt + TimeDelta::minutes(10));
+
+        assert!(line.starts_with("10:00 ████████████████░"));
+        assert!(!line.contains('\x1b'));
+    }
+
+    #[test]
+    fn history_table_without_color() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.set_description("Write tests");
+        pom.finish(dt + TimeDelta::minutes(25));
+        let entries = vec![HistoryEntry::archive(&pom, None).unwrap()];
+
+        colored::control::set_override(false);
+
+        let table = history_table(&entries, dt + TimeDelta::hours(2), true, "%d %b %R");
+        let mut output = Vec::new();
+        table.print(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains("2h ago"));
+        assert!(output.contains("Write tests"));
+        assert!(!output.contains('\x1b'));
+    }
+
+    #[test]
+    fn duration_formats() {
+        let cases = [
+            ("25", TimeDelta::minutes(25)),
+            ("1.5h", TimeDelta::minutes(90)),
+            ("90s", TimeDelta::seconds(90)),
+            ("22m30s", TimeDelta::seconds(22 * 60 + 30)),
+            ("12h", TimeDelta::hours(12)),
+            ("0.5m", TimeDelta::seconds(30)),
+            ("1h0.5m", TimeDelta::seconds(3630)),
+        ];
+
+        for (input, expected) in cases {
+            assert_eq!(
+                duration_from_human(input).unwrap(),
+                expected,
+                "for {}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_durations() {
+        for input in ["abc", "", "30s5m", "-5", "1.h", "NaN", "inf"] {
+            assert!(duration_from_human(input).is_err(), "for {}", input);
+        }
+    }
+
+    #[test]
+    fn history_total_row() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.finish(dt + TimeDelta::minutes(25));
+        let rest = Break::new(dt + TimeDelta::minutes(25), TimeDelta::minutes(5));
+        let entries = vec![
+            HistoryEntry::archive(&pom, None).unwrap(),
+            HistoryEntry::archive(&pom, None).unwrap(),
+            HistoryEntry::archive_break(
+                tomate::Phase::ShortBreak,
+                &rest,
+                dt + TimeDelta::minutes(30),
+            ),
+        ];
+
+        let row = total_row(&entries);
+
+        assert_eq!(row.get_cell(1).unwrap().get_content(), "3 entries");
+        assert_eq!(row.get_cell(2).unwrap().get_content(), "50m");
+    }
+
+    #[test]
+    fn history_table_date_format() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.finish(dt + TimeDelta::minutes(25));
+        let entries = vec![HistoryEntry::archive(&pom, None).unwrap()];
+
+        let table = history_table(&entries, dt, false, "%Y-%m-%d");
+        let mut output = Vec::new();
+        table.print(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.contains(&dt.format("%Y-%m-%d").to_string()));
+    }
+
+    #[test]
+    fn percent_change_between_weeks() {
+        assert_eq!(
+            percent_change(TimeDelta::hours(4), TimeDelta::hours(5)),
+            Some(25)
+        );
+        assert_eq!(
+            percent_change(TimeDelta::hours(3), TimeDelta::hours(2)),
+            Some(-33)
+        );
+        assert_eq!(percent_change(TimeDelta::zero(), TimeDelta::hours(2)), None);
+    }
 }
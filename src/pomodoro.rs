@@ -1,16 +1,23 @@
 use crate::time::Timer;
+use crate::PomodoroTemplate;
+use anyhow::{bail, Result};
 use chrono::{prelude::*, TimeDelta};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 /// A Pomodoro timer
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Pomodoro {
     #[serde(flatten)]
     timer: Timer,
     description: Option<String>,
     tags: Option<Vec<String>>,
     #[serde(default, with = "crate::time::datetimeopt::unix")]
+    #[schemars(with = "Option<i64>")]
     finished_at: Option<DateTime<Local>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<Uuid>,
 }
 
 impl Pomodoro {
@@ -22,14 +29,38 @@ impl Pomodoro {
             finished_at: None,
             description: None,
             tags: None,
+            id: Some(Uuid::new_v4()),
         }
     }
 
+    /// Start building a Pomodoro with optional fields
+    ///
+    /// See [`PomodoroBuilder`].
+    pub fn builder() -> PomodoroBuilder {
+        PomodoroBuilder::default()
+    }
+
     /// Get the struct describing the time this Pomodoro is running
     pub fn timer(&self) -> &Timer {
         &self.timer
     }
 
+    pub(crate) fn timer_mut(&mut self) -> &mut Timer {
+        &mut self.timer
+    }
+
+    /// Check if this Pomodoro has run past the end of its timer
+    pub fn is_overdue(&self, now: DateTime<Local>) -> bool {
+        self.timer.done(now)
+    }
+
+    /// Get the unique identifier generated when this Pomodoro was created
+    ///
+    /// Pomodoros saved by older versions of tomate don't have one.
+    pub fn id(&self) -> Option<Uuid> {
+        self.id
+    }
+
     /// Get the description
     pub fn description(&self) -> Option<&str> {
         self.description.as_deref()
@@ -57,10 +88,203 @@ impl Pomodoro {
 
     /// Get the duration that this Pomodoro lasted before it was finished.
     ///
-    /// This is the actual time between start and finish. If you want to get
-    /// the duration the timer was set for, use the duration of this Pomodoro's [`timer()`].
+    /// This is the actual time between start and finish, minus the time the
+    /// timer was paused. If you want to get the duration the timer was set
+    /// for, use the duration of this Pomodoro's [`timer()`].
     pub fn duration(&self) -> Option<TimeDelta> {
         self.finished_at
-            .map(|finished_at| finished_at - self.timer.starts_at())
+            .map(|finished_at| finished_at - self.timer.starts_at() - self.timer.paused_for())
+    }
+}
+
+/// Builds a [`Pomodoro`] one field at a time
+///
+/// Fields that aren't set get a default: the Pomodoro starts now and lasts
+/// for 25 minutes, with no description or tags. [`build`](Self::build)
+/// rejects durations that aren't positive and tags that contain commas.
+///
+/// ```
+/// use chrono::{prelude::*, TimeDelta};
+/// use tomate::Pomodoro;
+///
+/// let started_at: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+///
+/// let pom = Pomodoro::builder()
+///     .started_at(started_at)
+///     .duration(TimeDelta::minutes(50))
+///     .description("Write the builder")
+///     .tags(["code", "docs"])
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(pom.timer().ends_at(), started_at + TimeDelta::minutes(50));
+/// assert_eq!(pom.description(), Some("Write the builder"));
+/// assert_eq!(pom.tags(), Some(&vec!["code".to_string(), "docs".to_string()]));
+/// ```
+///
+/// ```
+/// use chrono::TimeDelta;
+/// use tomate::Pomodoro;
+///
+/// let result = Pomodoro::builder().duration(TimeDelta::zero()).build();
+///
+/// assert!(result.is_err());
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct PomodoroBuilder {
+    started_at: Option<DateTime<Local>>,
+    duration: Option<TimeDelta>,
+    description: Option<String>,
+    description_prefix: Option<String>,
+    tags: Option<Vec<String>>,
+}
+
+impl PomodoroBuilder {
+    /// Set the time the Pomodoro starts
+    pub fn started_at(mut self, started_at: DateTime<Local>) -> Self {
+        self.started_at = Some(started_at);
+        self
+    }
+
+    /// Set the length of the Pomodoro's timer
+    pub fn duration(mut self, duration: TimeDelta) -> Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Set the description
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Set the tags
+    pub fn tags<I, T>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.tags = Some(tags.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Take the duration, description, and tags from a template
+    ///
+    /// Values set after this replace the template's, except for the
+    /// description, which is added to the end of the template's description.
+    pub fn template(mut self, template: &PomodoroTemplate) -> Self {
+        if let Some(duration) = template.duration {
+            self.duration = Some(duration);
+        }
+        self.description_prefix = template.description.clone();
+        if let Some(tags) = &template.tags {
+            self.tags = Some(tags.clone());
+        }
+        self
+    }
+
+    /// Create the Pomodoro, filling in defaults for anything that wasn't set
+    pub fn build(self) -> Result<Pomodoro> {
+        let duration = self.duration.unwrap_or(TimeDelta::minutes(25));
+        if duration <= TimeDelta::zero() {
+            bail!("A Pomodoro must last longer than zero seconds");
+        }
+
+        let mut pom = Pomodoro::new(self.started_at.unwrap_or_else(Local::now), duration);
+        pom.description = match (self.description_prefix, self.description) {
+            (Some(prefix), Some(description)) => Some(format!("{}: {}", prefix, description)),
+            (prefix, description) => description.or(prefix),
+        };
+        pom.tags = self
+            .tags
+            .map(|tags| normalize_tags(tags, false))
+            .transpose()?;
+
+        Ok(pom)
+    }
+}
+
+/// Clean up a list of tags so that equivalent tags are stored the same way
+///
+/// Surrounding whitespace is trimmed from each tag, empty tags are dropped,
+/// and duplicates are removed, keeping the first occurrence.
+/// If `lowercase` is true, tags are also converted to lowercase before
+/// looking for duplicates, so `Work` and `work` become a single `work` tag.
+///
+/// Returns an error if any tag contains a comma, since tags are
+/// written out as a comma-separated list.
+pub fn normalize_tags(tags: Vec<String>, lowercase: bool) -> Result<Vec<String>> {
+    let mut normalized: Vec<String> = Vec::with_capacity(tags.len());
+
+    for tag in tags {
+        let tag = tag.trim();
+
+        if tag.contains(',') {
+            bail!("Tags cannot contain commas, but got \"{}\"", tag);
+        }
+
+        if tag.is_empty() {
+            continue;
+        }
+
+        let tag = if lowercase {
+            tag.to_lowercase()
+        } else {
+            tag.to_string()
+        };
+
+        if !normalized.contains(&tag) {
+            normalized.push(tag);
+        }
+    }
+
+    Ok(normalized)
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{prelude::*, TimeDelta};
+
+    use super::{normalize_tags, Pomodoro};
+
+    fn tags(tags: &[&str]) -> Vec<String> {
+        tags.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn overdue_only_after_the_end() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        let ends_at = dt + TimeDelta::minutes(25);
+
+        assert!(!pom.is_overdue(ends_at - TimeDelta::seconds(1)));
+        assert!(!pom.is_overdue(ends_at));
+        assert!(pom.is_overdue(ends_at + TimeDelta::seconds(1)));
+    }
+
+    #[test]
+    fn normalize_trims_whitespace() {
+        let normalized = normalize_tags(tags(&[" work ", "fun", "  "]), false).unwrap();
+
+        assert_eq!(normalized, tags(&["work", "fun"]));
+    }
+
+    #[test]
+    fn normalize_keeps_case_by_default() {
+        let normalized = normalize_tags(tags(&["Work", "work"]), false).unwrap();
+
+        assert_eq!(normalized, tags(&["Work", "work"]));
+    }
+
+    #[test]
+    fn normalize_lowercase_dedups_mixed_case() {
+        let normalized = normalize_tags(tags(&["Work", "fun", "work", "WORK"]), true).unwrap();
+
+        assert_eq!(normalized, tags(&["work", "fun"]));
+    }
+
+    #[test]
+    fn normalize_rejects_commas() {
+        assert!(normalize_tags(tags(&["work,fun"]), false).is_err());
     }
 }
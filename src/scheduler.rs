@@ -0,0 +1,347 @@
+//! Scheduling `tomate timer check` and the tick hook with systemd
+
+use std::io::{self, Write};
+
+use anyhow::{bail, Context, Result};
+use chrono::{prelude::*, TimeDelta};
+use colored::Colorize;
+use log::info;
+
+use tomate::{Config, Pomodoro, TomateError};
+
+use crate::Args;
+
+/// Start of the name of every systemd unit that tomate schedules to check timers, so they can be found again
+const TIMER_UNIT_PREFIX: &str = "tomate-check-";
+
+/// Start of the name of the systemd units that run the tick hook
+const TICK_UNIT_PREFIX: &str = "tomate-tick-";
+
+/// A systemd timer that will run `tomate timer check`
+#[derive(Debug, PartialEq)]
+pub struct ScheduledCheck {
+    /// Name of the timer unit
+    pub unit: String,
+    /// When the timer fires next, if it is going to
+    pub next: Option<DateTime<Local>>,
+}
+
+/// Parse the JSON output of `systemctl list-timers --output=json`
+pub fn parse_timer_list(json: &str) -> Result<Vec<ScheduledCheck>> {
+    #[derive(serde::Deserialize)]
+    struct ListedTimer {
+        unit: String,
+        next: Option<i64>,
+    }
+
+    let timers: Vec<ListedTimer> =
+        serde_json::from_str(json).with_context(|| "Failed to parse the list of systemd timers")?;
+
+    Ok(timers
+        .into_iter()
+        .map(|timer| ScheduledCheck {
+            unit: timer.unit,
+            next: timer
+                .next
+                .filter(|usec| *usec > 0)
+                .and_then(DateTime::from_timestamp_micros)
+                .map(|next| next.with_timezone(&Local)),
+        })
+        .collect())
+}
+
+/// Ask systemd for the timer checks tomate has scheduled
+pub fn scheduled_checks() -> Result<Vec<ScheduledCheck>> {
+    let output = std::process::Command::new("systemctl")
+        .args(["--user", "list-timers", "--all", "--output=json"])
+        .arg(format!("{}*", TIMER_UNIT_PREFIX))
+        .output()
+        .with_context(|| "Failed to run systemctl")?;
+
+    if !output.status.success() {
+        bail!(
+            "systemctl list-timers failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    parse_timer_list(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Schedule a `tomate timer check` for when the current timer runs out
+///
+/// Every kind of timer is scheduled this way, so they're all checked with the
+/// same accuracy, binary, and error handling.
+pub fn schedule_finish(args: &Args, config: &Config, timer_seconds: i64) -> Result<()> {
+    if config.dry_run {
+        info!(
+            "Dry run: would schedule a systemd timer in {} seconds",
+            timer_seconds
+        );
+        return Ok(());
+    }
+
+    let unit = format!("{}{}", TIMER_UNIT_PREFIX, uuid::Uuid::new_v4().simple());
+    let systemd_args = timer_check_args(args, config, &unit, timer_seconds)?;
+
+    run_systemd("systemd-run", &systemd_args, args.verbose.is_silent())
+}
+
+/// Build the `systemd-run` arguments for a timer check
+fn timer_check_args(
+    args: &Args,
+    config: &Config,
+    unit: &str,
+    timer_seconds: i64,
+) -> Result<Vec<String>> {
+    let mut systemd_args = vec![
+        "--user".to_string(),
+        format!("--unit={}", unit),
+        format!("--on-active={}", timer_seconds),
+        format!(
+            "--timer-property=AccuracySec={}ms",
+            config.timer_accuracy.num_milliseconds()
+        ),
+    ];
+    systemd_args.extend(tomate_command(args, &["timer", "check"])?);
+
+    Ok(systemd_args)
+}
+
+/// Get how often a Pomodoro needs to be ticked, if at all
+///
+/// Ticks run the tick hook if `tick_interval` is set, check for idleness if
+/// `idle_timeout` is set, and check for suspends if `pause_on_suspend` is on,
+/// so they run often enough for all of them.
+pub fn tick_interval(config: &Config) -> Option<TimeDelta> {
+    [
+        config.tick_interval,
+        config.idle_timeout,
+        config.pause_on_suspend.then_some(config.suspend_threshold),
+    ]
+    .into_iter()
+    .flatten()
+    .min()
+}
+
+/// Schedule a repeating systemd timer to run the tick hook during a Pomodoro
+///
+/// Does nothing unless [`tick_interval`] says the Pomodoro needs ticks.
+/// The timer is named after the Pomodoro, so [`stop_ticks`] can stop it once
+/// the Pomodoro is over.
+pub fn schedule_ticks(args: &Args, config: &Config, pom: &Pomodoro) -> Result<()> {
+    let (Some(interval), Some(id)) = (tick_interval(config), pom.id()) else {
+        return Ok(());
+    };
+    let tick_seconds = interval.num_seconds().max(1);
+
+    if config.dry_run {
+        info!(
+            "Dry run: would schedule a systemd timer every {} seconds",
+            tick_seconds
+        );
+        return Ok(());
+    }
+
+    let mut systemd_args = vec![
+        "--user".to_string(),
+        format!("--unit={}{}", TICK_UNIT_PREFIX, id.simple()),
+        format!("--on-active={}", tick_seconds),
+        format!("--on-unit-active={}", tick_seconds),
+        format!(
+            "--timer-property=AccuracySec={}ms",
+            config.timer_accuracy.num_milliseconds()
+        ),
+    ];
+    systemd_args.extend(tomate_command(args, &["timer", "tick", &id.to_string()])?);
+
+    // The timer check already warns if systemd-run is missing
+    run_systemd("systemd-run", &systemd_args, true)
+}
+
+/// Stop the repeating systemd timer started by [`schedule_ticks`]
+pub fn stop_ticks(config: &Config, id: uuid::Uuid) -> Result<()> {
+    let unit = format!("{}{}.timer", TICK_UNIT_PREFIX, id.simple());
+
+    if config.dry_run {
+        info!("Dry run: would stop systemd timer {}", unit.cyan());
+        return Ok(());
+    }
+
+    info!("Stopping systemd timer {}", unit.cyan());
+    let output = std::process::Command::new("systemctl")
+        .args(["--user", "stop", &unit])
+        .output()
+        .with_context(|| "Failed to run systemctl")?;
+
+    if !output.status.success() {
+        bail!(
+            "Failed to stop {}: {}",
+            unit,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(())
+}
+
+/// Build the command line for systemd to run tomate with
+///
+/// The command has to use the same config, profile, and session as this one,
+/// so it looks at the same state file.
+fn tomate_command(args: &Args, command: &[&str]) -> Result<Vec<String>> {
+    let mut words = vec![std::env::current_exe()?.to_str().unwrap().to_string()];
+
+    if let Some(config_path) = &args.config {
+        words.push("--config".to_string());
+        words.push(config_path.display().to_string());
+    }
+    if let Some(profile) = &args.profile {
+        words.push("--profile".to_string());
+        words.push(profile.clone());
+    }
+    if let Some(session) = &args.session {
+        words.push("--session".to_string());
+        words.push(session.clone());
+    }
+
+    words.extend(command.iter().map(|word| word.to_string()));
+
+    Ok(words)
+}
+
+/// Run `systemd_run` to schedule a timer check
+///
+/// If `systemd_run` isn't installed, the timer is left running and only a
+/// warning is printed, since it can still be finished by hand.
+/// If `quiet` is true, nothing is printed unless `systemd_run` fails.
+pub fn run_systemd(systemd_run: &str, systemd_args: &[String], quiet: bool) -> Result<()> {
+    let systemd_output = match std::process::Command::new(systemd_run)
+        .args(systemd_args)
+        .output()
+    {
+        Ok(output) => output,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            if !quiet {
+                eprintln!(
+                    "{} {} was not found, so the timer will not finish automatically. Run {} when it is done.",
+                    "Warning:".yellow().bold(),
+                    systemd_run,
+                    "tomate timer check".cyan()
+                );
+            }
+            return Ok(());
+        }
+        Err(source) => {
+            return Err(TomateError::SchedulerUnavailable {
+                program: systemd_run.to_string(),
+                source,
+            }
+            .into())
+        }
+    };
+
+    if !quiet {
+        io::stdout().write_all(&systemd_output.stderr)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::{prelude::*, TimeDelta};
+    use clap::Parser;
+
+    use tomate::Config;
+
+    use super::{parse_timer_list, run_systemd, tick_interval, timer_check_args, ScheduledCheck};
+    use crate::Args;
+
+    #[test]
+    fn missing_systemd_run_is_not_an_error() {
+        let result = run_systemd("tomate-missing-systemd-run", &["--user".to_string()], true);
+
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn timer_check_uses_configured_accuracy() {
+        let args = Args::parse_from(["tomate", "--config", "/tmp/tomate.toml", "start"]);
+        let mut config = Config::try_default().unwrap();
+        config.timer_accuracy = TimeDelta::milliseconds(2500);
+
+        let systemd_args = timer_check_args(&args, &config, "tomate-check-test", 1500).unwrap();
+
+        assert_eq!(
+            systemd_args[..4],
+            [
+                "--user",
+                "--unit=tomate-check-test",
+                "--on-active=1500",
+                "--timer-property=AccuracySec=2500ms",
+            ]
+        );
+        assert_eq!(
+            systemd_args[5..],
+            ["--config", "/tmp/tomate.toml", "timer", "check"]
+        );
+    }
+
+    #[test]
+    fn ticks_are_often_enough_for_every_option() {
+        let config = Config::try_default().unwrap();
+        assert_eq!(tick_interval(&config), None);
+
+        let config = Config {
+            tick_interval: Some(TimeDelta::minutes(5)),
+            pause_on_suspend: true,
+            suspend_threshold: TimeDelta::minutes(2),
+            ..config
+        };
+        assert_eq!(tick_interval(&config), Some(TimeDelta::minutes(2)));
+
+        let config = Config {
+            idle_timeout: Some(TimeDelta::minutes(1)),
+            ..config
+        };
+        assert_eq!(tick_interval(&config), Some(TimeDelta::minutes(1)));
+    }
+
+    #[test]
+    fn timer_check_keeps_the_session() {
+        let args = Args::parse_from(["tomate", "--session", "work", "start"]);
+        let config = Config::try_default().unwrap();
+
+        let systemd_args = timer_check_args(&args, &config, "tomate-check-test", 1500).unwrap();
+
+        assert_eq!(systemd_args[5..], ["--session", "work", "timer", "check"]);
+    }
+
+    #[test]
+    fn parse_systemd_timer_list() {
+        let checks = parse_timer_list(
+            r#"[
+                {"next":1711562400000000,"left":1500000000,"last":null,"passed":null,"unit":"tomate-check-1.timer","activates":"tomate-check-1.service"},
+                {"next":0,"left":0,"last":1711562400000000,"passed":60000000,"unit":"tomate-check-2.timer","activates":"tomate-check-2.service"}
+            ]"#,
+        )
+        .unwrap();
+
+        let next: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        assert_eq!(
+            checks,
+            vec![
+                ScheduledCheck {
+                    unit: "tomate-check-1.timer".to_string(),
+                    next: Some(next),
+                },
+                ScheduledCheck {
+                    unit: "tomate-check-2.timer".to_string(),
+                    next: None,
+                },
+            ]
+        );
+        assert!(parse_timer_list("").is_err());
+    }
+}
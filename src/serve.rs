@@ -0,0 +1,282 @@
+//! Serve the current status to other programs over a Unix domain socket
+//!
+//! Clients connect to the socket at [`Config::socket_path`] and read
+//! newline-delimited JSON [`StatusReport`]s. A report is sent as soon as a
+//! client connects, whenever the status changes, and in reply to any line
+//! the client writes.
+
+use std::{
+    io::{BufRead, BufReader, ErrorKind, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use chrono::{prelude::*, TimeDelta};
+use colored::Colorize;
+use log::{info, warn};
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{Clock, Config, Phase, Pomodoro, Status, Timer};
+
+/// How often the state file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long a client can block a write before it is disconnected
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A snapshot of the current status, as sent to clients
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize)]
+pub struct StatusReport {
+    /// Unique identifier of the current Pomodoro
+    pub id: Option<Uuid>,
+    /// Kind of timer that is running, or `None` if there isn't one
+    pub phase: Option<Phase>,
+    /// When the timer started
+    #[serde(with = "crate::time::datetimeopt::unix")]
+    pub started_at: Option<DateTime<Local>>,
+    /// When the timer ends
+    #[serde(with = "crate::time::datetimeopt::unix")]
+    pub ends_at: Option<DateTime<Local>>,
+    /// Time left on the timer
+    #[serde(
+        rename = "remaining_seconds",
+        serialize_with = "crate::time::durationopt::seconds::serialize"
+    )]
+    pub remaining: Option<TimeDelta>,
+    /// Whether the timer has run out, which is false if there isn't one
+    pub done: bool,
+    /// Description of the current Pomodoro or break
+    pub description: Option<String>,
+    /// Tags of the current Pomodoro
+    pub tags: Option<Vec<String>>,
+}
+
+impl StatusReport {
+    /// Describe a status as of `now`
+    pub fn new(status: &Status, now: DateTime<Local>) -> Self {
+        let timer: Option<&Timer> = match status {
+            Status::Inactive => None,
+            Status::Active(pom) => Some(pom.timer()),
+            Status::ShortBreak(rest) | Status::LongBreak(rest) => Some(rest.timer()),
+        };
+        let pomodoro = match status {
+            Status::Active(pom) => Some(pom),
+            _ => None,
+        };
+
+        Self {
+            id: pomodoro.and_then(Pomodoro::id),
+            phase: status.phase(),
+            started_at: timer.map(Timer::starts_at),
+            ends_at: timer.map(Timer::ends_at),
+            remaining: timer.map(|timer| timer.remaining(now)),
+            done: timer.is_some_and(|timer| timer.done(now)),
+            description: status.description().map(str::to_owned),
+            tags: pomodoro.and_then(|pom| pom.tags().cloned()),
+        }
+    }
+}
+
+/// Listen on the configured socket and stream status reports until interrupted
+///
+/// A socket file left behind by a server that is no longer running is
+/// replaced, and the socket file is removed when the server stops.
+pub fn serve(config: &Config, clock: &dyn Clock) -> Result<()> {
+    let listener = bind(&config.socket_path)?;
+    let _socket = SocketFile(config.socket_path.clone());
+
+    listener
+        .set_nonblocking(true)
+        .with_context(|| "Failed to configure socket")?;
+
+    let running = Arc::new(AtomicBool::new(true));
+    {
+        let running = running.clone();
+        ctrlc::set_handler(move || running.store(false, Ordering::SeqCst))
+            .with_context(|| "Failed to set up signal handler")?;
+    }
+
+    info!(
+        "Serving status on {}",
+        config.socket_path.display().to_string().cyan()
+    );
+
+    let current = Arc::new(Mutex::new(String::new()));
+    let mut clients: Vec<Arc<Mutex<UnixStream>>> = Vec::new();
+
+    while running.load(Ordering::SeqCst) {
+        match Status::load(&config.state_file_path) {
+            Ok(status) => {
+                let line = report_line(&StatusReport::new(&status, clock.now()))?;
+                let mut current = current.lock().unwrap();
+                if *current != line {
+                    clients.retain(|client| send(client, &line));
+                    *current = line;
+                }
+            }
+            Err(e) => warn!("Failed to read state file: {:#}", e),
+        }
+
+        loop {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    if let Some(client) = connect(stream, current.clone()) {
+                        clients.push(client);
+                    }
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    warn!("Failed to accept connection: {}", e);
+                    break;
+                }
+            }
+        }
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    info!("Shutting down");
+
+    Ok(())
+}
+
+/// Bind a socket at `path`, replacing a stale socket file if there is one
+fn bind(path: &Path) -> Result<UnixListener> {
+    if path.exists() {
+        if UnixStream::connect(path).is_ok() {
+            bail!(
+                "Another tomate server is already listening on {}",
+                path.display()
+            );
+        }
+
+        info!(
+            "Removing stale socket {}",
+            path.display().to_string().cyan()
+        );
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+    }
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory {}", dir.display()))?;
+    }
+
+    UnixListener::bind(path).with_context(|| format!("Failed to listen on {}", path.display()))
+}
+
+/// Send the current report to a new client, and answer its requests from now on
+fn connect(stream: UnixStream, current: Arc<Mutex<String>>) -> Option<Arc<Mutex<UnixStream>>> {
+    let reader = stream
+        .set_nonblocking(false)
+        .and_then(|_| stream.set_write_timeout(Some(WRITE_TIMEOUT)))
+        .and_then(|_| stream.try_clone());
+    let reader = match reader {
+        Ok(reader) => reader,
+        Err(e) => {
+            warn!("Failed to set up connection: {}", e);
+            return None;
+        }
+    };
+
+    let client = Arc::new(Mutex::new(stream));
+    if !send(&client, &current.lock().unwrap()) {
+        return None;
+    }
+
+    let requester = client.clone();
+    std::thread::spawn(move || {
+        for line in BufReader::new(reader).lines() {
+            if line.is_err() || !send(&requester, &current.lock().unwrap()) {
+                break;
+            }
+        }
+    });
+
+    Some(client)
+}
+
+/// Write a line to a client, returning `false` if it has gone away
+fn send(client: &Mutex<UnixStream>, line: &str) -> bool {
+    let mut stream = client.lock().unwrap();
+    writeln!(stream, "{}", line).is_ok()
+}
+
+fn report_line(report: &StatusReport) -> Result<String> {
+    serde_json::to_string(report).with_context(|| "Unable to serialize status as JSON")
+}
+
+/// Removes the socket file when the server stops
+struct SocketFile(PathBuf);
+
+impl Drop for SocketFile {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.0) {
+            warn!("Failed to remove socket {}: {}", self.0.display(), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::os::unix::net::UnixListener;
+
+    use chrono::{prelude::*, TimeDelta};
+
+    use super::{bind, report_line, StatusReport};
+    use crate::{Pomodoro, Status};
+
+    #[test]
+    fn report_active_pomodoro() {
+        let dt: DateTime<Local> = "2024-03-27T12:00:00-06:00".parse().unwrap();
+        let mut pom = Pomodoro::new(dt, TimeDelta::minutes(25));
+        pom.set_description("write the report");
+
+        let report = StatusReport::new(&Status::Active(pom), dt + TimeDelta::minutes(5));
+        let json: serde_json::Value = serde_json::from_str(&report_line(&report).unwrap()).unwrap();
+
+        assert_eq!(json["phase"], "pomodoro");
+        assert_eq!(json["started_at"], dt.timestamp());
+        assert_eq!(json["remaining_seconds"], 20 * 60);
+        assert_eq!(json["done"], false);
+        assert_eq!(json["description"], "write the report");
+        assert!(json["tags"].is_null());
+    }
+
+    #[test]
+    fn report_inactive() {
+        let report = StatusReport::new(&Status::Inactive, Local::now());
+
+        assert_eq!(
+            report_line(&report).unwrap(),
+            r#"{"id":null,"phase":null,"started_at":null,"ends_at":null,"remaining_seconds":null,"done":false,"description":null,"tags":null}"#
+        );
+    }
+
+    #[test]
+    fn bind_replaces_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tomate.sock");
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        assert!(bind(&path).is_ok());
+    }
+
+    #[test]
+    fn bind_refuses_live_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tomate.sock");
+        let _listener = UnixListener::bind(&path).unwrap();
+
+        assert!(bind(&path).is_err());
+    }
+}
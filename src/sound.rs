@@ -0,0 +1,65 @@
+use std::path::Path;
+
+use anyhow::Result;
+use colored::Colorize;
+use log::{info, warn};
+
+use crate::{Config, Phase};
+
+/// Play the sound configured for the end of a phase, if there is one
+///
+/// Failing to play the sound only logs a warning, since it should never
+/// prevent a timer from finishing.
+pub(crate) fn play_for(config: &Config, phase: Phase) {
+    let Some(path) = config.sound_for(phase) else {
+        return;
+    };
+
+    if config.dry_run {
+        info!(
+            "Dry run: would play sound file {}",
+            path.display().to_string().cyan()
+        );
+        return;
+    }
+
+    if let Err(e) = play_sound(path) {
+        warn!("Failed to play sound file {}: {:#}", path.display(), e);
+    }
+}
+
+/// Play a sound file, blocking until it is done
+#[cfg(feature = "sound")]
+pub fn play_sound(path: &Path) -> Result<()> {
+    use std::{fs::File, io::BufReader};
+
+    use anyhow::Context;
+
+    info!("Playing sound file {}", path.display().to_string().cyan());
+
+    let file = File::open(path)
+        .with_context(|| format!("Unable to open sound file {}", path.display()))?;
+    let source = rodio::Decoder::new(BufReader::new(file))
+        .with_context(|| format!("Unable to decode sound file {}", path.display()))?;
+
+    let (_stream, stream_handle) =
+        rodio::OutputStream::try_default().with_context(|| "Unable to open audio output")?;
+    let sink =
+        rodio::Sink::try_new(&stream_handle).with_context(|| "Unable to open audio output")?;
+
+    sink.append(source);
+    sink.sleep_until_end();
+
+    Ok(())
+}
+
+/// Play a sound file, blocking until it is done
+///
+/// Tomate was built without the `sound` feature, so this always fails.
+#[cfg(not(feature = "sound"))]
+pub fn play_sound(path: &Path) -> Result<()> {
+    anyhow::bail!(
+        "Unable to play {}, tomate was built without the \"sound\" feature",
+        path.display()
+    )
+}
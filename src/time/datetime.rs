@@ -1,14 +1,17 @@
 #[doc(hidden)]
 pub mod unix {
     use chrono::prelude::*;
-    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
 
     pub fn deserialize<'de, D>(deserializer: D) -> Result<DateTime<Local>, D::Error>
     where
         D: Deserializer<'de>,
     {
         let ts: i64 = Deserialize::deserialize(deserializer)?;
-        Ok(Local.timestamp_opt(ts, 0).unwrap())
+        Local
+            .timestamp_opt(ts, 0)
+            .single()
+            .ok_or_else(|| D::Error::custom(format!("timestamp {} is out of range", ts)))
     }
 
     pub fn serialize<S>(dt: &DateTime<Local>, serializer: S) -> Result<S::Ok, S::Error>
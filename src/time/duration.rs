@@ -1,16 +1,41 @@
-#[doc(hidden)]
+//! Serde helpers for [`TimeDelta`](chrono::TimeDelta) fields
+//!
+//! Tomate's own files store durations as an integer count of seconds
+//! using the [`seconds`] module, or [`milliseconds`] for the few settings that
+//! need to be more precise than that. The config file uses [`human`], so
+//! durations can be written like `"25m"`. Tools that would rather exchange durations as
+//! ISO 8601 strings (e.g. `PT1500S`) can opt in with the [`iso8601`] module:
+//!
+//! ```
+//! use chrono::TimeDelta;
+//! use serde::{Deserialize, Serialize};
+//!
+//! #[derive(Deserialize, Serialize)]
+//! struct Session {
+//!     #[serde(with = "tomate::duration::iso8601")]
+//!     length: TimeDelta,
+//! }
+//!
+//! let session: Session = toml::from_str(r#"length = "PT25M""#).unwrap();
+//! assert_eq!(session.length, TimeDelta::minutes(25));
+//! ```
+
+/// Serialize a duration as an integer count of seconds
 pub mod seconds {
     use chrono::TimeDelta;
-    use serde::{Deserialize, Deserializer, Serializer};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
 
+    /// Deserialize an integer count of seconds
     pub fn deserialize<'de, D>(deserializer: D) -> Result<TimeDelta, D::Error>
     where
         D: Deserializer<'de>,
     {
         let sec: i64 = Deserialize::deserialize(deserializer)?;
-        Ok(TimeDelta::new(sec, 0).unwrap())
+        TimeDelta::try_seconds(sec)
+            .ok_or_else(|| D::Error::custom(format!("duration of {sec} seconds is out of range")))
     }
 
+    /// Serialize a duration as an integer count of seconds
     pub fn serialize<S>(delta: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
@@ -18,3 +43,511 @@ pub mod seconds {
         serializer.serialize_i64(delta.num_seconds())
     }
 }
+
+/// Serialize a duration as an integer count of milliseconds
+pub mod milliseconds {
+    use chrono::TimeDelta;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    /// Deserialize an integer count of milliseconds
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<TimeDelta, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ms: i64 = Deserialize::deserialize(deserializer)?;
+        TimeDelta::try_milliseconds(ms).ok_or_else(|| {
+            D::Error::custom(format!("duration of {ms} milliseconds is out of range"))
+        })
+    }
+
+    /// Serialize a duration as an integer count of milliseconds
+    pub fn serialize<S>(delta: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_i64(delta.num_milliseconds())
+    }
+}
+
+/// Serialize a duration as a human-readable string, like `1h30m`
+///
+/// This is the same format the command line accepts: hours, minutes,
+/// seconds, and milliseconds, each section optional, like `25m`, `22m30s`,
+/// or `500ms`.
+/// For backward compatibility, a bare integer (not a string) is still
+/// read as a count of seconds, so `1500` and `"25m"` are the same duration.
+/// A string holding only a number is rejected when deserializing, since it
+/// would be unclear whether it means seconds or minutes. [`parse`] reads it
+/// as minutes, like the command line does.
+pub mod human {
+    use std::fmt::{self, Write};
+
+    use chrono::TimeDelta;
+    use regex::Regex;
+    use serde::{de, Deserializer, Serializer};
+
+    const FORMAT_HELP: &str = "Failed to parse duration string, format is <HOURS>h<MINUTES>m<SECONDS>s<MILLISECONDS>ms (each section is optional, decimals are allowed, and a bare number is minutes) example: 22m30s";
+
+    /// Deserialize a human-readable duration string or an integer count of seconds
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<TimeDelta, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(HumanVisitor {
+            milliseconds: false,
+        })
+    }
+
+    /// Serialize a duration as a human-readable string
+    pub fn serialize<S>(delta: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(&format(delta))
+    }
+
+    /// Describe a human-readable duration in a JSON Schema
+    pub fn schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+        schemars::json_schema!({
+            "type": ["string", "integer"],
+            "examples": ["25m", "1h30m", 1500],
+        })
+    }
+
+    /// Parse a human-readable duration, like `25m` or `1h30m`
+    pub fn parse(input: &str) -> Result<TimeDelta, String> {
+        let input = input.trim();
+
+        let seconds = if let Ok(minutes) = input.parse::<f64>() {
+            minutes * 60.0
+        } else {
+            let re = Regex::new(
+                r"^(?:([0-9]+(?:\.[0-9]+)?)h)?(?:([0-9]+(?:\.[0-9]+)?)m)?(?:([0-9]+(?:\.[0-9]+)?)s)?(?:([0-9]+(?:\.[0-9]+)?)ms)?$",
+            )
+            .unwrap();
+            let caps = re
+                .captures(input)
+                .filter(|_| !input.is_empty())
+                .ok_or(FORMAT_HELP)?;
+
+            let part = |i: usize| -> f64 {
+                caps.get(i)
+                    .map_or(0.0, |c| c.as_str().parse().unwrap_or(f64::NAN))
+            };
+
+            part(1) * 3600.0 + part(2) * 60.0 + part(3) + part(4) / 1000.0
+        };
+
+        if !seconds.is_finite() || seconds < 0.0 {
+            return Err(FORMAT_HELP.to_string());
+        }
+
+        TimeDelta::try_milliseconds((seconds * 1000.0).round() as i64)
+            .ok_or_else(|| "Duration is too long".to_string())
+    }
+
+    /// Format a duration as a human-readable string, like `1h30m`
+    pub fn format(duration: &TimeDelta) -> String {
+        if duration.num_milliseconds() == 0 {
+            return "0s".to_string();
+        }
+
+        let hours = duration.num_seconds() / 3600;
+        let minutes = (duration.num_seconds() / 60) - (hours * 60);
+        let seconds = duration.num_seconds() % 60;
+
+        let mut acc = String::new();
+
+        if hours > 0 {
+            write!(acc, "{}h", hours).unwrap();
+        }
+
+        if minutes > 0 {
+            write!(acc, "{}m", minutes).unwrap();
+        }
+
+        if seconds > 0 {
+            write!(acc, "{}s", seconds).unwrap();
+        }
+
+        let milliseconds = duration.num_milliseconds() % 1000;
+        if milliseconds > 0 {
+            write!(acc, "{}ms", milliseconds).unwrap();
+        }
+
+        acc
+    }
+
+    /// Like [`human`](super::human), but a bare integer is a count of milliseconds
+    ///
+    /// This is for fields that used to be written in milliseconds, so their
+    /// existing values keep working.
+    pub mod milliseconds {
+        use chrono::TimeDelta;
+        use serde::{Deserializer, Serializer};
+
+        use super::HumanVisitor;
+
+        /// Deserialize a human-readable duration string or an integer count of milliseconds
+        pub fn deserialize<'de, D>(deserializer: D) -> Result<TimeDelta, D::Error>
+        where
+            D: Deserializer<'de>,
+        {
+            deserializer.deserialize_any(HumanVisitor { milliseconds: true })
+        }
+
+        /// Serialize a duration as a human-readable string
+        pub fn serialize<S>(delta: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            super::serialize(delta, serializer)
+        }
+
+        /// Describe a human-readable duration in a JSON Schema
+        pub fn schema(_generator: &mut schemars::SchemaGenerator) -> schemars::Schema {
+            schemars::json_schema!({
+                "type": ["string", "integer"],
+                "examples": ["1s", "500ms", 1000],
+            })
+        }
+    }
+
+    /// Reads a duration string, or an integer in the unit given by `milliseconds`
+    struct HumanVisitor {
+        milliseconds: bool,
+    }
+
+    impl de::Visitor<'_> for HumanVisitor {
+        type Value = TimeDelta;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            if self.milliseconds {
+                formatter.write_str("a duration like \"1s\" or an integer count of milliseconds")
+            } else {
+                formatter.write_str("a duration like \"25m\" or an integer count of seconds")
+            }
+        }
+
+        fn visit_i64<E: de::Error>(self, count: i64) -> Result<TimeDelta, E> {
+            let (delta, unit) = if self.milliseconds {
+                (TimeDelta::try_milliseconds(count), "milliseconds")
+            } else {
+                (TimeDelta::try_seconds(count), "seconds")
+            };
+
+            delta.ok_or_else(|| E::custom(format!("duration of {count} {unit} is out of range")))
+        }
+
+        fn visit_u64<E: de::Error>(self, count: u64) -> Result<TimeDelta, E> {
+            let count = i64::try_from(count)
+                .map_err(|_| E::custom(format!("duration of {count} is out of range")))?;
+            self.visit_i64(count)
+        }
+
+        fn visit_str<E: de::Error>(self, s: &str) -> Result<TimeDelta, E> {
+            if s.trim().parse::<f64>().is_ok() {
+                return Err(E::custom(format!(
+                    "duration \"{s}\" needs a unit, like \"{s}m\" or \"{s}s\""
+                )));
+            }
+
+            parse(s).map_err(E::custom)
+        }
+    }
+}
+
+/// Serialize a duration as an ISO 8601 duration string, like `PT1500S`
+///
+/// Durations are always written as a whole number of seconds.
+/// When reading, the day, hour, minute, and second designators are accepted,
+/// so `P1DT2H`, `PT25M`, and `PT1M30S` are all valid.
+/// Years, months, weeks, and fractional values are rejected because they
+/// do not describe a fixed length of time. Designators have to be in
+/// descending order, each at most once.
+/// Negative durations are written with a leading minus sign, like `-PT30S`,
+/// which is read back as well.
+pub mod iso8601 {
+    use chrono::TimeDelta;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    /// Deserialize an ISO 8601 duration string
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<TimeDelta, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s: String = Deserialize::deserialize(deserializer)?;
+        parse(&s).map_err(D::Error::custom)
+    }
+
+    /// Serialize a duration as an ISO 8601 duration string
+    pub fn serialize<S>(delta: &TimeDelta, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let seconds = delta.num_seconds();
+        let sign = if seconds < 0 { "-" } else { "" };
+
+        serializer.serialize_str(&format!("{}PT{}S", sign, seconds.unsigned_abs()))
+    }
+
+    fn parse(input: &str) -> Result<TimeDelta, String> {
+        let invalid = || format!("invalid ISO 8601 duration \"{input}\"");
+
+        let (negative, rest) = match input.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, input),
+        };
+        let rest = rest.strip_prefix('P').ok_or_else(invalid)?;
+        let (date_part, time_part) = match rest.split_once('T') {
+            Some((date, time)) if !time.is_empty() => (date, Some(time)),
+            Some(_) => return Err(invalid()),
+            None => (rest, None),
+        };
+
+        if date_part.is_empty() && time_part.is_none() {
+            return Err(invalid());
+        }
+
+        let mut seconds = sum(date_part, &[('D', 86400)]).ok_or_else(invalid)?;
+
+        if let Some(time_part) = time_part {
+            let time = sum(time_part, &[('H', 3600), ('M', 60), ('S', 1)]).ok_or_else(invalid)?;
+            seconds = seconds.checked_add(time).ok_or_else(invalid)?;
+        }
+
+        if negative {
+            seconds = -seconds;
+        }
+
+        TimeDelta::try_seconds(seconds).ok_or_else(invalid)
+    }
+
+    /// Add up the components of `input` in seconds
+    ///
+    /// `units` lists the designators that are allowed, from largest to smallest,
+    /// with the number of seconds in each. Returns `None` if a designator is not
+    /// allowed, repeated, or out of order.
+    fn sum(input: &str, units: &[(char, i64)]) -> Option<i64> {
+        let mut seconds: i64 = 0;
+        let mut remaining = units;
+
+        for (value, designator) in components(input)? {
+            let position = remaining.iter().position(|(d, _)| *d == designator)?;
+            let unit = remaining[position].1;
+            remaining = &remaining[position + 1..];
+
+            seconds = add(seconds, value, unit)?;
+        }
+
+        Some(seconds)
+    }
+
+    fn components(input: &str) -> Option<Vec<(i64, char)>> {
+        let mut components = Vec::new();
+        let mut digits = String::new();
+
+        for c in input.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+            } else if digits.is_empty() {
+                return None;
+            } else {
+                components.push((digits.parse().ok()?, c));
+                digits.clear();
+            }
+        }
+
+        if digits.is_empty() {
+            Some(components)
+        } else {
+            None
+        }
+    }
+
+    fn add(acc: i64, value: i64, unit: i64) -> Option<i64> {
+        acc.checked_add(value.checked_mul(unit)?)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use chrono::TimeDelta;
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Seconds {
+        #[serde(with = "super::seconds")]
+        duration: TimeDelta,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Milliseconds {
+        #[serde(with = "super::milliseconds")]
+        duration: TimeDelta,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Human {
+        #[serde(with = "super::human")]
+        duration: TimeDelta,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Iso {
+        #[serde(with = "super::iso8601")]
+        duration: TimeDelta,
+    }
+
+    #[test]
+    fn seconds_round_trip() {
+        let value = Seconds {
+            duration: TimeDelta::minutes(25),
+        };
+
+        let toml = toml::to_string(&value).unwrap();
+        assert_eq!(toml.trim(), "duration = 1500");
+
+        let parsed: Seconds = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn milliseconds_round_trip() {
+        let value = Milliseconds {
+            duration: TimeDelta::milliseconds(100),
+        };
+
+        let toml = toml::to_string(&value).unwrap();
+        assert_eq!(toml.trim(), "duration = 100");
+
+        let parsed: Milliseconds = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn seconds_out_of_range_is_an_error() {
+        let result: Result<Seconds, _> = toml::from_str(&format!("duration = {}", i64::MAX));
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn human_round_trip() {
+        for (input, expected) in [
+            (r#""25m""#, TimeDelta::minutes(25)),
+            (r#""1h30m""#, TimeDelta::minutes(90)),
+        ] {
+            let parsed: Human = toml::from_str(&format!("duration = {input}")).unwrap();
+            assert_eq!(parsed.duration, expected);
+
+            let toml = toml::to_string(&parsed).unwrap();
+            assert_eq!(toml.trim(), format!("duration = {input}"));
+        }
+    }
+
+    #[test]
+    fn human_accepts_bare_seconds() {
+        let parsed: Human = toml::from_str("duration = 1500").unwrap();
+        assert_eq!(parsed.duration, TimeDelta::minutes(25));
+
+        let toml = toml::to_string(&parsed).unwrap();
+        assert_eq!(toml.trim(), r#"duration = "25m""#);
+    }
+
+    #[test]
+    fn human_milliseconds() {
+        #[derive(Debug, PartialEq, Deserialize, Serialize)]
+        struct Accuracy {
+            #[serde(with = "super::human::milliseconds")]
+            duration: TimeDelta,
+        }
+
+        for (input, expected) in [
+            ("2500", TimeDelta::milliseconds(2500)),
+            (r#""2500ms""#, TimeDelta::milliseconds(2500)),
+            (r#""2s500ms""#, TimeDelta::milliseconds(2500)),
+            (r#""1s""#, TimeDelta::seconds(1)),
+        ] {
+            let parsed: Accuracy = toml::from_str(&format!("duration = {input}")).unwrap();
+            assert_eq!(parsed.duration, expected, "for {input}");
+        }
+
+        let toml = toml::to_string(&Accuracy {
+            duration: TimeDelta::milliseconds(2500),
+        })
+        .unwrap();
+        assert_eq!(toml.trim(), r#"duration = "2s500ms""#);
+
+        let parsed: Human = toml::from_str(r#"duration = "1m500ms""#).unwrap();
+        assert_eq!(parsed.duration, TimeDelta::milliseconds(60_500));
+        let parsed: Human = toml::from_str("duration = 2").unwrap();
+        assert_eq!(parsed.duration, TimeDelta::seconds(2));
+    }
+
+    #[test]
+    fn human_requires_units_in_strings() {
+        let err = toml::from_str::<Human>(r#"duration = "25""#).unwrap_err();
+        assert!(
+            err.message()
+                .contains(r#"duration "25" needs a unit, like "25m" or "25s""#),
+            "unexpected error: {err}"
+        );
+
+        assert_eq!(super::human::parse("25"), Ok(TimeDelta::minutes(25)));
+    }
+
+    #[test]
+    fn human_rejects_malformed_input() {
+        for input in [r#""a while""#, r#""-5m""#, r#""1h30""#, "true"] {
+            let result: Result<Human, _> = toml::from_str(&format!("duration = {input}"));
+
+            assert!(result.is_err(), "expected {input:?} to be rejected");
+        }
+    }
+
+    #[test]
+    fn iso_round_trip() {
+        let value = Iso {
+            duration: TimeDelta::minutes(25),
+        };
+
+        let toml = toml::to_string(&value).unwrap();
+        assert_eq!(toml.trim(), r#"duration = "PT1500S""#);
+
+        let parsed: Iso = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn iso_parses_all_designators() {
+        let parsed: Iso = toml::from_str(r#"duration = "P1DT2H3M4S""#).unwrap();
+
+        assert_eq!(parsed.duration, TimeDelta::seconds(86400 + 7200 + 180 + 4));
+    }
+
+    #[test]
+    fn iso_round_trips_negative_durations() {
+        let value = Iso {
+            duration: TimeDelta::seconds(-5),
+        };
+
+        let toml = toml::to_string(&value).unwrap();
+        assert_eq!(toml.trim(), r#"duration = "-PT5S""#);
+
+        let parsed: Iso = toml::from_str(&toml).unwrap();
+        assert_eq!(parsed, value);
+    }
+
+    #[test]
+    fn iso_rejects_malformed_input() {
+        for input in [
+            "1500", "PT", "P", "PT1.5M", "P1Y", "PT25", "PTM", "PT5S3M", "PT1M1M", "P1D1D",
+            "PT-5S", "--PT5S",
+        ] {
+            let result: Result<Iso, _> = toml::from_str(&format!(r#"duration = "{input}""#));
+
+            assert!(result.is_err(), "expected {input:?} to be rejected");
+        }
+    }
+}
@@ -0,0 +1,42 @@
+#[doc(hidden)]
+pub mod seconds {
+    use chrono::TimeDelta;
+    use serde::Serializer;
+
+    pub fn serialize<S>(delta: &Option<TimeDelta>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match delta {
+            Some(ref delta) => serializer.serialize_some(&delta.num_seconds()),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub mod human {
+    use chrono::TimeDelta;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize, Serialize)]
+    struct Human(#[serde(with = "crate::time::duration::human")] TimeDelta);
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<TimeDelta>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let human: Option<Human> = Deserialize::deserialize(deserializer)?;
+        Ok(human.map(|Human(delta)| delta))
+    }
+
+    pub fn serialize<S>(delta: &Option<TimeDelta>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match delta {
+            Some(delta) => serializer.serialize_some(&Human(*delta)),
+            None => serializer.serialize_none(),
+        }
+    }
+}
@@ -2,19 +2,87 @@
 pub mod datetime;
 #[doc(hidden)]
 pub mod datetimeopt;
-#[doc(hidden)]
 pub mod duration;
+#[doc(hidden)]
+pub mod durationopt;
 
 use chrono::{prelude::*, TimeDelta};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
+/// A source of the current time
+///
+/// Library functions that need to know the current time take a `Clock`,
+/// so that callers can control what time it is.
+pub trait Clock {
+    /// Get the current time
+    fn now(&self) -> DateTime<Local>;
+}
+
+/// A [`Clock`] that reads the system's wall clock
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+}
+
+/// A [`Clock`] that is stopped at a single point in time
+#[derive(Clone, Copy, Debug)]
+pub struct FixedClock(pub DateTime<Local>);
+
+impl Clock for FixedClock {
+    fn now(&self) -> DateTime<Local> {
+        self.0
+    }
+}
+
 /// Like a kitchen timer
-#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Timer {
     #[serde(with = "crate::time::datetime::unix")]
+    #[schemars(with = "i64")]
     started_at: DateTime<Local>,
     #[serde(with = "crate::time::duration::seconds")]
+    #[schemars(with = "i64")]
     duration: TimeDelta,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::time::datetimeopt::unix"
+    )]
+    #[schemars(with = "Option<i64>")]
+    last_notified_at: Option<DateTime<Local>>,
+    /// The last time a periodic tick saw this timer, to tell when the computer was suspended
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::time::datetimeopt::unix"
+    )]
+    #[schemars(with = "Option<i64>")]
+    last_checked_at: Option<DateTime<Local>>,
+    /// When the timer was paused, like when the user went idle
+    ///
+    /// A paused timer stops counting at this time until it is resumed.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "crate::time::datetimeopt::unix"
+    )]
+    #[schemars(with = "Option<i64>")]
+    paused_at: Option<DateTime<Local>>,
+    /// Total time the timer spent paused before it was last resumed
+    ///
+    /// This moves the timer's end later without changing when it started.
+    #[serde(
+        default,
+        skip_serializing_if = "TimeDelta::is_zero",
+        with = "crate::time::duration::seconds"
+    )]
+    #[schemars(with = "i64")]
+    paused_for: TimeDelta,
 }
 
 impl Timer {
@@ -23,6 +91,10 @@ impl Timer {
         Self {
             started_at,
             duration,
+            last_notified_at: None,
+            last_checked_at: None,
+            paused_at: None,
+            paused_for: TimeDelta::zero(),
         }
     }
 
@@ -32,8 +104,10 @@ impl Timer {
     }
 
     /// Get the time this timer ends at
+    ///
+    /// Time the timer spent paused is added on to the end.
     pub fn ends_at(&self) -> DateTime<Local> {
-        self.started_at + self.duration
+        self.started_at + self.paused_for + self.duration
     }
 
     /// Get the length of time that this timer was set for
@@ -42,8 +116,11 @@ impl Timer {
     }
 
     /// Get the amount of time that has passed since this timer started
+    ///
+    /// Time stops passing while the timer is paused.
     pub fn elapsed(&self, now: DateTime<Local>) -> TimeDelta {
-        (now - self.started_at).clamp(TimeDelta::zero(), self.duration)
+        (self.counted_until(now) - self.started_at - self.paused_for)
+            .clamp(TimeDelta::zero(), self.duration)
     }
 
     /// Get the amount of time left on this timer
@@ -51,8 +128,133 @@ impl Timer {
         (self.duration - self.elapsed(now)).clamp(TimeDelta::zero(), self.duration)
     }
 
+    /// Get the amount of time that has passed since this timer ran out
+    ///
+    /// Returns zero if the timer is not done yet.
+    pub fn overtime(&self, now: DateTime<Local>) -> TimeDelta {
+        (self.counted_until(now) - self.ends_at()).max(TimeDelta::zero())
+    }
+
+    /// Get the fraction of this timer that has elapsed, from 0 to 1
+    ///
+    /// A timer with no duration is always complete.
+    pub fn progress_ratio(&self, now: DateTime<Local>) -> f64 {
+        let duration = self.duration.num_milliseconds();
+        if duration <= 0 {
+            return 1.0;
+        }
+
+        (self.elapsed(now).num_milliseconds() as f64 / duration as f64).clamp(0.0, 1.0)
+    }
+
+    /// Get the percent of this timer that has elapsed, from 0 to 100
+    ///
+    /// Rounds down, so a timer only reaches 100 once it is done.
+    pub fn percent(&self, now: DateTime<Local>) -> u8 {
+        (self.progress_ratio(now) * 100.0).floor() as u8
+    }
+
     /// Check if this timer's duration has run out
     pub fn done(&self, now: DateTime<Local>) -> bool {
-        now > self.ends_at()
+        self.counted_until(now) > self.ends_at()
+    }
+
+    /// Treat `paused` as time this timer was stopped, moving its end later
+    pub fn pause_for(&mut self, paused: TimeDelta) {
+        self.paused_for += paused;
+    }
+
+    /// Get the total time this timer spent paused, not counting a pause still going on
+    pub fn paused_for(&self) -> TimeDelta {
+        self.paused_for
+    }
+
+    /// Get the time this timer was paused at, if it is paused
+    pub fn paused_at(&self) -> Option<DateTime<Local>> {
+        self.paused_at
+    }
+
+    /// Mark this timer as paused since `at`
+    ///
+    /// The timer keeps its end until it is resumed.
+    pub fn pause(&mut self, at: DateTime<Local>) {
+        self.paused_at = Some(at);
+    }
+
+    /// Resume a paused timer at `at`, moving its end later by the time it was paused
+    pub fn resume(&mut self, at: DateTime<Local>) {
+        if let Some(paused_at) = self.paused_at.take() {
+            self.pause_for((at - paused_at).max(TimeDelta::zero()));
+        }
+    }
+
+    /// Get the last time a reminder was sent that this timer ran out
+    pub fn last_notified_at(&self) -> Option<DateTime<Local>> {
+        self.last_notified_at
+    }
+
+    /// Record that a reminder was sent that this timer ran out
+    pub fn set_last_notified_at(&mut self, at: DateTime<Local>) {
+        self.last_notified_at = Some(at);
+    }
+
+    /// Get the last time a periodic tick saw this timer
+    pub fn last_checked_at(&self) -> Option<DateTime<Local>> {
+        self.last_checked_at
+    }
+
+    /// Record that a periodic tick saw this timer
+    pub fn set_last_checked_at(&mut self, at: DateTime<Local>) {
+        self.last_checked_at = Some(at);
+    }
+
+    /// Check if it's been at least `interval` since the last reminder about this timer
+    ///
+    /// This is always true if no reminder has been sent yet. It does not check
+    /// if the timer has run out.
+    pub fn reminder_due(&self, now: DateTime<Local>, interval: TimeDelta) -> bool {
+        self.last_notified_at
+            .is_none_or(|last_notified_at| now - last_notified_at >= interval)
+    }
+
+    /// Check if this timer's duration has run out, or will within `grace`
+    ///
+    /// Scheduled checks can fire slightly before a timer ends,
+    /// so they should use this instead of [`Timer::done`] to avoid
+    /// leaving a timer running after its check has already happened.
+    pub fn done_within(&self, now: DateTime<Local>, grace: TimeDelta) -> bool {
+        self.counted_until(now + grace) >= self.ends_at()
+    }
+
+    /// Get the time this timer has counted up to, which is when it was paused if it is
+    fn counted_until(&self, now: DateTime<Local>) -> DateTime<Local> {
+        match self.paused_at {
+            Some(paused_at) => now.min(paused_at),
+            None => now,
+        }
+    }
+}
+
+/// Describe how long ago something happened, like "5m ago" or "yesterday"
+///
+/// `delta` is the time since the event. Anything less than a minute ago,
+/// including events in the future, is "just now".
+pub fn humanize_ago(delta: TimeDelta) -> String {
+    let minutes = delta.num_minutes();
+    let hours = delta.num_hours();
+    let days = delta.num_days();
+
+    if minutes < 1 {
+        "just now".to_string()
+    } else if hours < 1 {
+        format!("{}m ago", minutes)
+    } else if days < 1 {
+        format!("{}h ago", hours)
+    } else if days < 2 {
+        "yesterday".to_string()
+    } else if days < 14 {
+        format!("{}d ago", days)
+    } else {
+        format!("{}w ago", days / 7)
     }
 }